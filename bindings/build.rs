@@ -4,12 +4,21 @@ fn main() {
             Foundation::{
                 IReference,
                 IAsyncOperation,
-                TypedEventHandler
+                TypedEventHandler,
+                Collections::IVectorView,
+                TimeSpan,
+                DateTime,
             },
             Media::{
                 MediaPlaybackType,
                 Control::*,
             },
+            Storage::Streams::{
+                DataReader,
+                DataReaderLoadOperation,
+                IRandomAccessStreamReference,
+                IRandomAccessStreamWithContentType,
+            },
         }
     };
 }
@@ -0,0 +1,115 @@
+use super::Anim;
+use crate::{frame::Frame, text};
+
+const BORDER: [u8; 4] = [0x20, 0x20, 0x28, 0xff];
+const FILL: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const TEXT: [u8; 4] = [0x20, 0x20, 0x28, 0xff];
+// Pixels between the border and the text
+const PADDING: u32 = 2;
+// Rows below the box taken up by the tail
+const TAIL: u32 = 3;
+const MAX_LINES: usize = 3;
+
+/**
+ * Speech bubble with text in the built-in font and a tail pointing
+ * down at whoever is talking
+ *
+ * Drawn once when created, so make a new one to change the text
+ */
+pub struct Bubble {
+    pixels: Frame,
+}
+
+impl Bubble {
+    /**
+     * Wraps the text to fit `max_width` pixels of text per line
+     */
+    pub fn new(text: &str, max_width: u32) -> Bubble {
+        let lines = text::wrap(text, max_width, MAX_LINES);
+        let line_height = text::GLYPH_HEIGHT + text::SPACING;
+        let text_width = lines
+            .iter()
+            .map(|line| text::measure(line).0)
+            .max()
+            .unwrap_or(0);
+        let text_height = (lines.len() as u32 * line_height).saturating_sub(text::SPACING);
+        let inset = PADDING + 1;
+        let box_width = text_width + inset * 2;
+        let box_height = text_height + inset * 2;
+
+        let mut pixels = Frame::new(box_width, box_height + TAIL);
+        {
+            let mut set = |x: u32, y: u32, color: [u8; 4]| {
+                let i = ((y * box_width + x) * 4) as usize;
+                pixels.get_mut()[i..i + 4].copy_from_slice(&color);
+            };
+            for y in 0..box_height {
+                for x in 0..box_width {
+                    let edge_x = x == 0 || x == box_width - 1;
+                    let edge_y = y == 0 || y == box_height - 1;
+                    // Corners left out to round it off
+                    if edge_x && edge_y {
+                        continue;
+                    }
+                    set(x, y, if edge_x || edge_y { BORDER } else { FILL });
+                }
+            }
+            // Tail narrowing down from the middle of the bottom edge
+            let middle = box_width / 2;
+            for row in 0..TAIL {
+                let y = box_height - 1 + row;
+                let half = TAIL - row;
+                for x in middle.saturating_sub(half)..=(middle + half).min(box_width - 1) {
+                    let edge = x + half == middle || x == middle + half || row == TAIL - 1;
+                    set(x, y, if edge { BORDER } else { FILL });
+                }
+            }
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let y = inset + i as u32 * line_height;
+            text::draw(&mut pixels, line, inset as i32, y as i32, TEXT);
+        }
+        Bubble { pixels }
+    }
+}
+
+impl Anim for Bubble {
+    fn width(&self) -> u32 {
+        self.pixels.width
+    }
+    fn height(&self) -> u32 {
+        self.pixels.height
+    }
+    fn frames(&self) -> u32 {
+        1
+    }
+    fn fps(&self) -> u32 {
+        1
+    }
+
+    /**
+     * Never flipped, mirrored text is no use to anyone
+     */
+    fn draw(&self, _frame: u32, _flipped: bool, buffer: &mut Frame, x: i32, y: i32) {
+        let (buffer_width, buffer_height) = buffer.size();
+        for bubble_y in 0..self.pixels.height {
+            let buffer_y = y + bubble_y as i32;
+            if buffer_y < 0 || buffer_y >= buffer_height as i32 {
+                continue;
+            }
+            for bubble_x in 0..self.pixels.width {
+                let buffer_x = x + bubble_x as i32;
+                if buffer_x < 0 || buffer_x >= buffer_width as i32 {
+                    continue;
+                }
+                let i = ((bubble_y * self.pixels.width + bubble_x) * 4) as usize;
+                let pixel = &self.pixels.buffer[i..i + 4];
+                if pixel[3] == 0 {
+                    continue;
+                }
+                let j = ((buffer_y as u32 * buffer_width + buffer_x as u32) * 4) as usize;
+                buffer.get_mut()[j..j + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+}
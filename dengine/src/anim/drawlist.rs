@@ -0,0 +1,51 @@
+use super::Sprite;
+use crate::frame::Frame;
+
+/**
+ * Sprites to draw into one frame this tick
+ *
+ * Drawn back to front by `z`, sprites with the same `z` are drawn in
+ * the order they were pushed
+ */
+pub struct DrawList<'a> {
+    sprites: Vec<&'a Sprite>,
+}
+
+impl<'a> DrawList<'a> {
+    pub fn new() -> DrawList<'a> {
+        DrawList {
+            sprites: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, sprite: &'a Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    /**
+     * Draw every visible sprite, `to_frame` maps a sprite's world
+     * position to frame pixel coordinates
+     */
+    pub fn draw<F>(mut self, buffer: &mut Frame, to_frame: F)
+    where
+        F: Fn(f32, f32) -> (i32, i32),
+    {
+        self.sprites.sort_by_key(|sprite| sprite.z);
+        for sprite in self.sprites.iter().filter(|sprite| sprite.visible) {
+            let (x, y) = to_frame(sprite.x, sprite.y);
+            sprite.draw(buffer, x, y);
+        }
+    }
+}
+
+impl<'a> Default for DrawList<'a> {
+    fn default() -> Self {
+        DrawList::new()
+    }
+}
+
+impl<'a> Extend<&'a Sprite> for DrawList<'a> {
+    fn extend<I: IntoIterator<Item = &'a Sprite>>(&mut self, iter: I) {
+        self.sprites.extend(iter);
+    }
+}
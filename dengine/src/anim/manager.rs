@@ -1,19 +1,15 @@
-use crate::{
-    anim::{Anim, AnimSpritesheet},
-    dwindow::Frame,
-};
-use std::{cell::RefCell, collections::HashMap, default::Default, rc::Rc};
-
-struct AnimState {
-    pub current_anim: Option<Rc<Box<dyn Anim>>>,
-    frame: u32,
-    elapsed: f32,
-    flipped: bool,
-}
+use crate::anim::{Anim, AnimSpritesheet, Pose};
+use std::{collections::HashMap, default::Default, path::Path, sync::Arc};
 
+/**
+ * Library of every registered animation
+ *
+ * Playback state lives in each `Playback`, so any number of sprites can
+ * play from the same manager at once. Nothing in here changes after
+ * registration, so wrap it in an `Arc` to share it between threads.
+ */
 pub struct AnimManager {
-    anims: HashMap<String, Rc<Box<dyn Anim>>>,
-    state: RefCell<AnimState>,
+    anims: HashMap<String, Arc<dyn Anim>>,
 }
 
 #[derive(Default)]
@@ -47,9 +43,9 @@ impl<'a> AnimHandle<'a> {
         self
     }
 
-    pub fn import(mut self, path: &str) {
+    pub fn import<P: AsRef<Path>>(mut self, path: P) {
         if let Some(manager) = self.manager.take() {
-            manager.register_file_handle(path, self);
+            manager.register_file_handle(path.as_ref(), self);
         } else {
             panic!("bad handle");
         }
@@ -60,12 +56,6 @@ impl AnimManager {
     pub fn new() -> AnimManager {
         AnimManager {
             anims: HashMap::new(),
-            state: RefCell::new(AnimState {
-                current_anim: None,
-                frame: 0,
-                elapsed: 0.0,
-                flipped: false,
-            }),
         }
     }
 
@@ -77,46 +67,46 @@ impl AnimManager {
         }
     }
 
-    fn register_file_handle(&mut self, path: &str, handle: AnimHandle) {
-        let name = handle.name.clone();
-        let new_anim = AnimSpritesheet::from_handle(path, handle);
-        self.anims.insert(name, Rc::new(Box::new(new_anim)));
+    /**
+     * Register another name for an anim that's already registered,
+     * sharing its frames instead of importing the sheet again
+     */
+    pub fn alias(&mut self, name: &str, existing: &str) {
+        match self.get(existing) {
+            Some(anim) => {
+                self.anims.insert(String::from(name), anim);
+            }
+            None => panic!("can't alias unknown anim `{}`", existing),
+        }
     }
 
-    pub fn set_anim(&self, name: &str) {
-        let mut state = self.state.borrow_mut();
-        state.frame = 0;
-        if let Some(anim) = self.anims.get(name) {
-            state.current_anim = Some(Rc::clone(anim));
-        } else {
-            panic!("invalid anim name");
+    /**
+     * Register a `Pose` made from the first frame of an anim that's
+     * already registered, moved by each offset in turn
+     */
+    pub fn pose(&mut self, name: &str, existing: &str, offsets: &[(i32, i32)], fps: u32) {
+        match self.get(existing) {
+            Some(anim) => {
+                let pose = Pose::new(&*anim, 0, offsets, fps);
+                self.anims.insert(String::from(name), Arc::new(pose));
+            }
+            None => panic!("can't pose unknown anim `{}`", existing),
         }
     }
 
-    pub fn set_flipped(&self, flipped: bool) {
-        let mut state = self.state.borrow_mut();
-        state.flipped = flipped;
+    fn register_file_handle(&mut self, path: &Path, handle: AnimHandle) {
+        let name = handle.name.clone();
+        let new_anim = AnimSpritesheet::from_handle(path, handle);
+        self.anims.insert(name, Arc::new(new_anim));
     }
 
-    pub fn update(&self, delta: f32) {
-        let mut state = self.state.borrow_mut();
-        state.elapsed += delta;
-        if let Some(anim) = &state.current_anim {
-            let frame_count = anim.frames();
-            if state.elapsed > 1.0 / anim.fps() as f32 {
-                state.elapsed = 0.0;
-                state.frame = (state.frame + 1) % frame_count;
-            }
-        }
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Anim>> {
+        self.anims.get(name).map(Arc::clone)
     }
+}
 
-    pub fn draw(&self, buffer: &mut Frame) {
-        let state = self.state.borrow();
-        if let Some(anim) = &state.current_anim {
-            if buffer.size() != (anim.width(), anim.height()) {
-                buffer.set_size(anim.width(), anim.height());
-            }
-            anim.draw(state.frame, state.flipped, buffer);
-        }
+impl Default for AnimManager {
+    fn default() -> Self {
+        AnimManager::new()
     }
 }
@@ -1,15 +1,37 @@
-use crate::dwindow::Frame;
+use crate::frame::Frame;
 
-pub trait Anim {
+/**
+ * Immutable animation data, shared between threads and sprites
+ */
+pub trait Anim: Send + Sync {
     fn width(&self) -> u32;
     fn height(&self) -> u32;
     fn frames(&self) -> u32;
     fn fps(&self) -> u32;
-    fn draw(&self, frame: u32, flipped: bool, buffer: &mut Frame);
+    /**
+     * Draw a frame with its top-left corner at `x`, `y` in the buffer,
+     * clipping anything outside it and skipping transparent pixels
+     */
+    fn draw(&self, frame: u32, flipped: bool, buffer: &mut Frame, x: i32, y: i32);
 }
 
+mod bubble;
+pub use bubble::Bubble;
+
+mod drawlist;
+pub use drawlist::DrawList;
+
 mod manager;
 pub use manager::{AnimHandle, AnimManager};
 
+mod playback;
+pub use playback::Playback;
+
+mod pose;
+pub use pose::Pose;
+
+mod sprite;
+pub use sprite::Sprite;
+
 mod spritesheet;
 pub use spritesheet::AnimSpritesheet;
@@ -0,0 +1,115 @@
+use super::{Anim, AnimManager};
+use crate::frame::Frame;
+use std::sync::Arc;
+
+/**
+ * Playback state of one anim: current clip, frame and timing
+ *
+ * Owned by whoever is playing it, the clip itself is shared, so this
+ * is cheap to create and can be moved to any thread
+ */
+pub struct Playback {
+    anim: Option<Arc<dyn Anim>>,
+    frame: u32,
+    elapsed: f32,
+    flipped: bool,
+    speed: f32,
+}
+
+impl Playback {
+    pub fn new() -> Playback {
+        Playback {
+            anim: None,
+            frame: 0,
+            elapsed: 0.0,
+            flipped: false,
+            speed: 1.0,
+        }
+    }
+
+    /**
+     * Start playing a clip looked up by name, panics if it doesn't exist
+     */
+    pub fn play(&mut self, anims: &AnimManager, name: &str) {
+        if let Some(anim) = anims.get(name) {
+            self.set_anim(anim);
+        } else {
+            panic!("invalid anim name");
+        }
+    }
+
+    pub fn set_anim(&mut self, anim: Arc<dyn Anim>) {
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.anim = Some(anim);
+    }
+
+    pub fn anim(&self) -> Option<&Arc<dyn Anim>> {
+        self.anim.as_ref()
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn set_flipped(&mut self, flipped: bool) {
+        self.flipped = flipped;
+    }
+
+    pub fn flipped(&self) -> bool {
+        self.flipped
+    }
+
+    /**
+     * Multiplier on the clip's fps, kept across clips
+     */
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /**
+     * Jump to a point in the clip counted in frames, the fraction being
+     * how far into that frame, wrapping past the last one
+     */
+    pub fn set_position(&mut self, position: f32) {
+        if let Some(anim) = &self.anim {
+            let position = position.rem_euclid(anim.frames() as f32);
+            self.frame = (position.floor() as u32).min(anim.frames() - 1);
+            self.elapsed = position.fract() / anim.fps() as f32;
+        }
+    }
+
+    /**
+     * Size of the current clip, None if nothing is playing
+     */
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.anim.as_ref().map(|anim| (anim.width(), anim.height()))
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.elapsed += delta * self.speed;
+        if let Some(anim) = &self.anim {
+            let frame_count = anim.frames();
+            if self.elapsed > 1.0 / anim.fps() as f32 {
+                self.elapsed = 0.0;
+                self.frame = (self.frame + 1) % frame_count;
+            }
+        }
+    }
+
+    pub fn draw(&self, buffer: &mut Frame, x: i32, y: i32) {
+        if let Some(anim) = &self.anim {
+            anim.draw(self.frame, self.flipped, buffer, x, y);
+        }
+    }
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback::new()
+    }
+}
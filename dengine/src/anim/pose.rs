@@ -0,0 +1,77 @@
+use super::Anim;
+use crate::frame::Frame;
+
+/**
+ * One frame of another anim shifted around its box, a frame per
+ * offset, for moves like stretching up or sinking down that don't have
+ * a sheet of their own
+ *
+ * Baked once when created, pixels shifted out of the box are cut off
+ */
+pub struct Pose {
+    frames: Vec<Frame>,
+    fps: u32,
+}
+
+impl Pose {
+    pub fn new(anim: &dyn Anim, frame: u32, offsets: &[(i32, i32)], fps: u32) -> Pose {
+        let frames = offsets
+            .iter()
+            .map(|&(x, y)| {
+                let mut pixels = Frame::new(anim.width(), anim.height());
+                anim.draw(frame, false, &mut pixels, x, y);
+                pixels
+            })
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            panic!("a pose needs at least one offset");
+        }
+        Pose {
+            frames,
+            fps: fps.max(1),
+        }
+    }
+}
+
+impl Anim for Pose {
+    fn width(&self) -> u32 {
+        self.frames[0].width
+    }
+    fn height(&self) -> u32 {
+        self.frames[0].height
+    }
+    fn frames(&self) -> u32 {
+        self.frames.len() as u32
+    }
+    fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    fn draw(&self, frame: u32, flipped: bool, buffer: &mut Frame, x: i32, y: i32) {
+        let pixels = &self.frames[frame as usize];
+        let (buffer_width, buffer_height) = buffer.size();
+        for pose_y in 0..pixels.height {
+            let buffer_y = y + pose_y as i32;
+            if buffer_y < 0 || buffer_y >= buffer_height as i32 {
+                continue;
+            }
+            for pose_x in 0..pixels.width {
+                let buffer_x = x + pose_x as i32;
+                if buffer_x < 0 || buffer_x >= buffer_width as i32 {
+                    continue;
+                }
+                let pixel_x = match flipped {
+                    false => pose_x,
+                    true => pixels.width - pose_x - 1,
+                };
+                let i = ((pose_y * pixels.width + pixel_x) * 4) as usize;
+                let pixel = &pixels.buffer[i..i + 4];
+                if pixel[3] == 0 {
+                    continue;
+                }
+                let j = ((buffer_y as u32 * buffer_width + buffer_x as u32) * 4) as usize;
+                buffer.get_mut()[j..j + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::{AnimManager, Playback};
+use crate::frame::Frame;
+
+/**
+ * One independently animated entity
+ *
+ * Its own playback state plus where to draw it, while the decoded
+ * sprite sheet is shared with every other sprite playing the same anim
+ */
+pub struct Sprite {
+    pub playback: Playback,
+    pub x: f32,
+    pub y: f32,
+    pub z: i32,
+    pub visible: bool,
+}
+
+impl Sprite {
+    pub fn new() -> Sprite {
+        Sprite {
+            playback: Playback::new(),
+            x: 0.0,
+            y: 0.0,
+            z: 0,
+            visible: true,
+        }
+    }
+
+    pub fn set_anim(&mut self, anims: &AnimManager, name: &str) {
+        self.playback.play(anims, name);
+    }
+
+    pub fn set_flipped(&mut self, flipped: bool) {
+        self.playback.set_flipped(flipped);
+    }
+
+    pub fn flipped(&self) -> bool {
+        self.playback.flipped()
+    }
+
+    /**
+     * Size of the current anim, None if nothing is playing
+     */
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.playback.size()
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.playback.update(delta);
+    }
+
+    pub fn draw(&self, buffer: &mut Frame, x: i32, y: i32) {
+        self.playback.draw(buffer, x, y);
+    }
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Sprite::new()
+    }
+}
@@ -1,6 +1,7 @@
 use super::{Anim, AnimHandle};
-use crate::dwindow::Frame;
+use crate::frame::Frame;
 use image::{io::Reader as ImageReader, Pixel, RgbaImage};
+use std::path::Path;
 
 pub struct AnimSpritesheet {
     spritesheet: RgbaImage,
@@ -11,7 +12,7 @@ pub struct AnimSpritesheet {
 }
 
 impl AnimSpritesheet {
-    pub fn from_handle(path: &str, handle: AnimHandle) -> AnimSpritesheet {
+    pub fn from_handle(path: &Path, handle: AnimHandle) -> AnimSpritesheet {
         let spritesheet = ImageReader::open(path)
             .unwrap()
             .decode()
@@ -86,19 +87,29 @@ impl Anim for AnimSpritesheet {
         self.fps
     }
 
-    fn draw(&self, frame: u32, flipped: bool, buffer: &mut Frame) {
-        for (x, y, pixel) in buffer
-            .get_mut()
-            .chunks_exact_mut(4)
-            .enumerate()
-            .map(|(i, pixel)| (i as u32 % self.width, i as u32 / self.width, pixel))
-        {
-            let x = match flipped {
-                false => x,
-                true => self.width - x - 1,
-            };
-            let anim_pixel = self.get_pixel(x, y, frame);
-            pixel.copy_from_slice(anim_pixel);
+    fn draw(&self, frame: u32, flipped: bool, buffer: &mut Frame, x: i32, y: i32) {
+        let (buffer_width, buffer_height) = buffer.size();
+        for sprite_y in 0..self.height {
+            let buffer_y = y + sprite_y as i32;
+            if buffer_y < 0 || buffer_y >= buffer_height as i32 {
+                continue;
+            }
+            for sprite_x in 0..self.width {
+                let buffer_x = x + sprite_x as i32;
+                if buffer_x < 0 || buffer_x >= buffer_width as i32 {
+                    continue;
+                }
+                let anim_x = match flipped {
+                    false => sprite_x,
+                    true => self.width - sprite_x - 1,
+                };
+                let anim_pixel = self.get_pixel(anim_x, sprite_y, frame);
+                if anim_pixel[3] == 0 {
+                    continue;
+                }
+                let i = ((buffer_y as u32 * buffer_width + buffer_x as u32) * 4) as usize;
+                buffer.get_mut()[i..i + 4].copy_from_slice(anim_pixel);
+            }
         }
     }
 }
@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+
+// Onset strength is measured this many times a second
+const HOPS_PER_SECOND: u32 = 100;
+// Seconds of onsets kept to find the tempo in
+const HISTORY: f32 = 8.0;
+// Seconds of onsets needed before guessing at all
+const MIN_HISTORY: f32 = 3.0;
+// Hops between estimates
+const ESTIMATE_EVERY: usize = 25;
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+// Tempo guesses are weighted towards this, an octave away counts
+// for a lot less, to settle between half and double time
+const PREFERRED_BPM: f32 = 120.0;
+const OCTAVE_WEIGHT: f32 = 1.0;
+// Below this the onsets don't repeat enough to be a beat
+const MIN_CONFIDENCE: f32 = 0.1;
+// Below this mean square a hop counts as silence
+const SILENCE: f32 = 1e-6;
+// Seconds of silent hops before the beat is dropped, so a quiet break
+// or a gap between songs doesn't lose it
+const SILENT_FOR: f32 = 0.5;
+// Cutoff of the low pass picking out kicks and bass
+const BASS_HZ: f32 = 150.0;
+
+/**
+ * Tempo and where in the current beat the audio is
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Beat {
+    pub bpm: f32,
+    /** 0 right on a beat, rising to 1 just before the next */
+    pub phase: f32,
+    /** 0 to 1, how strongly the audio repeats at this tempo */
+    pub confidence: f32,
+}
+
+impl Beat {
+    /**
+     * Seconds from one beat to the next
+     */
+    pub fn period(&self) -> f32 {
+        60.0 / self.bpm
+    }
+
+    /**
+     * The same beat `seconds` later
+     */
+    pub fn advance(&self, seconds: f32) -> Beat {
+        Beat {
+            phase: (self.phase + seconds / self.period()).rem_euclid(1.0),
+            ..*self
+        }
+    }
+}
+
+/**
+ * Finds the beat in a stream of mono samples
+ *
+ * Measures how sharply loudness rises every 10ms, mostly in the bass,
+ * then finds the tempo those onsets repeat at by autocorrelation and
+ * the phase that lines beats up with the strongest of them. Cheap
+ * enough to run on an audio thread.
+ */
+pub struct BeatTracker {
+    sample_rate: u32,
+    hop: usize,
+    // Running sums for the hop being filled
+    filled: usize,
+    energy: f32,
+    bass_energy: f32,
+    bass: f32,
+    bass_alpha: f32,
+    // Log energies of the last hop, to take differences from
+    last: (f32, f32),
+    onsets: VecDeque<f32>,
+    // Silent hops in a row, up to `silent_limit`
+    silent_hops: usize,
+    silent_limit: usize,
+    since_estimate: usize,
+    // Estimate and the number of hops since it was made
+    estimate: Option<(Beat, usize)>,
+}
+
+impl BeatTracker {
+    pub fn new(sample_rate: u32) -> BeatTracker {
+        let sample_rate = sample_rate.max(HOPS_PER_SECOND);
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * BASS_HZ);
+        let dt = 1.0 / sample_rate as f32;
+        let silent_limit = (SILENT_FOR * HOPS_PER_SECOND as f32) as usize;
+        BeatTracker {
+            sample_rate,
+            hop: (sample_rate / HOPS_PER_SECOND) as usize,
+            filled: 0,
+            energy: 0.0,
+            bass_energy: 0.0,
+            bass: 0.0,
+            bass_alpha: dt / (rc + dt),
+            last: (0.0, 0.0),
+            onsets: VecDeque::new(),
+            silent_hops: silent_limit,
+            silent_limit,
+            since_estimate: 0,
+            estimate: None,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /**
+     * Add mono samples, in -1 to 1
+     */
+    pub fn feed(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.bass += self.bass_alpha * (sample - self.bass);
+            self.energy += sample * sample;
+            self.bass_energy += self.bass * self.bass;
+            self.filled += 1;
+            if self.filled == self.hop {
+                self.end_hop();
+            }
+        }
+    }
+
+    /**
+     * Add interleaved samples, mixed down to mono
+     */
+    pub fn feed_interleaved(&mut self, samples: &[f32], channels: usize) {
+        let channels = channels.max(1);
+        let mono = samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect::<Vec<_>>();
+        self.feed(&mono);
+    }
+
+    /**
+     * The latest estimate, as of the last sample fed, None once it's
+     * been silent for a while or anything without a steady beat
+     */
+    pub fn beat(&self) -> Option<Beat> {
+        if self.silent_hops >= self.silent_limit {
+            return None;
+        }
+        let (beat, hops) = self.estimate?;
+        let seconds =
+            (hops as f32 * self.hop as f32 + self.filled as f32) / self.sample_rate as f32;
+        Some(beat.advance(seconds))
+    }
+
+    /**
+     * Forget everything heard, like when the song changes
+     */
+    pub fn reset(&mut self) {
+        *self = BeatTracker::new(self.sample_rate);
+    }
+
+    fn end_hop(&mut self) {
+        let energy = self.energy / self.hop as f32;
+        let bass_energy = self.bass_energy / self.hop as f32;
+        self.filled = 0;
+        self.energy = 0.0;
+        self.bass_energy = 0.0;
+        self.silent_hops = match energy < SILENCE {
+            true => (self.silent_hops + 1).min(self.silent_limit),
+            false => 0,
+        };
+
+        // Rises in log energy, so quiet and loud songs count the same
+        let level = (
+            (1.0 + 1000.0 * energy).ln(),
+            (1.0 + 1000.0 * bass_energy).ln(),
+        );
+        let onset = (level.0 - self.last.0).max(0.0) * 0.5 + (level.1 - self.last.1).max(0.0);
+        self.last = level;
+
+        let capacity = (HISTORY * HOPS_PER_SECOND as f32) as usize;
+        if self.onsets.len() == capacity {
+            self.onsets.pop_front();
+        }
+        self.onsets.push_back(onset);
+        if let Some((_, hops)) = &mut self.estimate {
+            *hops += 1;
+        }
+
+        self.since_estimate += 1;
+        let enough = self.onsets.len() as f32 >= MIN_HISTORY * HOPS_PER_SECOND as f32;
+        if enough && self.since_estimate >= ESTIMATE_EVERY {
+            self.since_estimate = 0;
+            self.estimate = self.estimate_beat().map(|beat| (beat, 0));
+        }
+    }
+
+    fn estimate_beat(&self) -> Option<Beat> {
+        let mean = self.onsets.iter().sum::<f32>() / self.onsets.len() as f32;
+        let onsets = self
+            .onsets
+            .iter()
+            .map(|onset| onset - mean)
+            .collect::<Vec<_>>();
+        let n = onsets.len();
+        let correlate = |lag: usize| {
+            let sum = (lag..n).map(|i| onsets[i] * onsets[i - lag]).sum::<f32>();
+            sum / (n - lag) as f32
+        };
+        let zero = correlate(0);
+        if zero <= 0.0 {
+            return None;
+        }
+
+        let hops_per_minute = 60.0 * HOPS_PER_SECOND as f32;
+        let min_lag = (hops_per_minute / MAX_BPM).floor() as usize;
+        let max_lag = ((hops_per_minute / MIN_BPM).ceil() as usize).min(n / 2);
+        let preferred = hops_per_minute / PREFERRED_BPM;
+        let correlations = (0..=max_lag + 1).map(correlate).collect::<Vec<_>>();
+        let weight = |lag: f32| {
+            let octaves = (lag / preferred).log2() / OCTAVE_WEIGHT;
+            (-0.5 * octaves * octaves).exp()
+        };
+        let best = (min_lag.max(1)..=max_lag).max_by(|a, b| {
+            let a = correlations[*a] * weight(*a as f32);
+            let b = correlations[*b] * weight(*b as f32);
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        let confidence = (correlations[best] / zero).clamp(0.0, 1.0);
+        if confidence < MIN_CONFIDENCE {
+            return None;
+        }
+
+        // Fit a parabola through the peak for a lag between hops
+        let (left, middle, right) = (
+            correlations[best - 1],
+            correlations[best],
+            correlations[best + 1],
+        );
+        let curve = left - 2.0 * middle + right;
+        let offset = match curve.abs() > 0.0 {
+            true => (0.5 * (left - right) / curve).clamp(-0.5, 0.5),
+            false => 0.0,
+        };
+        let period = best as f32 + offset;
+
+        // Phase is whichever offset back from the newest hop lines the
+        // most onset strength up at that period
+        let beats = (n as f32 / period).floor() as usize;
+        let phase_hops = (0..period.ceil() as usize)
+            .max_by(|a, b| {
+                let score = |back: usize| {
+                    (0..beats)
+                        .map(|k| back as f32 + k as f32 * period)
+                        .filter(|back| (back.round() as usize) < n)
+                        .map(|back| onsets[n - 1 - back.round() as usize])
+                        .sum::<f32>()
+                };
+                score(*a)
+                    .partial_cmp(&score(*b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(0);
+
+        Some(Beat {
+            bpm: hops_per_minute / period,
+            phase: (phase_hops as f32 / period).min(1.0).rem_euclid(1.0),
+            confidence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 8000;
+
+    /**
+     * `seconds` of a low thump every half second, 120 bpm, over a
+     * quiet hum
+     */
+    fn clicks(seconds: f32) -> Vec<f32> {
+        let tone =
+            |i: usize, hz: f32| (i as f32 * 2.0 * std::f32::consts::PI * hz / RATE as f32).sin();
+        (0..(seconds * RATE as f32) as usize)
+            .map(|i| match i % (RATE as usize / 2) < RATE as usize / 50 {
+                true => tone(i, 60.0),
+                false => 0.01 * tone(i, 440.0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_a_steady_beat() {
+        let mut tracker = BeatTracker::new(RATE);
+        tracker.feed(&clicks(2.0));
+        assert!(tracker.beat().is_none());
+        tracker.feed(&clicks(6.0));
+        let beat = tracker.beat().unwrap();
+        assert!((beat.bpm - 120.0).abs() < 2.0, "{:?}", beat);
+    }
+
+    #[test]
+    fn stays_locked_to_the_clicks() {
+        // How far apart two phases are, going around the shorter way
+        let apart = |a: f32, b: f32| {
+            let d = (a - b).rem_euclid(1.0);
+            d.min(1.0 - d)
+        };
+        let track = clicks(12.0);
+        let mut tracker = BeatTracker::new(RATE);
+        tracker.feed(&track[..RATE as usize * 6]);
+        // Fed a bit at a time, the phase follows where the next click is
+        let step = RATE as usize / 20;
+        for (i, chunk) in track[RATE as usize * 6..].chunks(step).enumerate() {
+            tracker.feed(chunk);
+            let fed = 6.0 + ((i + 1) * step) as f32 / RATE as f32;
+            let expected = (fed / 0.5).fract();
+            let beat = tracker.beat().unwrap();
+            assert!(
+                apart(beat.phase, expected) < 0.1,
+                "{:?} at {}s, expected phase {}",
+                beat,
+                fed,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn only_a_long_silence_loses_the_beat() {
+        let mut tracker = BeatTracker::new(RATE);
+        tracker.feed(&clicks(8.0));
+        tracker.feed(&vec![0.0; RATE as usize * 3 / 10]);
+        assert!(tracker.beat().is_some());
+        tracker.feed(&vec![0.0; RATE as usize * 3 / 10]);
+        assert!(tracker.beat().is_none());
+        tracker.feed(&clicks(0.1));
+        assert!(tracker.beat().is_some());
+    }
+}
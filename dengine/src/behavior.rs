@@ -0,0 +1,1065 @@
+use crate::{
+    anim::AnimManager,
+    event::DEvent,
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+    utility::{Scores, Selection},
+};
+use serde::Deserialize;
+use std::{collections::HashMap, error, fmt, fs, io, path::Path, sync::Arc};
+
+/**
+ * Target name that pops back to the interrupted state instead of
+ * entering a new one
+ */
+pub const BACK: &str = "back";
+
+/**
+ * Whatever a behavior file drives, usually the game struct itself
+ */
+pub trait Actor: 'static {
+    fn play(&mut self, anim: &str);
+    fn set_flipped(&mut self, flipped: bool);
+    fn x(&self) -> f32;
+    fn set_x(&mut self, x: f32);
+
+    /**
+     * Leftmost and rightmost x a moving state can walk to, None if
+     * there's nowhere to go
+     */
+    fn walk_bounds(&self) -> Option<(f32, f32)>;
+
+    /**
+     * Named condition for `when`, like `media_playing`. Unknown names
+     * should be false, `Behaviors::check_flags` catches them at load.
+     */
+    fn flag(&self, name: &str) -> bool;
+
+    /**
+     * Score for a `next` with `score = "name"`, multiplied into its
+     * weight. Defaults to 1 for actors that don't score anything.
+     */
+    fn score(&self, _name: &str) -> f32 {
+        1.0
+    }
+
+    fn is_clicked(&self, event: &DEvent) -> bool;
+
+    /**
+     * Uniform random number in `[0, 1)`
+     */
+    fn random(&mut self) -> f32;
+
+    /**
+     * Pick among the `next`s that fired, named after the state each
+     * goes to and scored by weight. Defaults to a weighted roll,
+     * override to keep track of decisions.
+     */
+    fn choose(&mut self, mut options: Scores) -> Scores {
+        let roll = self.random();
+        options.pick(Selection::Weighted, roll);
+        options
+    }
+
+    /**
+     * Enter the state written in code that a `code = "name"` state
+     * runs, None if there's no such state. Defaults to none at all.
+     */
+    fn enter_code(&mut self, _sm: &StateMachine<Self>, _name: &str) -> Option<Box<dyn DState<Self>>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum BehaviorError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /** Parsed fine but doesn't make sense, e.g. a `to` naming no state */
+    Invalid(String),
+}
+
+impl fmt::Display for BehaviorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BehaviorError::Io(err) => write!(f, "can't read behaviors: {}", err),
+            BehaviorError::Parse(err) => write!(f, "can't parse behaviors: {}", err),
+            BehaviorError::Invalid(message) => write!(f, "invalid behaviors: {}", message),
+        }
+    }
+}
+
+impl error::Error for BehaviorError {}
+
+impl From<io::Error> for BehaviorError {
+    fn from(err: io::Error) -> Self {
+        BehaviorError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for BehaviorError {
+    fn from(err: toml::de::Error) -> Self {
+        BehaviorError::Parse(err)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BehaviorFile {
+    initial: String,
+    #[serde(default)]
+    anims: HashMap<String, AnimDef>,
+    #[serde(default)]
+    next: Vec<NextDef>,
+    states: HashMap<String, StateDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimDef {
+    sheet: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    frames: Option<u32>,
+    fps: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StateDef {
+    anim: Option<String>,
+    code: Option<String>,
+    flipped: Option<bool>,
+    duration: Option<Range>,
+    speed: Option<f32>,
+    #[serde(default)]
+    next: Vec<NextDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NextDef {
+    to: String,
+    when: String,
+    weight: Option<f32>,
+    score: Option<String>,
+    #[serde(default)]
+    push: bool,
+}
+
+/**
+ * Either `1.5` or `[1.0, 11.0]` for a random pick in between
+ */
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum Range {
+    Fixed(f32),
+    Between(f32, f32),
+}
+
+impl Range {
+    fn pick<T: Actor>(self, client: &mut T) -> f32 {
+        match self {
+            Range::Fixed(value) => value,
+            Range::Between(min, max) => min + client.random() * (max - min),
+        }
+    }
+}
+
+enum Condition {
+    Elapsed,
+    Arrived,
+    Clicked,
+    Done,
+    Flag(String),
+    NotFlag(String),
+}
+
+impl Condition {
+    fn parse(when: &str) -> Condition {
+        match when {
+            "elapsed" => Condition::Elapsed,
+            "arrived" => Condition::Arrived,
+            "clicked" => Condition::Clicked,
+            "done" => Condition::Done,
+            _ => match when.strip_prefix('!') {
+                Some(flag) => Condition::NotFlag(String::from(flag)),
+                None => Condition::Flag(String::from(when)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Elapsed => write!(f, "elapsed"),
+            Condition::Arrived => write!(f, "arrived"),
+            Condition::Clicked => write!(f, "clicked"),
+            Condition::Done => write!(f, "done"),
+            Condition::Flag(flag) => write!(f, "{}", flag),
+            Condition::NotFlag(flag) => write!(f, "!{}", flag),
+        }
+    }
+}
+
+struct Next {
+    to: String,
+    when: Condition,
+    weight: f32,
+    score: Option<String>,
+    push: bool,
+}
+
+impl Next {
+    fn from_def(def: NextDef) -> Next {
+        Next {
+            when: Condition::parse(&def.when),
+            weight: def.weight.unwrap_or(1.0),
+            to: def.to,
+            score: def.score,
+            push: def.push,
+        }
+    }
+}
+
+struct BehaviorDef {
+    anim: Option<String>,
+    code: Option<String>,
+    flipped: Option<bool>,
+    duration: Option<Range>,
+    speed: Option<f32>,
+    next: Vec<Next>,
+}
+
+/**
+ * States loaded from a behavior file
+ *
+ * ```toml
+ * initial = "idle"
+ *
+ * # Optional, for anims not registered in code
+ * [anims.boppin]
+ * sheet = "boppin.png"
+ * width = 32
+ * height = 32
+ * frames = 8
+ * fps = 12
+ *
+ * # Checked in every state except the target itself
+ * [[next]]
+ * to = "dance"
+ * when = "media_playing"
+ *
+ * [states.idle]
+ * anim = "ready"
+ * duration = [1.0, 11.0]
+ *
+ * [[states.idle.next]]
+ * to = "walk"
+ * when = "elapsed"
+ * weight = 3
+ *
+ * [states.walk]
+ * anim = "walking"
+ * speed = 400.0
+ *
+ * [[states.walk.next]]
+ * to = "idle"
+ * when = "arrived"
+ *
+ * # Runs a state written in code instead of playing an anim
+ * [states.curious]
+ * code = "curious"
+ *
+ * [[states.curious.next]]
+ * to = "idle"
+ * when = "done"
+ * ```
+ *
+ * `when` is `elapsed` (after `duration`), `arrived` (a state with a
+ * `speed` reached the random spot it walks to), `done` (the code a
+ * state runs with `Actor::enter_code` tried to leave, the file decides
+ * where to instead), `clicked`, or a flag from `Actor::flag`, negated
+ * with a leading `!`. When several fire at once, one is picked by
+ * `Actor::choose`, at random by `weight` times the actor's `score` for
+ * the name given in `score` if any. `push = true` suspends the current
+ * state instead of leaving it, and `to = "back"` returns to it.
+ */
+pub struct Behaviors {
+    initial: String,
+    anims: HashMap<String, AnimDef>,
+    next: Vec<Next>,
+    states: HashMap<String, BehaviorDef>,
+}
+
+impl Behaviors {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Arc<Behaviors>, BehaviorError> {
+        Behaviors::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Arc<Behaviors>, BehaviorError> {
+        let file: BehaviorFile = toml::from_str(source)?;
+        let behaviors = Behaviors {
+            initial: file.initial,
+            anims: file.anims,
+            next: file.next.into_iter().map(Next::from_def).collect(),
+            states: file
+                .states
+                .into_iter()
+                .map(|(name, def)| {
+                    let state = BehaviorDef {
+                        anim: def.anim,
+                        code: def.code,
+                        flipped: def.flipped,
+                        duration: def.duration,
+                        speed: def.speed,
+                        next: def.next.into_iter().map(Next::from_def).collect(),
+                    };
+                    (name, state)
+                })
+                .collect(),
+        };
+        behaviors.validate()?;
+        Ok(Arc::new(behaviors))
+    }
+
+    fn validate(&self) -> Result<(), BehaviorError> {
+        let invalid = |message: String| Err(BehaviorError::Invalid(message));
+        if !self.states.contains_key(&self.initial) {
+            return invalid(format!("initial state `{}` doesn't exist", self.initial));
+        }
+        if self.states.contains_key(BACK) {
+            return invalid(format!("`{}` is reserved and can't name a state", BACK));
+        }
+        for (name, state) in &self.states {
+            if state.anim.is_some() == state.code.is_some() {
+                return invalid(format!("`{}` needs either an anim or code", name));
+            }
+            if state.code.is_some() && state.speed.is_some() {
+                return invalid(format!("`{}` runs code and can't have a speed", name));
+            }
+        }
+        for (name, anim) in &self.anims {
+            let sizes = [anim.width, anim.height, anim.frames, anim.fps];
+            if sizes.iter().any(Option::is_some) && sizes.iter().any(Option::is_none) {
+                return invalid(format!(
+                    "anim `{}` needs all of width, height, frames and fps, or none",
+                    name
+                ));
+            }
+        }
+        let global = self.next.iter().map(|next| ("every state", None, next));
+        let local = self.states.iter().flat_map(|(name, state)| {
+            state
+                .next
+                .iter()
+                .map(move |next| (name.as_str(), Some(state), next))
+        });
+        for (from, state, next) in global.chain(local) {
+            if next.to != BACK && !self.states.contains_key(&next.to) {
+                return invalid(format!("`{}` goes to missing state `{}`", from, next.to));
+            }
+            if next.weight < 0.0 {
+                return invalid(format!("`{}` has a negative weight", from));
+            }
+            match (&next.when, state) {
+                (Condition::Elapsed, Some(state)) if state.duration.is_none() => {
+                    return invalid(format!("`{}` waits on `elapsed` without a duration", from));
+                }
+                (Condition::Arrived, Some(state)) if state.speed.is_none() => {
+                    return invalid(format!("`{}` waits on `arrived` without a speed", from));
+                }
+                (Condition::Done, Some(state)) if state.code.is_none() => {
+                    return invalid(format!("`{}` waits on `done` without code", from));
+                }
+                (Condition::Done, None) => {
+                    return invalid(String::from("`done` only works in a state that runs code"));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Register every `[anims]` entry with its sheet looked up in `dir`,
+     * then make sure each state's anim exists, either from the file or
+     * registered in code beforehand
+     */
+    pub fn register_anims(&self, dir: &Path, anims: &mut AnimManager) -> Result<(), BehaviorError> {
+        for (name, def) in &self.anims {
+            let sheet = dir.join(&def.sheet);
+            // Importing panics on a bad sheet, so catch the common case here
+            if !sheet.is_file() {
+                return Err(BehaviorError::Invalid(format!(
+                    "anim `{}` sheet `{}` not found",
+                    name,
+                    sheet.display()
+                )));
+            }
+            let mut handle = anims.register(name);
+            if let (Some(width), Some(height), Some(frames), Some(fps)) =
+                (def.width, def.height, def.frames, def.fps)
+            {
+                handle = handle.width(width).height(height).frames(frames).fps(fps);
+            }
+            handle.import(sheet);
+        }
+        for (name, state) in &self.states {
+            if let Some(anim) = &state.anim {
+                if anims.get(anim).is_none() {
+                    return Err(BehaviorError::Invalid(format!(
+                        "state `{}` plays unknown anim `{}`",
+                        name, anim
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Make sure every `code = "name"` names one of the states the actor
+     * knows how to enter
+     */
+    pub fn check_code(&self, known: &[&str]) -> Result<(), BehaviorError> {
+        for (name, state) in &self.states {
+            if let Some(code) = &state.code {
+                if !known.contains(&code.as_str()) {
+                    return Err(BehaviorError::Invalid(format!(
+                        "state `{}` runs unknown code `{}`",
+                        name, code
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Make sure every flag a `when` waits on is one the actor sets, so a
+     * typo doesn't quietly never fire
+     */
+    pub fn check_flags(&self, known: &[&str]) -> Result<(), BehaviorError> {
+        let states = self
+            .states
+            .iter()
+            .flat_map(|(name, state)| state.next.iter().map(move |next| (name.as_str(), next)));
+        let global = self.next.iter().map(|next| ("[[next]]", next));
+        for (from, next) in states.chain(global) {
+            if let Condition::Flag(flag) | Condition::NotFlag(flag) = &next.when {
+                if !known.contains(&flag.as_str()) {
+                    return Err(BehaviorError::Invalid(format!(
+                        "`{}` waits on unknown flag `{}`",
+                        from, flag
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn initial(&self) -> &str {
+        &self.initial
+    }
+
+    /**
+     * Start a machine in the initial state
+     */
+    pub fn init<T: Actor>(self: &Arc<Self>, sm: &StateMachine<T>, client: &mut T) {
+        let behaviors = Arc::clone(self);
+        let initial = self.initial.clone();
+        sm.init_with(client, &self.initial, move |sm, client| {
+            BehaviorState::enter_named(sm, behaviors, initial, client)
+        });
+    }
+
+    /**
+     * Transition into one of the file's states, or back out of a pushed
+     * one for `"back"`
+     */
+    pub fn transition<T: Actor>(self: &Arc<Self>, name: &str, push: bool) -> Transition<T> {
+        if name == BACK {
+            return Transition::pop();
+        }
+        let behaviors = Arc::clone(self);
+        let state = String::from(name);
+        let enter = move |sm: &StateMachine<T>, client: &mut T| {
+            BehaviorState::enter_named(sm, behaviors, state, client)
+        };
+        match push {
+            true => Transition::push_with(name, enter),
+            false => Transition::with(name, enter),
+        }
+    }
+}
+
+/**
+ * Runs one state of a behavior file, entered through `Behaviors`
+ *
+ * Private so nothing can `Transition::to` it without saying which state
+ */
+struct BehaviorState<T> {
+    behaviors: Arc<Behaviors>,
+    name: String,
+    timer: Option<Timer>,
+    destination: Option<f32>,
+    arrived: bool,
+    // The code state it runs, until that asks to leave and it's done
+    code: Option<Box<dyn DState<T>>>,
+    done: bool,
+}
+
+impl<T: Actor> BehaviorState<T> {
+    fn enter_named(
+        sm: &StateMachine<T>,
+        behaviors: Arc<Behaviors>,
+        name: String,
+        client: &mut T,
+    ) -> Box<dyn DState<T>> {
+        let def = &behaviors.states[&name];
+        let timer = def.duration.map(|range| Timer::new(range.pick(client)));
+        let destination = match def.speed {
+            Some(_) => client
+                .walk_bounds()
+                .map(|(left, right)| left + client.random() * (right - left)),
+            None => None,
+        };
+        let code = def
+            .code
+            .as_ref()
+            .and_then(|code| client.enter_code(sm, code));
+        let mut state = BehaviorState {
+            // Nowhere to walk to counts as being there already
+            arrived: def.speed.is_some() && destination.is_none(),
+            // Same for code the actor turned out not to have
+            done: def.code.is_some() && code.is_none(),
+            behaviors: Arc::clone(&behaviors),
+            name,
+            timer,
+            destination,
+            code,
+        };
+        state.start(client);
+        Box::new(state)
+    }
+
+    fn def(&self) -> &BehaviorDef {
+        &self.behaviors.states[&self.name]
+    }
+
+    fn start(&mut self, client: &mut T) {
+        let def = self.def();
+        if let Some(anim) = &def.anim {
+            client.play(anim);
+        }
+        if let Some(destination) = self.destination {
+            client.set_flipped(destination < client.x());
+        } else if let Some(flipped) = def.flipped {
+            client.set_flipped(flipped);
+        }
+    }
+
+    /**
+     * The code state asked to go somewhere, which the file decides
+     * instead, so it's over
+     */
+    fn finish(&mut self, sm: &StateMachine<T>, client: &mut T) {
+        if let Some(mut code) = self.code.take() {
+            code.exit(sm, client);
+        }
+        self.done = true;
+    }
+
+    fn fires(&self, condition: &Condition, client: &T, clicked: bool) -> bool {
+        match condition {
+            Condition::Elapsed => self.timer.as_ref().map_or(false, Timer::finished),
+            Condition::Arrived => self.arrived,
+            Condition::Clicked => clicked,
+            Condition::Done => !clicked && self.done,
+            Condition::Flag(flag) => !clicked && client.flag(flag),
+            Condition::NotFlag(flag) => !clicked && !client.flag(flag),
+        }
+    }
+
+    /**
+     * Pick among every `next` that fires right now, weighted
+     */
+    fn choose(&self, client: &mut T, clicked: bool) -> Option<Transition<T>> {
+        let global = self
+            .behaviors
+            .next
+            .iter()
+            .filter(|next| next.to != self.name);
+        let options = self
+            .def()
+            .next
+            .iter()
+            .chain(global)
+            .filter(|next| self.fires(&next.when, client, clicked))
+            .collect::<Vec<_>>();
+        let scores = Scores::new(options.iter().map(|next| {
+            let score = next.score.as_ref().map_or(1.0, |name| client.score(name));
+            (next.to.as_str(), next.weight * score)
+        }));
+        if scores.iter().all(|(_, score)| score <= 0.0) {
+            return None;
+        }
+        let scores = client.choose(scores);
+        let chosen = options[scores.chosen_index()?];
+        let transition = self.behaviors.transition(&chosen.to, chosen.push);
+        Some(transition.because(format!("{}: {}", chosen.when, scores)))
+    }
+}
+
+impl<T: Actor> DState<T> for BehaviorState<T> {
+    /**
+     * Needs to know which state, so it's only ever entered through
+     * `Behaviors::init` or `Behaviors::transition`
+     */
+    fn enter(_sm: &StateMachine<T>, _client: &mut T) -> Box<dyn DState<T>> {
+        unreachable!("behavior states are entered through Behaviors")
+    }
+
+    fn update(
+        &mut self,
+        sm: &StateMachine<T>,
+        client: &mut T,
+        delta: f32,
+    ) -> Option<Transition<T>> {
+        if let Some(timer) = &mut self.timer {
+            timer.tick(delta);
+        }
+        if let (Some(destination), Some(speed)) = (self.destination, self.def().speed) {
+            if !self.arrived {
+                let x = client.x();
+                let step = speed * delta;
+                if (destination - x).abs() <= step {
+                    client.set_x(destination);
+                    self.arrived = true;
+                } else {
+                    client.set_x(x + step * (destination - x).signum());
+                }
+            }
+        }
+        if let Some(code) = &mut self.code {
+            if code.update(sm, client, delta).is_some() {
+                self.finish(sm, client);
+            }
+        }
+        self.choose(client, false)
+    }
+
+    fn handle_event(
+        &mut self,
+        sm: &StateMachine<T>,
+        client: &mut T,
+        event: &DEvent,
+    ) -> Option<Transition<T>> {
+        if client.is_clicked(event) {
+            if let Some(transition) = self.choose(client, true) {
+                return Some(transition);
+            }
+        }
+        if let Some(code) = &mut self.code {
+            if code.handle_event(sm, client, event).is_some() {
+                self.finish(sm, client);
+                return self.choose(client, false);
+            }
+        }
+        None
+    }
+
+    fn exit(&mut self, sm: &StateMachine<T>, client: &mut T) {
+        if let Some(code) = &mut self.code {
+            code.exit(sm, client);
+        }
+    }
+
+    fn pause(&mut self, sm: &StateMachine<T>, client: &mut T) {
+        if let Some(code) = &mut self.code {
+            code.pause(sm, client);
+        }
+    }
+
+    fn child(&self) -> Option<&StateMachine<T>> {
+        self.code.as_ref().and_then(|code| code.child())
+    }
+
+    /**
+     * Picks up where it left off, with whatever was left of the wait
+     */
+    fn resume(&mut self, sm: &StateMachine<T>, client: &mut T) {
+        self.start(client);
+        if let Some(code) = &mut self.code {
+            code.resume(sm, client);
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{Clock, ManualClock},
+        event::MouseButton,
+        fsm::Tracer,
+        rng::Rng,
+    };
+    use std::time::Duration;
+
+    const PET: &str = r#"
+        initial = "idle"
+
+        [[next]]
+        to = "dance"
+        when = "music"
+
+        [states.idle]
+        anim = "sit"
+        duration = [1.0, 3.0]
+
+        [[states.idle.next]]
+        to = "walk"
+        when = "elapsed"
+        weight = 2
+
+        [[states.idle.next]]
+        to = "fetch"
+        when = "elapsed"
+
+        [[states.idle.next]]
+        to = "bark"
+        when = "clicked"
+        push = true
+
+        [states.walk]
+        anim = "walk"
+        speed = 50.0
+
+        [[states.walk.next]]
+        to = "idle"
+        when = "arrived"
+
+        [states.fetch]
+        code = "fetch"
+
+        [[states.fetch.next]]
+        to = "idle"
+        when = "done"
+
+        [states.bark]
+        anim = "bark"
+        duration = 0.5
+
+        [[states.bark.next]]
+        to = "back"
+        when = "elapsed"
+
+        [states.dance]
+        anim = "dance"
+
+        [[states.dance.next]]
+        to = "idle"
+        when = "!music"
+    "#;
+
+    struct Pet {
+        clock: ManualClock,
+        rng: Rng,
+        x: f32,
+        music: bool,
+        log: Vec<String>,
+    }
+
+    impl Actor for Pet {
+        fn play(&mut self, anim: &str) {
+            self.log.push(format!("play {}", anim));
+        }
+
+        fn set_flipped(&mut self, _flipped: bool) {}
+
+        fn x(&self) -> f32 {
+            self.x
+        }
+
+        fn set_x(&mut self, x: f32) {
+            self.x = x;
+        }
+
+        fn walk_bounds(&self) -> Option<(f32, f32)> {
+            Some((0.0, 100.0))
+        }
+
+        fn flag(&self, name: &str) -> bool {
+            name == "music" && self.music
+        }
+
+        fn is_clicked(&self, event: &DEvent) -> bool {
+            matches!(event, DEvent::MousePressed { .. })
+        }
+
+        fn random(&mut self) -> f32 {
+            self.rng.f32()
+        }
+
+        fn enter_code(
+            &mut self,
+            sm: &StateMachine<Pet>,
+            name: &str,
+        ) -> Option<Box<dyn DState<Pet>>> {
+            match name {
+                "fetch" => Some(Fetch::enter(sm, self)),
+                _ => None,
+            }
+        }
+    }
+
+    /**
+     * Asks to leave after a second, to wherever, which the file decides
+     */
+    struct Fetch {
+        left: f32,
+    }
+
+    impl DState<Pet> for Fetch {
+        fn enter(_sm: &StateMachine<Pet>, _client: &mut Pet) -> Box<dyn DState<Pet>> {
+            Box::new(Fetch { left: 1.0 })
+        }
+
+        fn update(
+            &mut self,
+            _sm: &StateMachine<Pet>,
+            _client: &mut Pet,
+            delta: f32,
+        ) -> Option<Transition<Pet>> {
+            self.left -= delta;
+            match self.left <= 0.0 {
+                true => Some(Transition::pop()),
+                false => None,
+            }
+        }
+
+        fn exit(&mut self, _sm: &StateMachine<Pet>, client: &mut Pet) {
+            client.log.push(String::from("fetched"));
+        }
+    }
+
+    fn simulate(seed: u64) -> (Vec<String>, Vec<String>) {
+        let behaviors = Behaviors::parse(PET).unwrap();
+        behaviors.check_code(&["fetch"]).unwrap();
+        behaviors.check_flags(&["music"]).unwrap();
+        let mut pet = Pet {
+            clock: ManualClock::new(12),
+            rng: Rng::new(seed),
+            x: 50.0,
+            music: false,
+            log: Vec::new(),
+        };
+        let sm = StateMachine::new();
+        let tracer = Tracer::new(50, |pet: &Pet| pet.clock.now());
+        sm.set_tracer(tracer.clone());
+        behaviors.init(&sm, &mut pet);
+        let click = DEvent::MousePressed {
+            button: MouseButton::Left,
+            x: 0.0,
+            y: 0.0,
+        };
+        for tick in 0..80 {
+            pet.clock.advance(Duration::from_millis(250));
+            match tick {
+                20 => pet.music = true,
+                24 => pet.music = false,
+                _ => (),
+            }
+            if tick % 30 == 29 {
+                sm.handle_event(&mut pet, &click);
+            }
+            sm.update(&mut pet, 0.25);
+        }
+        let history = tracer
+            .history()
+            .iter()
+            .map(|record| String::from(record.to_string().trim_start()))
+            .collect();
+        (history, pet.log)
+    }
+
+    #[test]
+    fn runs_the_same_for_a_seed() {
+        let (history, log) = simulate(1);
+        assert_eq!(
+            history,
+            [
+                "0.000s * -> idle (init)",
+                "1.750s idle -> walk: elapsed: walk 2.00* | fetch 1.00",
+                "2.750s walk -> idle: arrived: idle 1.00*",
+                "4.500s idle -> walk: elapsed: walk 2.00* | fetch 1.00",
+                "5.250s walk -> dance: music: dance 1.00*",
+                "6.250s dance -> idle: !music: idle 1.00*",
+                "7.500s idle -> bark (push): clicked: bark 1.00*",
+                "7.750s bark -> idle (pop): elapsed: back 1.00*",
+                "9.750s idle -> walk: elapsed: walk 2.00* | fetch 1.00",
+                "10.500s walk -> idle: arrived: idle 1.00*",
+                "13.250s idle -> walk: elapsed: walk 2.00* | fetch 1.00",
+                "14.500s walk -> idle: arrived: idle 1.00*",
+                "15.000s idle -> bark (push): clicked: bark 1.00*",
+                "15.250s bark -> idle (pop): elapsed: back 1.00*",
+                "17.500s idle -> fetch: elapsed: walk 2.00 | fetch 1.00*",
+                "18.500s fetch -> idle: done: idle 1.00*",
+            ]
+        );
+        // The code state cleaned up after itself
+        assert_eq!(log.iter().filter(|line| *line == "fetched").count(), 1);
+        assert_eq!(simulate(1), (history, log));
+    }
+
+    #[test]
+    fn waits_keep_their_time_while_pushed_over() {
+        let source = r#"
+            initial = "idle"
+            [states.idle]
+            anim = "sit"
+            duration = 2.0
+            [[states.idle.next]]
+            to = "bark"
+            when = "clicked"
+            push = true
+            [[states.idle.next]]
+            to = "walk"
+            when = "elapsed"
+            [states.bark]
+            anim = "bark"
+            duration = 0.5
+            [[states.bark.next]]
+            to = "back"
+            when = "elapsed"
+            [states.walk]
+            anim = "walk"
+        "#;
+        let behaviors = Behaviors::parse(source).unwrap();
+        let mut pet = Pet {
+            clock: ManualClock::new(12),
+            rng: Rng::new(1),
+            x: 50.0,
+            music: false,
+            log: Vec::new(),
+        };
+        let sm = StateMachine::new();
+        let tracer = Tracer::new(10, |pet: &Pet| pet.clock.now());
+        sm.set_tracer(tracer.clone());
+        behaviors.init(&sm, &mut pet);
+        let click = DEvent::MousePressed {
+            button: MouseButton::Left,
+            x: 0.0,
+            y: 0.0,
+        };
+        for tick in 0..16 {
+            pet.clock.advance(Duration::from_millis(250));
+            if tick == 4 {
+                sm.handle_event(&mut pet, &click);
+            }
+            sm.update(&mut pet, 0.25);
+        }
+        let history = tracer
+            .history()
+            .iter()
+            .map(|record| String::from(record.to_string().trim_start()))
+            .collect::<Vec<_>>();
+        // A second in idle before the bark, then the other second after
+        assert_eq!(
+            history,
+            [
+                "0.000s * -> idle (init)",
+                "1.250s idle -> bark (push): clicked: bark 1.00*",
+                "1.500s bark -> idle (pop): elapsed: back 1.00*",
+                "2.500s idle -> walk: elapsed: walk 1.00*",
+            ]
+        );
+    }
+
+    fn invalid(source: &str) -> String {
+        match Behaviors::parse(source) {
+            Err(BehaviorError::Invalid(message)) => message,
+            Err(err) => panic!("expected invalid behaviors, got {}", err),
+            Ok(_) => panic!("expected invalid behaviors"),
+        }
+    }
+
+    #[test]
+    fn rejects_what_cant_run() {
+        let missing = r#"
+            initial = "idle"
+            [states.idle]
+            anim = "sit"
+            [[states.idle.next]]
+            to = "nap"
+            when = "clicked"
+        "#;
+        assert_eq!(invalid(missing), "`idle` goes to missing state `nap`");
+        let both = r#"
+            initial = "idle"
+            [states.idle]
+            anim = "sit"
+            code = "sit"
+        "#;
+        assert_eq!(invalid(both), "`idle` needs either an anim or code");
+        let no_duration = r#"
+            initial = "idle"
+            [states.idle]
+            anim = "sit"
+            [[states.idle.next]]
+            to = "idle"
+            when = "elapsed"
+        "#;
+        assert_eq!(
+            invalid(no_duration),
+            "`idle` waits on `elapsed` without a duration"
+        );
+        let global_done = r#"
+            initial = "idle"
+            [[next]]
+            to = "idle"
+            when = "done"
+            [states.idle]
+            anim = "sit"
+        "#;
+        assert_eq!(
+            invalid(global_done),
+            "`done` only works in a state that runs code"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_code() {
+        let behaviors = Behaviors::parse(PET).unwrap();
+        match behaviors.check_code(&["dig"]) {
+            Err(BehaviorError::Invalid(message)) => {
+                assert_eq!(message, "state `fetch` runs unknown code `fetch`")
+            }
+            _ => panic!("expected unknown code to be rejected"),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        let behaviors = Behaviors::parse(PET).unwrap();
+        match behaviors.check_flags(&["musik"]) {
+            Err(BehaviorError::Invalid(message)) => {
+                assert!(
+                    message.ends_with("waits on unknown flag `music`"),
+                    "{}",
+                    message
+                )
+            }
+            _ => panic!("expected unknown flags to be rejected"),
+        }
+    }
+}
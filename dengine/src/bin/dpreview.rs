@@ -0,0 +1,155 @@
+use dengine::{
+    anim::{AnimManager, Playback},
+    frame::Frame,
+    term::{self, TermGraphics, TermRenderer},
+};
+use std::{
+    env, io, process, thread,
+    time::{Duration, Instant},
+};
+
+const USAGE: &str = "\
+usage: dpreview <sheet.png> [<width> <height> <frames> <fps>] [options]
+
+Plays an animation in the terminal at its authored timing.
+Without a size the whole sheet is shown as a single frame.
+
+options:
+    --graphics <halfblock|sixel|kitty>  override detection (or set DENGINE_GRAPHICS)
+    --scale <n>                         upscale for sixel/kitty, default 4
+    --flip                              mirror horizontally
+    --loops <n>                         stop after n loops, default forever";
+
+struct Args {
+    path: String,
+    size: Option<[u32; 4]>,
+    graphics: TermGraphics,
+    scale: u32,
+    flipped: bool,
+    loops: u32,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut positional = Vec::new();
+    let mut graphics = TermGraphics::detect();
+    let mut scale = 4;
+    let mut flipped = false;
+    let mut loops = 0;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--graphics" => {
+                let name = args.next().ok_or("--graphics needs a value")?;
+                graphics = TermGraphics::from_name(&name)
+                    .ok_or_else(|| format!("unknown graphics mode `{}`", name))?;
+            }
+            "--scale" => {
+                scale = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--scale needs a number")?;
+            }
+            "--loops" => {
+                loops = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--loops needs a number")?;
+            }
+            "--flip" => flipped = true,
+            "-h" | "--help" => return Err(String::new()),
+            _ => positional.push(arg),
+        }
+    }
+
+    let size = match positional.len() {
+        0 => return Err(String::new()),
+        1 => None,
+        5 => {
+            let mut size = [0; 4];
+            for (i, n) in positional[1..].iter().enumerate() {
+                size[i] = n.parse().map_err(|_| format!("`{}` is not a number", n))?;
+            }
+            Some(size)
+        }
+        _ => {
+            return Err(String::from(
+                "expected a sheet and optionally all of width, height, frames and fps",
+            ))
+        }
+    };
+
+    Ok(Args {
+        path: positional.remove(0),
+        size,
+        graphics,
+        scale,
+        flipped,
+        loops,
+    })
+}
+
+fn main() -> io::Result<()> {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("error: {}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    // Register the clip exactly like a game would
+    let mut anims = AnimManager::new();
+    let handle = anims.register("preview");
+    match args.size {
+        Some([width, height, frames, fps]) => handle
+            .width(width)
+            .height(height)
+            .frames(frames)
+            .fps(fps)
+            .import(&args.path),
+        None => handle.import(&args.path),
+    }
+    let anim = anims.get("preview").unwrap();
+
+    let mut playback = Playback::new();
+    playback.set_anim(anim.clone());
+    playback.set_flipped(args.flipped);
+
+    let mut frame = Frame::new(anim.width(), anim.height());
+    let mut renderer = TermRenderer::new(io::stdout(), args.graphics).scale(args.scale);
+    // Ctrl-C stops the loop rather than the process, so the cursor the
+    // renderer hides comes back
+    term::catch_interrupt();
+    renderer.begin()?;
+
+    let frame_time = Duration::from_secs_f32(1.0 / anim.fps() as f32);
+    let mut loops = 0;
+    let mut last = Instant::now();
+    while !term::interrupted() {
+        for byte in frame.get_mut().iter_mut() {
+            *byte = 0;
+        }
+        playback.draw(&mut frame, 0, 0);
+        renderer.draw(&frame)?;
+
+        // Sleep for what's left of this frame, then advance by real time
+        thread::sleep(frame_time.checked_sub(last.elapsed()).unwrap_or_default());
+        let current = Instant::now();
+        let before = playback.frame();
+        playback.update((current - last).as_secs_f32());
+        last = current;
+
+        if playback.frame() < before || anim.frames() == 1 {
+            loops += 1;
+            if args.loops != 0 && loops >= args.loops {
+                break;
+            }
+        }
+    }
+
+    renderer.end()
+}
@@ -0,0 +1,682 @@
+/**
+ * Result of ticking a node
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Running,
+    Success,
+    Failure,
+}
+
+/**
+ * Timing handed to every node on a tick
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Tick {
+    pub delta: f32,
+    /** Seconds the tree has been ticked for, keeps going across resets */
+    pub time: f32,
+}
+
+impl Tick {
+    /**
+     * When this tick's delta began, so something started now has
+     * already run for `delta` seconds
+     */
+    pub fn start(&self) -> f32 {
+        self.time - self.delta
+    }
+}
+
+/**
+ * One node of a behavior tree, working on the same client type as
+ * `StateMachine<T>`
+ *
+ * Composites remember which child is running, so a node is only
+ * restarted once it finishes or its parent resets it.
+ */
+pub trait Node<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status;
+
+    /**
+     * Forget any progress, called when a parent gives up on this node
+     * or starts it over
+     */
+    fn reset(&mut self) {}
+}
+
+type BoxNode<T> = Box<dyn Node<T>>;
+
+/**
+ * Owns the root node and the clock, tick it from a `DState::update`
+ */
+pub struct BehaviorTree<T> {
+    root: BoxNode<T>,
+    time: f32,
+}
+
+impl<T> BehaviorTree<T> {
+    pub fn new<N: 'static + Node<T>>(root: N) -> BehaviorTree<T> {
+        BehaviorTree {
+            root: Box::new(root),
+            time: 0.0,
+        }
+    }
+
+    /**
+     * Tick the whole tree once, a finished tree starts over on the
+     * next tick
+     */
+    pub fn tick(&mut self, client: &mut T, delta: f32) -> Status {
+        self.time += delta;
+        let tick = Tick {
+            delta,
+            time: self.time,
+        };
+        self.root.tick(client, &tick)
+    }
+
+    pub fn reset(&mut self) {
+        self.root.reset();
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+}
+
+/**
+ * Runs children in order until one fails
+ */
+pub struct Sequence<T> {
+    children: Vec<BoxNode<T>>,
+    current: usize,
+}
+
+impl<T> Sequence<T> {
+    pub fn new() -> Sequence<T> {
+        Sequence {
+            children: Vec::new(),
+            current: 0,
+        }
+    }
+
+    pub fn then<N: 'static + Node<T>>(mut self, node: N) -> Self {
+        self.children.push(Box::new(node));
+        self
+    }
+}
+
+impl<T> Default for Sequence<T> {
+    fn default() -> Self {
+        Sequence::new()
+    }
+}
+
+impl<T> Node<T> for Sequence<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        while self.current < self.children.len() {
+            match self.children[self.current].tick(client, tick) {
+                Status::Running => return Status::Running,
+                Status::Success => self.current += 1,
+                Status::Failure => {
+                    self.reset();
+                    return Status::Failure;
+                }
+            }
+        }
+        self.reset();
+        Status::Success
+    }
+
+    fn reset(&mut self) {
+        for child in &mut self.children {
+            child.reset();
+        }
+        self.current = 0;
+    }
+}
+
+/**
+ * Tries children in order until one succeeds
+ */
+pub struct Selector<T> {
+    children: Vec<BoxNode<T>>,
+    current: usize,
+}
+
+impl<T> Selector<T> {
+    pub fn new() -> Selector<T> {
+        Selector {
+            children: Vec::new(),
+            current: 0,
+        }
+    }
+
+    pub fn or<N: 'static + Node<T>>(mut self, node: N) -> Self {
+        self.children.push(Box::new(node));
+        self
+    }
+}
+
+impl<T> Default for Selector<T> {
+    fn default() -> Self {
+        Selector::new()
+    }
+}
+
+impl<T> Node<T> for Selector<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        while self.current < self.children.len() {
+            match self.children[self.current].tick(client, tick) {
+                Status::Running => return Status::Running,
+                Status::Failure => self.current += 1,
+                Status::Success => {
+                    self.reset();
+                    return Status::Success;
+                }
+            }
+        }
+        self.reset();
+        Status::Failure
+    }
+
+    fn reset(&mut self) {
+        for child in &mut self.children {
+            child.reset();
+        }
+        self.current = 0;
+    }
+}
+
+/**
+ * Ticks every unfinished child each tick
+ *
+ * Succeeds once `required` children succeeded (all of them unless
+ * set), fails as soon as that can't happen anymore. Children still
+ * running at that point are reset.
+ */
+pub struct Parallel<T> {
+    children: Vec<(BoxNode<T>, Status)>,
+    required: Option<usize>,
+}
+
+impl<T> Parallel<T> {
+    pub fn new() -> Parallel<T> {
+        Parallel {
+            children: Vec::new(),
+            required: None,
+        }
+    }
+
+    pub fn with<N: 'static + Node<T>>(mut self, node: N) -> Self {
+        self.children.push((Box::new(node), Status::Running));
+        self
+    }
+
+    pub fn require(mut self, required: usize) -> Self {
+        self.required = Some(required);
+        self
+    }
+}
+
+impl<T> Default for Parallel<T> {
+    fn default() -> Self {
+        Parallel::new()
+    }
+}
+
+impl<T> Node<T> for Parallel<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        let required = self.required.unwrap_or(self.children.len());
+        let mut succeeded = 0;
+        let mut failed = 0;
+        for (child, status) in &mut self.children {
+            if *status == Status::Running {
+                *status = child.tick(client, tick);
+            }
+            match status {
+                Status::Success => succeeded += 1,
+                Status::Failure => failed += 1,
+                Status::Running => (),
+            }
+        }
+        let result = if succeeded >= required {
+            Status::Success
+        } else if self.children.len() - failed < required {
+            Status::Failure
+        } else {
+            return Status::Running;
+        };
+        self.reset();
+        result
+    }
+
+    fn reset(&mut self) {
+        for (child, status) in &mut self.children {
+            child.reset();
+            *status = Status::Running;
+        }
+    }
+}
+
+/**
+ * Runs its child again every time it succeeds, fails when it does
+ */
+pub struct Repeat<T> {
+    child: BoxNode<T>,
+    times: Option<u32>,
+    count: u32,
+}
+
+impl<T> Repeat<T> {
+    pub fn forever<N: 'static + Node<T>>(node: N) -> Repeat<T> {
+        Repeat {
+            child: Box::new(node),
+            times: None,
+            count: 0,
+        }
+    }
+
+    /**
+     * Succeeds after `times` successes in a row
+     */
+    pub fn times<N: 'static + Node<T>>(times: u32, node: N) -> Repeat<T> {
+        Repeat {
+            child: Box::new(node),
+            times: Some(times),
+            count: 0,
+        }
+    }
+}
+
+impl<T> Node<T> for Repeat<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        match self.child.tick(client, tick) {
+            Status::Running => Status::Running,
+            Status::Failure => {
+                self.count = 0;
+                Status::Failure
+            }
+            Status::Success => {
+                self.count += 1;
+                match self.times {
+                    Some(times) if self.count >= times => {
+                        self.count = 0;
+                        Status::Success
+                    }
+                    // The next run starts on the next tick, so a child
+                    // that succeeds instantly can't spin forever here
+                    _ => Status::Running,
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.child.reset();
+        self.count = 0;
+    }
+}
+
+/**
+ * Fails if its child is still running after `limit` seconds
+ */
+pub struct Timeout<T> {
+    child: BoxNode<T>,
+    limit: f32,
+    started: Option<f32>,
+}
+
+impl<T> Timeout<T> {
+    pub fn new<N: 'static + Node<T>>(limit: f32, node: N) -> Timeout<T> {
+        Timeout {
+            child: Box::new(node),
+            limit,
+            started: None,
+        }
+    }
+}
+
+impl<T> Node<T> for Timeout<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        let started = *self.started.get_or_insert(tick.start());
+        if tick.time - started >= self.limit {
+            self.reset();
+            return Status::Failure;
+        }
+        let status = self.child.tick(client, tick);
+        if status != Status::Running {
+            self.started = None;
+        }
+        status
+    }
+
+    fn reset(&mut self) {
+        self.child.reset();
+        self.started = None;
+    }
+}
+
+/**
+ * Fails without ticking its child for `duration` seconds after the
+ * child last succeeded
+ *
+ * Keeps its cooldown through resets, that's the point of it
+ */
+pub struct Cooldown<T> {
+    child: BoxNode<T>,
+    duration: f32,
+    ready_at: f32,
+}
+
+impl<T> Cooldown<T> {
+    pub fn new<N: 'static + Node<T>>(duration: f32, node: N) -> Cooldown<T> {
+        Cooldown {
+            child: Box::new(node),
+            duration,
+            ready_at: 0.0,
+        }
+    }
+}
+
+impl<T> Node<T> for Cooldown<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        if tick.time < self.ready_at {
+            return Status::Failure;
+        }
+        let status = self.child.tick(client, tick);
+        if status == Status::Success {
+            self.ready_at = tick.time + self.duration;
+        }
+        status
+    }
+
+    fn reset(&mut self) {
+        self.child.reset();
+    }
+}
+
+/**
+ * Swaps success and failure
+ */
+pub struct Invert<T> {
+    child: BoxNode<T>,
+}
+
+impl<T> Invert<T> {
+    pub fn new<N: 'static + Node<T>>(node: N) -> Invert<T> {
+        Invert {
+            child: Box::new(node),
+        }
+    }
+}
+
+impl<T> Node<T> for Invert<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        match self.child.tick(client, tick) {
+            Status::Running => Status::Running,
+            Status::Success => Status::Failure,
+            Status::Failure => Status::Success,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.child.reset();
+    }
+}
+
+type ActionFn<T> = dyn FnMut(&mut T, &Tick, f32) -> Status;
+type StartFn<T> = dyn FnMut(&mut T);
+
+/**
+ * Leaf that does something to the client
+ *
+ * The closure gets called every tick the action runs for, with the
+ * tick and how many seconds ago this run started
+ */
+pub struct Action<T> {
+    action: Box<ActionFn<T>>,
+    on_start: Option<Box<StartFn<T>>>,
+    started: Option<f32>,
+}
+
+impl<T> Action<T> {
+    pub fn new<F>(action: F) -> Action<T>
+    where
+        F: 'static + FnMut(&mut T, &Tick, f32) -> Status,
+    {
+        Action {
+            action: Box::new(action),
+            on_start: None,
+            started: None,
+        }
+    }
+
+    /**
+     * Called once on the first tick of every run, before the action
+     */
+    pub fn on_start<F: 'static + FnMut(&mut T)>(mut self, on_start: F) -> Self {
+        self.on_start = Some(Box::new(on_start));
+        self
+    }
+}
+
+impl<T> Node<T> for Action<T> {
+    fn tick(&mut self, client: &mut T, tick: &Tick) -> Status {
+        let started = match self.started {
+            Some(started) => started,
+            None => {
+                if let Some(on_start) = &mut self.on_start {
+                    on_start(client);
+                }
+                *self.started.insert(tick.start())
+            }
+        };
+        let status = (self.action)(client, tick, tick.time - started);
+        if status != Status::Running {
+            self.started = None;
+        }
+        status
+    }
+
+    fn reset(&mut self) {
+        self.started = None;
+    }
+}
+
+/**
+ * Leaf that succeeds or fails right away depending on the client
+ */
+pub struct Condition<T> {
+    condition: Box<dyn Fn(&T) -> bool>,
+}
+
+impl<T> Condition<T> {
+    pub fn new<F: 'static + Fn(&T) -> bool>(condition: F) -> Condition<T> {
+        Condition {
+            condition: Box::new(condition),
+        }
+    }
+}
+
+impl<T> Node<T> for Condition<T> {
+    fn tick(&mut self, client: &mut T, _tick: &Tick) -> Status {
+        match (self.condition)(client) {
+            true => Status::Success,
+            false => Status::Failure,
+        }
+    }
+}
+
+/**
+ * Leaf that runs for `duration` seconds, then succeeds
+ */
+pub struct Wait {
+    duration: f32,
+    started: Option<f32>,
+}
+
+impl Wait {
+    pub fn new(duration: f32) -> Wait {
+        Wait {
+            duration,
+            started: None,
+        }
+    }
+}
+
+impl<T> Node<T> for Wait {
+    fn tick(&mut self, _client: &mut T, tick: &Tick) -> Status {
+        let started = *self.started.get_or_insert(tick.start());
+        if tick.time - started >= self.duration {
+            self.started = None;
+            Status::Success
+        } else {
+            Status::Running
+        }
+    }
+
+    fn reset(&mut self) {
+        self.started = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Client {
+        hungry: bool,
+        log: Vec<String>,
+    }
+
+    /**
+     * Logs when it starts and succeeds after running for `secs`
+     */
+    fn task(name: &'static str, secs: f32) -> Action<Client> {
+        Action::new(move |client: &mut Client, tick: &Tick, elapsed| {
+            if elapsed < secs {
+                return Status::Running;
+            }
+            client.log.push(format!("{} done at {}", name, tick.time));
+            Status::Success
+        })
+        .on_start(move |client: &mut Client| client.log.push(format!("{} start", name)))
+    }
+
+    fn run(tree: &mut BehaviorTree<Client>, client: &mut Client, ticks: usize) -> Vec<Status> {
+        (0..ticks).map(|_| tree.tick(client, 0.5)).collect()
+    }
+
+    #[test]
+    fn sequence_runs_in_order() {
+        let mut client = Client::default();
+        let mut tree = BehaviorTree::new(
+            Sequence::new()
+                .then(task("sit", 1.0))
+                .then(task("wag", 1.0)),
+        );
+        let statuses = run(&mut tree, &mut client, 4);
+        assert_eq!(
+            statuses,
+            [
+                Status::Running,
+                Status::Running,
+                Status::Success,
+                Status::Running
+            ]
+        );
+        assert_eq!(
+            client.log,
+            [
+                "sit start",
+                "sit done at 1",
+                "wag start",
+                "wag done at 1.5",
+                "sit start"
+            ]
+        );
+    }
+
+    #[test]
+    fn selector_falls_back() {
+        let mut client = Client::default();
+        let mut tree = BehaviorTree::new(
+            Selector::new()
+                .or(Sequence::new()
+                    .then(Condition::new(|client: &Client| client.hungry))
+                    .then(task("eat", 0.0)))
+                .or(task("nap", 0.5)),
+        );
+        assert_eq!(tree.tick(&mut client, 0.5), Status::Success);
+        client.hungry = true;
+        assert_eq!(tree.tick(&mut client, 0.5), Status::Success);
+        assert_eq!(
+            client.log,
+            ["nap start", "nap done at 0.5", "eat start", "eat done at 1"]
+        );
+    }
+
+    #[test]
+    fn parallel_needs_enough_successes() {
+        let mut client = Client::default();
+        let mut tree = BehaviorTree::new(
+            Parallel::new()
+                .with(task("bark", 0.5))
+                .with(task("spin", 1.5))
+                .require(1),
+        );
+        assert_eq!(tree.tick(&mut client, 0.5), Status::Success);
+        assert_eq!(client.log, ["bark start", "bark done at 0.5", "spin start"]);
+
+        let mut tree = BehaviorTree::new(
+            Parallel::new()
+                .with(Condition::new(|client: &Client| client.hungry))
+                .with(task("spin", 1.5)),
+        );
+        assert_eq!(tree.tick(&mut client, 0.5), Status::Failure);
+    }
+
+    #[test]
+    fn timeout_gives_up() {
+        let mut client = Client::default();
+        let mut tree = BehaviorTree::new(Timeout::new(1.0, task("dig", 5.0)));
+        let statuses = run(&mut tree, &mut client, 3);
+        // Starts over from scratch the tick after
+        assert_eq!(
+            statuses,
+            [Status::Running, Status::Failure, Status::Running]
+        );
+        assert_eq!(client.log, ["dig start", "dig start"]);
+    }
+
+    #[test]
+    fn cooldown_waits_after_success() {
+        let mut client = Client::default();
+        let mut tree = BehaviorTree::new(Cooldown::new(
+            1.0,
+            Invert::new(Invert::new(task("yip", 0.0))),
+        ));
+        let statuses = run(&mut tree, &mut client, 4);
+        assert_eq!(
+            statuses,
+            [
+                Status::Success,
+                Status::Failure,
+                Status::Success,
+                Status::Failure
+            ]
+        );
+        assert_eq!(
+            client.log,
+            [
+                "yip start",
+                "yip done at 0.5",
+                "yip start",
+                "yip done at 1.5"
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/**
+ * Source of time for the game thread
+ *
+ * Anything that asks what time it is should go through one of these,
+ * so a simulation can swap in a `ManualClock` and get the same result
+ * on every run
+ */
+pub trait Clock: Send {
+    /**
+     * Time since the clock started
+     */
+    fn now(&self) -> Duration;
+
+    /**
+     * Hour of the day, 0 to 23
+     */
+    fn hour(&self) -> u32;
+}
+
+/**
+ * Real time, from when it was created
+ */
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /**
+     * Local hour on Windows, UTC anywhere else
+     */
+    fn hour(&self) -> u32 {
+        local_hour()
+    }
+}
+
+#[cfg(windows)]
+fn local_hour() -> u32 {
+    use std::mem::MaybeUninit;
+    use winapi::um::sysinfoapi::GetLocalTime;
+    unsafe {
+        let mut time = MaybeUninit::uninit();
+        GetLocalTime(time.as_mut_ptr());
+        time.assume_init().wHour as u32
+    }
+}
+
+#[cfg(not(windows))]
+fn local_hour() -> u32 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    (seconds / 3600 % 24) as u32
+}
+
+/**
+ * Time that only moves when told to
+ *
+ * Clones share the same time, so keep one to `advance` after handing
+ * another to whatever owns the clock
+ */
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+    start_hour: u32,
+}
+
+impl ManualClock {
+    /**
+     * Start at zero, at `start_hour` o'clock
+     */
+    pub fn new(start_hour: u32) -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(Duration::from_secs(0))),
+            start_hour: start_hour % 24,
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock() += by;
+    }
+
+    pub fn set(&self, now: Duration) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock()
+    }
+
+    fn hour(&self) -> u32 {
+        ((self.start_hour as u64 + self.now().as_secs() / 3600) % 24) as u32
+    }
+}
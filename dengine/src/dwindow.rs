@@ -1,27 +1,82 @@
-use parking_lot::{Mutex, MutexGuard};
-use pixels::{raw_window_handle::HasRawWindowHandle, Pixels, SurfaceTexture};
-use raw_window_handle::RawWindowHandle;
+use crate::event::EventQueue;
+pub use crate::frame::{Frame, FrameBuffer};
+use parking_lot::Mutex;
+use pixels::{Pixels, SurfaceTexture};
 use std::{
+    error, fmt,
     marker::PhantomData,
-    mem,
     ops::Deref,
+    slice,
     sync::{mpsc, Arc},
     thread,
 };
-use winapi::um::winuser::{SetWindowLongA, GWL_EXSTYLE, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW};
+#[cfg(unix)]
+use winit::platform::unix::EventLoopExtUnix;
+#[cfg(windows)]
+use winit::platform::windows::EventLoopExtWindows;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
-    event_loop::EventLoop,
-    platform::windows::EventLoopExtWindows,
+    event_loop::{EventLoop, EventLoopWindowTarget},
     window::{Window, WindowBuilder},
 };
 
+/**
+ * How a DWindow puts its frame on screen
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowMode {
+    /**
+     * One small window the size of the frame, moved around to follow
+     * `x` and `y`
+     */
+    Sprite,
+    /**
+     * One transparent, click-through window covering each monitor
+     *
+     * The frame spans the whole desktop and never moves, so `x` and `y`
+     * are world coordinates to draw at. Only opaque pixels take input.
+     * Windows only, elsewhere `build` refuses it.
+     */
+    Overlay,
+}
+
+#[derive(Debug)]
+pub enum WindowError {
+    /** Overlays need per-pixel click-through, which only Windows has */
+    OverlayUnsupported,
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowError::OverlayUnsupported => {
+                write!(f, "overlay windows are only supported on Windows")
+            }
+        }
+    }
+}
+
+impl error::Error for WindowError {}
+
+/**
+ * Where an overlay frame sits on the desktop and how big it is, which
+ * changes when the monitors do
+ */
+#[derive(Clone, Copy)]
+struct Layout {
+    origin: PhysicalPosition<i32>,
+    frame_size: (u32, u32),
+}
+
 pub struct DWindow {
     pub x: f32,
     pub y: f32,
     scale: f32,
+    mode: WindowMode,
+    layout: Arc<Mutex<Layout>>,
     window: Arc<Window>,
     framebuffer: Arc<FrameBuffer>,
+    events: EventQueue,
 }
 
 impl DWindow {
@@ -29,11 +84,54 @@ impl DWindow {
         self.scale
     }
 
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    /**
+     * World position of the top-left pixel of the frame
+     */
+    pub fn origin(&self) -> PhysicalPosition<i32> {
+        match self.mode {
+            WindowMode::Sprite => self.snapped_position(),
+            WindowMode::Overlay => self.layout.lock().origin,
+        }
+    }
+
+    /**
+     * Size the frame has to be to cover the desktop in overlay mode,
+     * None in sprite mode where it fits whatever is drawn
+     */
+    pub fn frame_size(&self) -> Option<(u32, u32)> {
+        match self.mode {
+            WindowMode::Sprite => None,
+            WindowMode::Overlay => Some(self.layout.lock().frame_size),
+        }
+    }
+
+    /**
+     * Convert a world position to frame pixel coordinates
+     */
+    pub fn to_frame(&self, x: f32, y: f32) -> (i32, i32) {
+        let origin = self.origin();
+        (
+            ((x - origin.x as f32) / self.scale).floor() as i32,
+            ((y - origin.y as f32) / self.scale).floor() as i32,
+        )
+    }
+
+    /**
+     * Cursor position in world coordinates, even when it isn't over
+     * the window. None off Windows.
+     */
+    pub fn cursor(&self) -> Option<PhysicalPosition<i32>> {
+        win32::cursor_position()
+    }
+
     pub fn update(&self, _delta: f32) {
-        self.set_outer_position(PhysicalPosition {
-            x: self.x - (self.x % self.scale),
-            y: self.y - (self.y % self.scale),
-        });
+        if self.mode == WindowMode::Sprite {
+            self.set_outer_position(self.snapped_position());
+        }
     }
 
     pub fn framebuffer(&self) -> &FrameBuffer {
@@ -43,6 +141,20 @@ impl DWindow {
     pub fn swap_buffers(&self) {
         self.framebuffer.swap_buffers();
     }
+
+    /**
+     * Events pushed by the window thread, drain once per tick
+     */
+    pub fn events(&self) -> &EventQueue {
+        &self.events
+    }
+
+    fn snapped_position(&self) -> PhysicalPosition<i32> {
+        PhysicalPosition {
+            x: (self.x - (self.x % self.scale)) as i32,
+            y: (self.y - (self.y % self.scale)) as i32,
+        }
+    }
 }
 
 impl Deref for DWindow {
@@ -52,66 +164,212 @@ impl Deref for DWindow {
     }
 }
 
-pub struct Frame {
-    pub width: u32,
-    pub height: u32,
-    pub buffer: Vec<u8>,
+/**
+ * A window and its pixel surface, owned by the window thread
+ */
+pub struct Surface {
+    window: Arc<Window>,
+    pixels: Pixels,
+    mode: WindowMode,
+    scale: f32,
+    /** Where this window's top-left pixel sits in the frame */
+    offset: (i32, i32),
+    buffer_size: (u32, u32),
+    click_through: bool,
 }
 
-impl Frame {
-    fn new(width: u32, height: u32) -> Frame {
-        let mut buffer = Vec::new();
-        buffer.resize((width * height * 4) as usize, 0);
-        Frame {
-            width,
-            height,
-            buffer,
-        }
+impl Surface {
+    pub fn window(&self) -> &Window {
+        &self.window
     }
 
-    pub fn size(&self) -> (u32, u32) {
-        (self.width, self.height)
+    pub fn scale(&self) -> f32 {
+        self.scale
     }
 
-    pub fn set_size(&mut self, width: u32, height: u32) {
-        self.buffer.resize((width * height * 4) as usize, 0);
-        self.width = width;
-        self.height = height;
+    pub fn offset(&self) -> (i32, i32) {
+        self.offset
     }
 
-    pub fn get_mut(&mut self) -> &mut Vec<u8> {
-        &mut self.buffer
+    /**
+     * Copy this surface's part of the frame to the screen
+     *
+     * Sprite surfaces resize themselves to match the frame, overlay
+     * surfaces copy the rectangle they cover
+     */
+    pub fn present(&mut self, frame: &Frame) -> Result<(), pixels::Error> {
+        match self.mode {
+            WindowMode::Sprite => {
+                if frame.size() != self.buffer_size {
+                    let scaled_width = (frame.width as f32 * self.scale) as u32;
+                    let scaled_height = (frame.height as f32 * self.scale) as u32;
+                    self.window.set_inner_size(PhysicalSize {
+                        width: scaled_width,
+                        height: scaled_height,
+                    });
+                    self.pixels.resize_surface(scaled_width, scaled_height);
+                    self.pixels.resize_buffer(frame.width, frame.height);
+                    self.buffer_size = frame.size();
+                }
+                self.pixels.get_frame().copy_from_slice(&frame.buffer);
+            }
+            WindowMode::Overlay => {
+                let width = self.buffer_size.0;
+                let target = self.pixels.get_frame();
+                for (row, line) in target.chunks_exact_mut(width as usize * 4).enumerate() {
+                    line.iter_mut().for_each(|byte| *byte = 0);
+                    let y = self.offset.1 + row as i32;
+                    if y < 0 || y >= frame.height as i32 {
+                        continue;
+                    }
+                    let start_x = self.offset.0.max(0);
+                    let end_x = (self.offset.0 + width as i32).min(frame.width as i32);
+                    if start_x < end_x {
+                        let src = ((y as u32 * frame.width + start_x as u32) * 4) as usize;
+                        let dst = ((start_x - self.offset.0) * 4) as usize;
+                        let len = ((end_x - start_x) * 4) as usize;
+                        line[dst..dst + len].copy_from_slice(&frame.buffer[src..src + len]);
+                    }
+                }
+            }
+        }
+        self.pixels.render()
     }
-}
 
-pub struct FrameBuffer {
-    front_buffer: Mutex<Box<Frame>>,
-    back_buffer: Mutex<Box<Frame>>,
+    /**
+     * Let clicks through unless the cursor is over an opaque pixel
+     *
+     * Only does anything for overlay surfaces, call every frame after
+     * `present()` with the same frame
+     */
+    pub fn update_hit_test(&mut self, frame: &Frame) {
+        if self.mode != WindowMode::Overlay {
+            return;
+        }
+        let cursor = match win32::cursor_position() {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let window_pos = self
+            .window
+            .outer_position()
+            .unwrap_or_else(|_| PhysicalPosition::new(0, 0));
+        let x = self.offset.0 + ((cursor.x - window_pos.x) as f32 / self.scale).floor() as i32;
+        let y = self.offset.1 + ((cursor.y - window_pos.y) as f32 / self.scale).floor() as i32;
+
+        let opaque = x >= 0
+            && y >= 0
+            && (x as u32) < frame.width
+            && (y as u32) < frame.height
+            && frame.buffer[((y as u32 * frame.width + x as u32) * 4 + 3) as usize] != 0;
+        if opaque == self.click_through {
+            self.click_through = !opaque;
+            win32::set_click_through(&self.window, self.click_through);
+        }
+    }
 }
 
-impl FrameBuffer {
-    pub fn new(width: u32, height: u32) -> FrameBuffer {
-        FrameBuffer {
-            front_buffer: Mutex::new(Box::new(Frame::new(width, height))),
-            back_buffer: Mutex::new(Box::new(Frame::new(width, height))),
+/**
+ * Window tweaks winit doesn't expose, no-ops off Windows
+ */
+#[cfg(windows)]
+mod win32 {
+    use pixels::raw_window_handle::HasRawWindowHandle;
+    use raw_window_handle::RawWindowHandle;
+    use std::mem::MaybeUninit;
+    use winapi::{
+        shared::windef::{HWND, POINT},
+        um::winuser::{
+            GetCursorPos, GetWindowLongA, SetLayeredWindowAttributes, SetWindowLongA, GWL_EXSTYLE,
+            LWA_ALPHA, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT,
+        },
+    };
+    use winit::{dpi::PhysicalPosition, window::Window};
+
+    fn hwnd(window: &Window) -> Option<HWND> {
+        match window.raw_window_handle() {
+            RawWindowHandle::Windows(handle) => Some(handle.hwnd as HWND),
+            _ => None,
         }
     }
 
-    pub fn get_back_buffer(&self) -> MutexGuard<'_, Box<Frame>> {
-        self.back_buffer.try_lock().unwrap()
+    /**
+     * Keep the window out of the taskbar and from stealing focus
+     */
+    pub fn set_tool_window(window: &Window) {
+        if let Some(hwnd) = hwnd(window) {
+            unsafe {
+                SetWindowLongA(
+                    hwnd,
+                    GWL_EXSTYLE,
+                    (WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE) as i32,
+                );
+            }
+        }
     }
 
-    pub fn get_front_buffer(&self) -> MutexGuard<'_, Box<Frame>> {
-        self.front_buffer.lock()
+    /**
+     * Like `set_tool_window()`, but also layered and click-through
+     *
+     * Layered + transparent is what makes a window click-through,
+     * full alpha keeps the pixels themselves fully visible
+     */
+    pub fn set_overlay_window(window: &Window) {
+        if let Some(hwnd) = hwnd(window) {
+            unsafe {
+                SetWindowLongA(
+                    hwnd,
+                    GWL_EXSTYLE,
+                    (WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE | WS_EX_LAYERED | WS_EX_TRANSPARENT)
+                        as i32,
+                );
+                SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA);
+            }
+        }
     }
 
-    pub fn swap_buffers(&self) {
-        let mut fb = self.front_buffer.lock();
-        let mut bb = self.back_buffer.try_lock().unwrap();
-        mem::swap(fb.as_mut(), bb.as_mut());
+    pub fn set_click_through(window: &Window, click_through: bool) {
+        if let Some(hwnd) = hwnd(window) {
+            unsafe {
+                let style = GetWindowLongA(hwnd, GWL_EXSTYLE) as u32;
+                let style = match click_through {
+                    true => style | WS_EX_TRANSPARENT,
+                    false => style & !WS_EX_TRANSPARENT,
+                };
+                SetWindowLongA(hwnd, GWL_EXSTYLE, style as i32);
+            }
+        }
+    }
+
+    pub fn cursor_position() -> Option<PhysicalPosition<i32>> {
+        unsafe {
+            let mut point = MaybeUninit::<POINT>::uninit();
+            if GetCursorPos(point.as_mut_ptr()) == 0 {
+                return None;
+            }
+            let point = point.assume_init();
+            Some(PhysicalPosition::new(point.x, point.y))
+        }
     }
 }
 
+#[cfg(not(windows))]
+mod win32 {
+    use winit::{dpi::PhysicalPosition, window::Window};
+
+    pub fn set_tool_window(_window: &Window) {}
+
+    pub fn set_overlay_window(_window: &Window) {}
+
+    pub fn set_click_through(_window: &Window, _click_through: bool) {}
+
+    pub fn cursor_position() -> Option<PhysicalPosition<i32>> {
+        None
+    }
+}
+
+type LoopFn<T> = dyn Fn(&FrameBuffer, &EventQueue, Surfaces, EventLoop<T>) + 'static + Send;
+
 pub struct DWindowBuilder<T>
 where
     T: 'static + Send,
@@ -122,7 +380,8 @@ where
     height: u32,
     title: String,
     scale: f32,
-    loop_fn: Option<Box<dyn Fn(f32, &FrameBuffer, Pixels, &Window, EventLoop<T>) + 'static + Send>>,
+    mode: WindowMode,
+    loop_fn: Option<Box<LoopFn<T>>>,
     event_type: PhantomData<T>,
 }
 
@@ -138,6 +397,7 @@ where
             height: 0,
             title: String::from("Digit"),
             scale: 1.0,
+            mode: WindowMode::Sprite,
             loop_fn: None,
             event_type: PhantomData::<T>,
         }
@@ -149,6 +409,10 @@ where
         self
     }
 
+    /**
+     * Initial frame size, ignored in overlay mode where the frame
+     * always covers the desktop
+     */
     pub fn size(mut self, width: u32, height: u32) -> Self {
         self.width = width;
         self.height = height;
@@ -165,65 +429,230 @@ where
         self
     }
 
+    pub fn mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     pub fn loop_fn<F>(mut self, loop_fn: F) -> Self
     where
-        F: Fn(f32, &FrameBuffer, Pixels, &Window, EventLoop<T>) + 'static + Send,
+        F: Fn(&FrameBuffer, &EventQueue, Surfaces, EventLoop<T>) + 'static + Send,
     {
         self.loop_fn = Some(Box::new(loop_fn));
         self
     }
 
-    pub fn build(mut self) -> DWindow {
-        let scaled_width = (self.width as f32 * self.scale) as u32;
-        let scaled_height = (self.height as f32 * self.scale) as u32;
-
+    /**
+     * Open the window on a thread of its own that runs the loop function
+     */
+    pub fn build(mut self) -> Result<DWindow, WindowError> {
+        if cfg!(not(windows)) && self.mode == WindowMode::Overlay {
+            return Err(WindowError::OverlayUnsupported);
+        }
         let loop_fn = self.loop_fn.take().unwrap();
 
         let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
             let event_loop = EventLoop::<T>::new_any_thread();
-            let window = Arc::new(
-                WindowBuilder::new()
-                    .with_inner_size(PhysicalSize {
-                        width: scaled_width,
-                        height: scaled_height,
-                    })
-                    .with_title(self.title)
-                    .with_always_on_top(true)
-                    .with_transparent(true)
-                    .with_decorations(false)
-                    .build(&event_loop)
-                    .unwrap(),
-            );
-            window.set_outer_position(PhysicalPosition::new(self.x, self.y));
-            if let RawWindowHandle::Windows(handle) = window.raw_window_handle() {
-                unsafe {
-                    SetWindowLongA(
-                        mem::transmute(handle.hwnd),
-                        GWL_EXSTYLE,
-                        (WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE) as i32,
-                    );
+            // Nothing to cover without a monitor, so make do with one window
+            if self.mode == WindowMode::Overlay && event_loop.available_monitors().next().is_none()
+            {
+                self.mode = WindowMode::Sprite;
+            }
+            let mut surfaces = Surfaces {
+                surfaces: Vec::new(),
+                title: self.title.clone(),
+                scale: self.scale,
+                mode: self.mode,
+                layout: Arc::new(Mutex::new(Layout {
+                    origin: PhysicalPosition::new(self.x, self.y),
+                    frame_size: (self.width, self.height),
+                })),
+            };
+            match self.mode {
+                WindowMode::Sprite => surfaces.surfaces.push(self.build_sprite(&event_loop)),
+                WindowMode::Overlay => {
+                    surfaces.rebuild(&event_loop);
                 }
             }
 
-            let surf = SurfaceTexture::new(scaled_width as u32, scaled_width as u32, &*window);
-            let pixels = Pixels::new(self.width, self.height, surf).unwrap();
+            let frame_size = surfaces.layout.lock().frame_size;
             let dwindow = DWindow {
                 x: self.x as f32,
                 y: self.y as f32,
                 scale: self.scale,
-                window,
-                framebuffer: Arc::new(FrameBuffer::new(self.width, self.height)),
+                mode: self.mode,
+                layout: surfaces.layout.clone(),
+                window: surfaces.surfaces[0].window.clone(),
+                framebuffer: Arc::new(FrameBuffer::new(frame_size.0, frame_size.1)),
+                events: EventQueue::new(),
             };
 
             let framebuffer = dwindow.framebuffer.clone();
-            let window = dwindow.window.clone();
+            let events = dwindow.events.clone();
 
             tx.send(dwindow).unwrap();
 
-            loop_fn(self.scale, &framebuffer, pixels, &window, event_loop);
+            loop_fn(&framebuffer, &events, surfaces, event_loop);
         });
 
-        rx.recv().unwrap()
+        Ok(rx.recv().unwrap())
+    }
+
+    fn build_sprite(&self, target: &EventLoopWindowTarget<T>) -> Surface {
+        let scaled_width = (self.width as f32 * self.scale) as u32;
+        let scaled_height = (self.height as f32 * self.scale) as u32;
+
+        let window = Arc::new(
+            WindowBuilder::new()
+                .with_inner_size(PhysicalSize {
+                    width: scaled_width,
+                    height: scaled_height,
+                })
+                .with_title(&self.title)
+                .with_always_on_top(true)
+                .with_transparent(true)
+                .with_decorations(false)
+                .build(target)
+                .unwrap(),
+        );
+        window.set_outer_position(PhysicalPosition::new(self.x, self.y));
+        win32::set_tool_window(&window);
+
+        let surf = SurfaceTexture::new(scaled_width, scaled_height, &*window);
+        let pixels = Pixels::new(self.width, self.height, surf).unwrap();
+        Surface {
+            window,
+            pixels,
+            mode: WindowMode::Sprite,
+            scale: self.scale,
+            offset: (0, 0),
+            buffer_size: (self.width, self.height),
+            click_through: false,
+        }
+    }
+}
+
+impl<T> Default for DWindowBuilder<T>
+where
+    T: 'static + Send,
+{
+    fn default() -> Self {
+        DWindowBuilder::new()
+    }
+}
+
+/**
+ * Every window a DWindow shows its frame in, handed to the loop
+ * function to present on the window thread
+ */
+pub struct Surfaces {
+    surfaces: Vec<Surface>,
+    title: String,
+    scale: f32,
+    mode: WindowMode,
+    layout: Arc<Mutex<Layout>>,
+}
+
+impl Surfaces {
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Surface> {
+        self.surfaces.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Surface> {
+        self.surfaces.iter_mut()
+    }
+
+    /**
+     * Cover every monitor with an overlay window again, reusing the
+     * windows there are, when monitors were added, removed or moved
+     *
+     * Returns false and leaves everything as it was in sprite mode, or
+     * while there's no monitor at all
+     */
+    pub fn rebuild<T>(&mut self, target: &EventLoopWindowTarget<T>) -> bool {
+        let monitors = target.available_monitors().collect::<Vec<_>>();
+        if self.mode != WindowMode::Overlay || monitors.is_empty() {
+            return false;
+        }
+
+        // The frame covers the bounding box of every monitor
+        let left = monitors.iter().map(|m| m.position().x).min().unwrap_or(0);
+        let top = monitors.iter().map(|m| m.position().y).min().unwrap_or(0);
+        let right = monitors
+            .iter()
+            .map(|m| m.position().x + m.size().width as i32)
+            .max()
+            .unwrap_or(0);
+        let bottom = monitors
+            .iter()
+            .map(|m| m.position().y + m.size().height as i32)
+            .max()
+            .unwrap_or(0);
+        let frame_size = (
+            ((right - left) as f32 / self.scale).ceil() as u32,
+            ((bottom - top) as f32 / self.scale).ceil() as u32,
+        );
+
+        self.surfaces.truncate(monitors.len());
+        for (index, monitor) in monitors.iter().enumerate() {
+            let position = monitor.position();
+            let size = monitor.size();
+            let offset = (
+                ((position.x - left) as f32 / self.scale).floor() as i32,
+                ((position.y - top) as f32 / self.scale).floor() as i32,
+            );
+            let buffer_size = (
+                (size.width as f32 / self.scale).ceil() as u32,
+                (size.height as f32 / self.scale).ceil() as u32,
+            );
+            match self.surfaces.get_mut(index) {
+                Some(surface) => {
+                    surface.window.set_outer_position(position);
+                    surface.window.set_inner_size(size);
+                    surface.pixels.resize_surface(size.width, size.height);
+                    surface.pixels.resize_buffer(buffer_size.0, buffer_size.1);
+                    surface.offset = offset;
+                    surface.buffer_size = buffer_size;
+                }
+                None => {
+                    let window = Arc::new(
+                        WindowBuilder::new()
+                            .with_inner_size(size)
+                            .with_position(position)
+                            .with_title(&self.title)
+                            .with_always_on_top(true)
+                            .with_transparent(true)
+                            .with_decorations(false)
+                            .build(target)
+                            .unwrap(),
+                    );
+                    window.set_outer_position(position);
+                    win32::set_overlay_window(&window);
+
+                    let surf = SurfaceTexture::new(size.width, size.height, &*window);
+                    let pixels = Pixels::new(buffer_size.0, buffer_size.1, surf).unwrap();
+                    self.surfaces.push(Surface {
+                        window,
+                        pixels,
+                        mode: WindowMode::Overlay,
+                        scale: self.scale,
+                        offset,
+                        buffer_size,
+                        click_through: true,
+                    });
+                }
+            }
+        }
+
+        *self.layout.lock() = Layout {
+            origin: PhysicalPosition::new(left, top),
+            frame_size,
+        };
+        true
     }
 }
@@ -1 +1,266 @@
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    fmt, mem,
+    sync::Arc,
+};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ElementState, MouseScrollDelta, WindowEvent},
+    event_loop::EventLoopWindowTarget,
+    monitor::MonitorHandle,
+    window::WindowId,
+};
 
+pub use winit::event::{MouseButton, VirtualKeyCode as Key};
+
+/**
+ * Engine-level event delivered to the active state once per tick
+ *
+ * Mouse coordinates are in frame pixels (window pixels divided by
+ * the window scale, offset by where the window sits in the frame),
+ * so states don't have to care about scaling or which window it was
+ */
+#[derive(Clone)]
+pub enum DEvent {
+    MouseMoved {
+        x: f32,
+        y: f32,
+    },
+    MousePressed {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    },
+    MouseReleased {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    },
+    MouseWheel {
+        delta: f32,
+        x: f32,
+        y: f32,
+    },
+    MouseEntered,
+    MouseLeft,
+    /** Only delivered while the window has keyboard focus */
+    KeyPressed(Key),
+    KeyReleased(Key),
+    Focused(bool),
+    WindowMoved {
+        x: i32,
+        y: i32,
+    },
+    WindowResized {
+        width: u32,
+        height: u32,
+    },
+    ScaleChanged {
+        scale: f64,
+    },
+    /** Monitors were added, removed, moved or resized */
+    DisplayChanged,
+    Timer(u32),
+    Custom(Arc<dyn Any + Send + Sync>),
+}
+
+impl DEvent {
+    pub fn custom<C: Any + Send + Sync>(payload: C) -> DEvent {
+        DEvent::Custom(Arc::new(payload))
+    }
+
+    /**
+     * Downcast a `Custom` payload, returns None for any other event
+     * or if the payload is of a different type
+     */
+    pub fn as_custom<C: Any>(&self) -> Option<&C> {
+        match self {
+            DEvent::Custom(payload) => payload.downcast_ref::<C>(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for DEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DEvent::MouseMoved { x, y } => write!(f, "MouseMoved({}, {})", x, y),
+            DEvent::MousePressed { button, x, y } => {
+                write!(f, "MousePressed({:?}, {}, {})", button, x, y)
+            }
+            DEvent::MouseReleased { button, x, y } => {
+                write!(f, "MouseReleased({:?}, {}, {})", button, x, y)
+            }
+            DEvent::MouseWheel { delta, x, y } => write!(f, "MouseWheel({}, {}, {})", delta, x, y),
+            DEvent::MouseEntered => write!(f, "MouseEntered"),
+            DEvent::MouseLeft => write!(f, "MouseLeft"),
+            DEvent::KeyPressed(key) => write!(f, "KeyPressed({:?})", key),
+            DEvent::KeyReleased(key) => write!(f, "KeyReleased({:?})", key),
+            DEvent::Focused(focused) => write!(f, "Focused({})", focused),
+            DEvent::WindowMoved { x, y } => write!(f, "WindowMoved({}, {})", x, y),
+            DEvent::WindowResized { width, height } => {
+                write!(f, "WindowResized({}, {})", width, height)
+            }
+            DEvent::ScaleChanged { scale } => write!(f, "ScaleChanged({})", scale),
+            DEvent::DisplayChanged => write!(f, "DisplayChanged"),
+            DEvent::Timer(id) => write!(f, "Timer({})", id),
+            DEvent::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/**
+ * Thread-safe FIFO of events
+ *
+ * Cloning gives another handle to the same queue, so the window thread
+ * (or a service) can push while the game thread drains once per tick
+ */
+#[derive(Clone)]
+pub struct EventQueue {
+    events: Arc<Mutex<VecDeque<DEvent>>>,
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        EventQueue {
+            events: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn push(&self, event: DEvent) {
+        self.events.lock().push_back(event);
+    }
+
+    /**
+     * Take every queued event in the order they were pushed
+     */
+    pub fn drain(&self) -> VecDeque<DEvent> {
+        mem::take(&mut *self.events.lock())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.lock().is_empty()
+    }
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        EventQueue::new()
+    }
+}
+
+/**
+ * Converts winit window events into `DEvent`s
+ *
+ * Lives on the window thread, tracks the cursor since winit only
+ * reports its position on `CursorMoved`
+ */
+pub struct EventTranslator {
+    scale: f32,
+    offsets: HashMap<WindowId, (i32, i32)>,
+    cursor: (f32, f32),
+    displays: Vec<(PhysicalPosition<i32>, (u32, u32))>,
+}
+
+impl EventTranslator {
+    pub fn new(scale: f32) -> EventTranslator {
+        EventTranslator {
+            scale,
+            offsets: HashMap::new(),
+            cursor: (0.0, 0.0),
+            displays: Vec::new(),
+        }
+    }
+
+    /**
+     * Register where a window's top-left pixel sits in the frame, for
+     * windows that don't start at the frame origin
+     */
+    pub fn add_window(&mut self, window: WindowId, offset: (i32, i32)) {
+        self.offsets.insert(window, offset);
+    }
+
+    /**
+     * Forget every window, before adding them again once they've moved
+     */
+    pub fn clear_windows(&mut self) {
+        self.offsets.clear();
+    }
+
+    pub fn translate(&mut self, window: WindowId, event: &WindowEvent<'_>) -> Option<DEvent> {
+        let (x, y) = self.cursor;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let offset = self.offsets.get(&window).copied().unwrap_or((0, 0));
+                self.cursor = (
+                    position.x as f32 / self.scale + offset.0 as f32,
+                    position.y as f32 / self.scale + offset.1 as f32,
+                );
+                Some(DEvent::MouseMoved {
+                    x: self.cursor.0,
+                    y: self.cursor.1,
+                })
+            }
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => Some(DEvent::MousePressed {
+                    button: *button,
+                    x,
+                    y,
+                }),
+                ElementState::Released => Some(DEvent::MouseReleased {
+                    button: *button,
+                    x,
+                    y,
+                }),
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / self.scale,
+                };
+                Some(DEvent::MouseWheel { delta, x, y })
+            }
+            WindowEvent::CursorEntered { .. } => Some(DEvent::MouseEntered),
+            WindowEvent::CursorLeft { .. } => Some(DEvent::MouseLeft),
+            WindowEvent::KeyboardInput { input, .. } => {
+                input.virtual_keycode.map(|key| match input.state {
+                    ElementState::Pressed => DEvent::KeyPressed(key),
+                    ElementState::Released => DEvent::KeyReleased(key),
+                })
+            }
+            WindowEvent::Focused(focused) => Some(DEvent::Focused(*focused)),
+            WindowEvent::Moved(pos) => Some(DEvent::WindowMoved { x: pos.x, y: pos.y }),
+            WindowEvent::Resized(size) => Some(DEvent::WindowResized {
+                width: size.width,
+                height: size.height,
+            }),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => Some(DEvent::ScaleChanged {
+                scale: *scale_factor,
+            }),
+            _ => None,
+        }
+    }
+
+    /**
+     * Compare the current monitor layout against the last one seen
+     *
+     * winit has no event for this, so call it every so often from the
+     * window thread. The first call only records the layout.
+     */
+    pub fn poll_displays<T>(&mut self, target: &EventLoopWindowTarget<T>) -> Option<DEvent> {
+        let displays = target
+            .available_monitors()
+            .map(|m: MonitorHandle| (m.position(), (m.size().width, m.size().height)))
+            .collect::<Vec<_>>();
+        let first = self.displays.is_empty();
+        if displays != self.displays {
+            self.displays = displays;
+            if !first {
+                return Some(DEvent::DisplayChanged);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,62 @@
+use parking_lot::{Mutex, MutexGuard};
+use std::mem;
+
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub buffer: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32) -> Frame {
+        let mut buffer = Vec::new();
+        buffer.resize((width * height * 4) as usize, 0);
+        Frame {
+            width,
+            height,
+            buffer,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.buffer.resize((width * height * 4) as usize, 0);
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn get_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+pub struct FrameBuffer {
+    front_buffer: Mutex<Box<Frame>>,
+    back_buffer: Mutex<Box<Frame>>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        FrameBuffer {
+            front_buffer: Mutex::new(Box::new(Frame::new(width, height))),
+            back_buffer: Mutex::new(Box::new(Frame::new(width, height))),
+        }
+    }
+
+    pub fn get_back_buffer(&self) -> MutexGuard<'_, Box<Frame>> {
+        self.back_buffer.try_lock().unwrap()
+    }
+
+    pub fn get_front_buffer(&self) -> MutexGuard<'_, Box<Frame>> {
+        self.front_buffer.lock()
+    }
+
+    pub fn swap_buffers(&self) {
+        let mut fb = self.front_buffer.lock();
+        let mut bb = self.back_buffer.try_lock().unwrap();
+        mem::swap(fb.as_mut(), bb.as_mut());
+    }
+}
@@ -1,28 +1,578 @@
-use std::cell::RefCell;
+use crate::event::DEvent;
+use std::{any, cell::RefCell, collections::VecDeque, fmt, rc::Rc, time::Duration};
 
+/**
+ * Runs one active state, plus a stack of suspended ones underneath it
+ *
+ * A pushed state interrupts the current one, which gets `pause`d and
+ * later `resume`d exactly where it left off when the interrupt pops
+ *
+ * Machines nest: a parent state can own a child machine, handle the
+ * transitions common to its group and pass everything else down.
+ * Children reach the parent's machine with `Transition::up`.
+ *
+ * Guards can veto transitions before anything exits or enters, and a
+ * `Tracer` keeps a history of what happened and why
+ */
 pub struct StateMachine<T> {
     current: RefCell<Option<Box<dyn DState<T>>>>,
+    suspended: RefCell<Vec<Box<dyn DState<T>>>>,
+    names: RefCell<Vec<String>>,
+    guards: RefCell<Vec<Box<Guard<T>>>>,
+    tracer: RefCell<Option<Tracer<T>>>,
 }
 
-impl<T> StateMachine<T> {
+type Guard<T> = dyn Fn(&T, &TransitionRecord) -> bool;
+
+impl<T: 'static> StateMachine<T> {
     pub fn new() -> StateMachine<T> {
         StateMachine {
             current: RefCell::new(None),
+            suspended: RefCell::new(Vec::new()),
+            names: RefCell::new(Vec::new()),
+            guards: RefCell::new(Vec::new()),
+            tracer: RefCell::new(None),
         }
     }
 
+    /**
+     * Only let transitions through while `guard` returns true
+     *
+     * Checked before the current state exits, so a vetoed transition
+     * leaves the machine exactly as it was. Not checked for `init`.
+     */
+    pub fn guard<F>(&self, guard: F)
+    where
+        F: 'static + Fn(&T, &TransitionRecord) -> bool,
+    {
+        self.guards.borrow_mut().push(Box::new(guard));
+    }
+
+    /**
+     * Record every transition from now on, hand the same tracer to child
+     * machines to get their transitions in the same history
+     */
+    pub fn set_tracer(&self, tracer: Tracer<T>) {
+        *self.tracer.borrow_mut() = Some(tracer);
+    }
+
+    pub fn tracer(&self) -> Option<Tracer<T>> {
+        self.tracer.borrow().clone()
+    }
+
     pub fn init<S: 'static + DState<T>>(&self, client: &mut T) {
-        *self.current.borrow_mut() = Some(self.transit::<S>(client));
+        self.init_with(client, short_type_name::<S>(), S::enter);
+    }
+
+    /**
+     * Start the machine with a state built by a closure, see
+     * `Transition::with`
+     */
+    pub fn init_with<F>(&self, client: &mut T, name: &str, enter: F)
+    where
+        F: FnOnce(&StateMachine<T>, &mut T) -> Box<dyn DState<T>>,
+    {
+        let from = self.current_state_name();
+        self.names.borrow_mut().push(String::from(name));
+        self.trace(
+            client,
+            TransitionRecord::new(TransitionKind::Init, from, name, None),
+        );
+        let state = enter(self, client);
+        self.set_current(state);
     }
 
+    /**
+     * Enter a state right away, without exiting anything
+     *
+     * Meant for `enter` implementations that want to redirect to
+     * another state, `update` should return a `Transition` instead
+     */
     pub fn transit<S: 'static + DState<T>>(&self, client: &mut T) -> Box<dyn DState<T>> {
         S::enter(self, client)
     }
 
-    pub fn update(&self, client: &mut T, delta: f32) {
+    /**
+     * Update the active state
+     *
+     * Returns any transition a state sent `up`, for a parent state to
+     * apply to its own machine. The root machine can ignore it.
+     */
+    pub fn update(&self, client: &mut T, delta: f32) -> Option<Transition<T>> {
+        let mut state = self.current.borrow_mut().take().unwrap();
+        let transition = state.update(self, client, delta);
+        self.finish(client, state, transition)
+    }
+
+    pub fn handle_event(&self, client: &mut T, event: &DEvent) -> Option<Transition<T>> {
+        let mut state = self.current.borrow_mut().take().unwrap();
+        let transition = state.handle_event(self, client, event);
+        self.finish(client, state, transition)
+    }
+
+    /**
+     * Apply a transition from outside the machine, e.g. a parent state
+     * switching its child. Must not be called from a state of this
+     * same machine, return the transition instead.
+     */
+    pub fn transition(&self, client: &mut T, transition: Transition<T>) -> Option<Transition<T>> {
         let state = self.current.borrow_mut().take().unwrap();
-        let new = state.update(self, client, delta);
-        *self.current.borrow_mut() = Some(new);
+        self.finish(client, state, Some(transition))
+    }
+
+    /**
+     * Pause the active state, for a parent state being paused itself
+     */
+    pub fn pause(&self, client: &mut T) {
+        let mut state = self.current.borrow_mut().take().unwrap();
+        state.pause(self, client);
+        *self.current.borrow_mut() = Some(state);
+    }
+
+    pub fn resume(&self, client: &mut T) {
+        let mut state = self.current.borrow_mut().take().unwrap();
+        state.resume(self, client);
+        *self.current.borrow_mut() = Some(state);
+    }
+
+    /**
+     * Exit every state on the stack, active one first
+     *
+     * For a parent state's `exit`, so its children get to clean up too.
+     * The machine needs `init` again before it can be updated.
+     */
+    pub fn shutdown(&self, client: &mut T) {
+        if let Some(mut state) = self.current.borrow_mut().take() {
+            state.exit(self, client);
+        }
+        loop {
+            let state = self.suspended.borrow_mut().pop();
+            match state {
+                Some(mut state) => state.exit(self, client),
+                None => break,
+            }
+        }
+        self.names.borrow_mut().clear();
+    }
+
+    /**
+     * Name of the active state, still answers while that state is
+     * running its own callbacks
+     */
+    pub fn current_state_name(&self) -> Option<String> {
+        self.names.borrow().last().cloned()
+    }
+
+    /**
+     * Names of every state on the stack, bottom first, active last
+     */
+    pub fn stack_names(&self) -> Vec<String> {
+        self.names.borrow().clone()
+    }
+
+    /**
+     * Name of the active state at every level of nesting, outermost
+     * first. Stops at the level that's currently running a callback.
+     */
+    pub fn active_path(&self) -> Vec<String> {
+        let mut path = Vec::new();
+        path.extend(self.current_state_name());
+        if let Some(state) = self.current.borrow().as_ref() {
+            if let Some(child) = state.child() {
+                path.extend(child.active_path());
+            }
+        }
+        path
+    }
+
+    /**
+     * Number of states on the stack, including the active one
+     */
+    pub fn depth(&self) -> usize {
+        self.names.borrow().len()
+    }
+
+    fn finish(
+        &self,
+        client: &mut T,
+        state: Box<dyn DState<T>>,
+        transition: Option<Transition<T>>,
+    ) -> Option<Transition<T>> {
+        let mut state = state;
+        let transition = match transition {
+            Some(transition) => transition,
+            None => {
+                *self.current.borrow_mut() = Some(state);
+                return None;
+            }
+        };
+        let Transition { name, kind, reason } = transition;
+        let kind = match kind {
+            Kind::Up(transition) => {
+                *self.current.borrow_mut() = Some(state);
+                return Some(*transition);
+            }
+            kind => kind,
+        };
+        let names = self.stack_names();
+        let to = match kind {
+            // Popping the last state would leave nothing to run
+            Kind::Pop if self.suspended.borrow().is_empty() => {
+                *self.current.borrow_mut() = Some(state);
+                return None;
+            }
+            Kind::Pop => names[names.len() - 2].clone(),
+            _ => name.unwrap_or_default(),
+        };
+        let mut record =
+            TransitionRecord::new(kind.record_kind(), names.last().cloned(), &to, reason);
+        if !self
+            .guards
+            .borrow()
+            .iter()
+            .all(|guard| guard(client, &record))
+        {
+            record.vetoed = true;
+            self.trace(client, record);
+            *self.current.borrow_mut() = Some(state);
+            return None;
+        }
+        self.trace(client, record);
+        // Until the new state is entered, report it by its expected name
+        let name = to;
+        match kind {
+            Kind::Switch(enter) => {
+                state.exit(self, client);
+                drop(state);
+                self.set_name(name);
+                let new = enter(self, client);
+                self.set_current(new);
+            }
+            Kind::Push(enter) => {
+                state.pause(self, client);
+                self.suspended.borrow_mut().push(state);
+                self.names.borrow_mut().push(name);
+                let new = enter(self, client);
+                self.set_current(new);
+            }
+            Kind::Pop => {
+                state.exit(self, client);
+                drop(state);
+                self.names.borrow_mut().pop();
+                let mut resumed = self.suspended.borrow_mut().pop().unwrap();
+                resumed.resume(self, client);
+                *self.current.borrow_mut() = Some(resumed);
+            }
+            Kind::Up(_) => unreachable!(),
+        }
+        None
+    }
+
+    fn trace(&self, client: &T, record: TransitionRecord) {
+        if let Some(tracer) = self.tracer.borrow().as_ref() {
+            tracer.record(client, record);
+        }
+    }
+
+    fn set_current(&self, state: Box<dyn DState<T>>) {
+        self.set_name(String::from(state.name()));
+        *self.current.borrow_mut() = Some(state);
+    }
+
+    fn set_name(&self, name: String) {
+        if let Some(top) = self.names.borrow_mut().last_mut() {
+            *top = name;
+        }
+    }
+}
+
+impl<T: 'static> Default for StateMachine<T> {
+    fn default() -> Self {
+        StateMachine::new()
+    }
+}
+
+type EnterFn<T> = dyn FnOnce(&StateMachine<T>, &mut T) -> Box<dyn DState<T>>;
+
+enum Kind<T> {
+    Switch(Box<EnterFn<T>>),
+    Push(Box<EnterFn<T>>),
+    Pop,
+    Up(Box<Transition<T>>),
+}
+
+impl<T> Kind<T> {
+    fn record_kind(&self) -> TransitionKind {
+        match self {
+            Kind::Switch(_) | Kind::Up(_) => TransitionKind::Switch,
+            Kind::Push(_) => TransitionKind::Push,
+            Kind::Pop => TransitionKind::Pop,
+        }
+    }
+}
+
+/**
+ * What the state machine should do after a state callback
+ *
+ * Target states are entered only after the current state exits (or
+ * pauses, for a push)
+ */
+pub struct Transition<T> {
+    name: Option<String>,
+    kind: Kind<T>,
+    reason: Option<String>,
+}
+
+impl<T: 'static> Transition<T> {
+    /**
+     * Replace the current state
+     */
+    pub fn to<S: 'static + DState<T>>() -> Transition<T> {
+        Transition {
+            name: Some(String::from(short_type_name::<S>())),
+            kind: Kind::Switch(Box::new(S::enter)),
+            reason: None,
+        }
+    }
+
+    /**
+     * Replace the current state with one built by a closure, for states
+     * that need data passed in or share one type between several names
+     */
+    pub fn with<F>(name: &str, enter: F) -> Transition<T>
+    where
+        F: 'static + FnOnce(&StateMachine<T>, &mut T) -> Box<dyn DState<T>>,
+    {
+        Transition {
+            name: Some(String::from(name)),
+            kind: Kind::Switch(Box::new(enter)),
+            reason: None,
+        }
+    }
+
+    /**
+     * Suspend the current state and run another on top of it
+     */
+    pub fn push<S: 'static + DState<T>>() -> Transition<T> {
+        Transition {
+            name: Some(String::from(short_type_name::<S>())),
+            kind: Kind::Push(Box::new(S::enter)),
+            reason: None,
+        }
+    }
+
+    pub fn push_with<F>(name: &str, enter: F) -> Transition<T>
+    where
+        F: 'static + FnOnce(&StateMachine<T>, &mut T) -> Box<dyn DState<T>>,
+    {
+        Transition {
+            name: Some(String::from(name)),
+            kind: Kind::Push(Box::new(enter)),
+            reason: None,
+        }
+    }
+
+    /**
+     * Exit the current state and resume the one it interrupted
+     *
+     * Ignored if nothing is suspended underneath
+     */
+    pub fn pop() -> Transition<T> {
+        Transition {
+            name: None,
+            kind: Kind::Pop,
+            reason: None,
+        }
+    }
+
+    /**
+     * Hand a transition to the machine one level up, leaving this
+     * machine as it is
+     */
+    pub fn up(transition: Transition<T>) -> Transition<T> {
+        Transition {
+            name: transition.name.clone(),
+            reason: transition.reason.clone(),
+            kind: Kind::Up(Box::new(transition)),
+        }
+    }
+
+    /**
+     * Say why, for the transition history
+     */
+    pub fn because<R: Into<String>>(mut self, reason: R) -> Transition<T> {
+        let reason = reason.into();
+        if let Kind::Up(transition) = &mut self.kind {
+            transition.reason = Some(reason.clone());
+        }
+        self.reason = Some(reason);
+        self
+    }
+
+    /**
+     * Name of the state this enters, None for a pop
+     */
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionKind {
+    Init,
+    Switch,
+    Push,
+    Pop,
+}
+
+/**
+ * One transition, as seen by guards and kept by a `Tracer`
+ *
+ * `time` is zero until the transition gets recorded
+ */
+#[derive(Clone, Debug)]
+pub struct TransitionRecord {
+    pub time: Duration,
+    pub kind: TransitionKind,
+    pub from: Option<String>,
+    pub to: String,
+    pub reason: Option<String>,
+    pub vetoed: bool,
+}
+
+impl TransitionRecord {
+    fn new(
+        kind: TransitionKind,
+        from: Option<String>,
+        to: &str,
+        reason: Option<String>,
+    ) -> TransitionRecord {
+        TransitionRecord {
+            time: Duration::from_secs(0),
+            kind,
+            from,
+            to: String::from(to),
+            reason,
+            vetoed: false,
+        }
+    }
+}
+
+impl fmt::Display for TransitionRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>9.3}s {} -> {}",
+            self.time.as_secs_f64(),
+            self.from.as_deref().unwrap_or("*"),
+            self.to
+        )?;
+        match self.kind {
+            TransitionKind::Init => write!(f, " (init)")?,
+            TransitionKind::Push => write!(f, " (push)")?,
+            TransitionKind::Pop => write!(f, " (pop)")?,
+            TransitionKind::Switch => (),
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        if self.vetoed {
+            write!(f, " [vetoed]")?;
+        }
+        Ok(())
+    }
+}
+
+struct TraceLog {
+    capacity: usize,
+    echo: bool,
+    records: VecDeque<TransitionRecord>,
+}
+
+/**
+ * Bounded history of transitions, oldest dropped first
+ *
+ * Timestamps come from `now`, which gets the client so it can read
+ * whatever clock the client runs on. Clones share the same history.
+ */
+pub struct Tracer<T> {
+    log: Rc<RefCell<TraceLog>>,
+    now: Rc<dyn Fn(&T) -> Duration>,
+}
+
+impl<T> Clone for Tracer<T> {
+    fn clone(&self) -> Self {
+        Tracer {
+            log: Rc::clone(&self.log),
+            now: Rc::clone(&self.now),
+        }
+    }
+}
+
+impl<T> Tracer<T> {
+    pub fn new<F>(capacity: usize, now: F) -> Tracer<T>
+    where
+        F: 'static + Fn(&T) -> Duration,
+    {
+        Tracer {
+            log: Rc::new(RefCell::new(TraceLog {
+                capacity,
+                echo: false,
+                records: VecDeque::new(),
+            })),
+            now: Rc::new(now),
+        }
+    }
+
+    /**
+     * Also print every transition to stderr as it happens
+     */
+    pub fn echo(self, echo: bool) -> Tracer<T> {
+        self.log.borrow_mut().echo = echo;
+        self
+    }
+
+    /**
+     * Everything still kept, oldest first
+     */
+    pub fn history(&self) -> Vec<TransitionRecord> {
+        self.log.borrow().records.iter().cloned().collect()
+    }
+
+    /**
+     * The history, one transition per line
+     */
+    pub fn dump(&self) -> String {
+        let log = self.log.borrow();
+        let mut dump = String::new();
+        for record in &log.records {
+            dump.push_str(&record.to_string());
+            dump.push('\n');
+        }
+        dump
+    }
+
+    pub fn clear(&self) {
+        self.log.borrow_mut().records.clear();
+    }
+
+    fn record(&self, client: &T, mut record: TransitionRecord) {
+        record.time = (self.now)(client);
+        let mut log = self.log.borrow_mut();
+        if log.echo {
+            eprintln!("{}", record);
+        }
+        if log.capacity == 0 {
+            return;
+        }
+        if log.records.len() == log.capacity {
+            log.records.pop_front();
+        }
+        log.records.push_back(record);
     }
 }
 
@@ -30,10 +580,251 @@ pub trait DState<T> {
     fn enter(sm: &StateMachine<T>, client: &mut T) -> Box<dyn DState<T>>
     where
         Self: Sized;
-    fn update(
-        self: Box<Self>,
-        sm: &StateMachine<T>,
-        client: &mut T,
-        delta: f32,
-    ) -> Box<dyn DState<T>>;
+
+    /**
+     * Called every tick, return a transition to leave this state
+     */
+    fn update(&mut self, sm: &StateMachine<T>, client: &mut T, delta: f32)
+        -> Option<Transition<T>>;
+
+    /**
+     * Called for every queued event before `update` on the same tick
+     *
+     * Return a transition to leave this state, or None to stay in it
+     */
+    fn handle_event(
+        &mut self,
+        _sm: &StateMachine<T>,
+        _client: &mut T,
+        _event: &DEvent,
+    ) -> Option<Transition<T>> {
+        None
+    }
+
+    /**
+     * Called on every transition away from this state, before the next
+     * state is entered
+     */
+    fn exit(&mut self, _sm: &StateMachine<T>, _client: &mut T) {}
+
+    /**
+     * Called when another state is pushed on top of this one
+     */
+    fn pause(&mut self, _sm: &StateMachine<T>, _client: &mut T) {}
+
+    /**
+     * Called when the state pushed on top of this one pops, this state
+     * is active again from the next callback on
+     */
+    fn resume(&mut self, _sm: &StateMachine<T>, _client: &mut T) {}
+
+    /**
+     * The child machine of a parent state, so the full active path can
+     * be reported
+     */
+    fn child(&self) -> Option<&StateMachine<T>> {
+        None
+    }
+
+    /**
+     * Stable name for logging and assertions, defaults to the type name
+     */
+    fn name(&self) -> &str {
+        short_type_name::<Self>()
+    }
+}
+
+/**
+ * `std::any::type_name` without the module path, `a::b::Foo<c::Bar>`
+ * becomes `Foo<c::Bar>`
+ */
+pub fn short_type_name<S: ?Sized>() -> &'static str {
+    let full = any::type_name::<S>();
+    let end = full.find('<').unwrap_or(full.len());
+    let start = full[..end].rfind("::").map(|i| i + 2).unwrap_or(0);
+    &full[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+
+    struct Client {
+        clock: ManualClock,
+        // Transition the active state returns on its next update
+        next: Option<Transition<Client>>,
+        log: Vec<String>,
+    }
+
+    impl Client {
+        fn new() -> Client {
+            Client {
+                clock: ManualClock::new(0),
+                next: None,
+                log: Vec::new(),
+            }
+        }
+    }
+
+    macro_rules! test_state {
+        ($name:ident) => {
+            struct $name;
+
+            impl DState<Client> for $name {
+                fn enter(
+                    _sm: &StateMachine<Client>,
+                    client: &mut Client,
+                ) -> Box<dyn DState<Client>> {
+                    client.log.push(format!("enter {}", stringify!($name)));
+                    Box::new($name)
+                }
+
+                fn update(
+                    &mut self,
+                    _sm: &StateMachine<Client>,
+                    client: &mut Client,
+                    _delta: f32,
+                ) -> Option<Transition<Client>> {
+                    client.next.take()
+                }
+
+                fn exit(&mut self, _sm: &StateMachine<Client>, client: &mut Client) {
+                    client.log.push(format!("exit {}", stringify!($name)));
+                }
+
+                fn pause(&mut self, _sm: &StateMachine<Client>, client: &mut Client) {
+                    client.log.push(format!("pause {}", stringify!($name)));
+                }
+
+                fn resume(&mut self, _sm: &StateMachine<Client>, client: &mut Client) {
+                    client.log.push(format!("resume {}", stringify!($name)));
+                }
+            }
+        };
+    }
+
+    test_state!(Idle);
+    test_state!(Walk);
+    test_state!(Nap);
+
+    fn machine(client: &mut Client) -> (StateMachine<Client>, Tracer<Client>) {
+        let sm = StateMachine::new();
+        let tracer = Tracer::new(10, |client: &Client| client.clock.now());
+        sm.set_tracer(tracer.clone());
+        sm.init::<Idle>(client);
+        (sm, tracer)
+    }
+
+    fn step(sm: &StateMachine<Client>, client: &mut Client, transition: Transition<Client>) {
+        client.clock.advance(Duration::from_millis(500));
+        client.next = Some(transition);
+        sm.update(client, 0.5);
+    }
+
+    fn history(tracer: &Tracer<Client>) -> Vec<String> {
+        tracer
+            .history()
+            .iter()
+            .map(|record| String::from(record.to_string().trim_start()))
+            .collect()
+    }
+
+    #[test]
+    fn exits_before_entering_lazily() {
+        let mut client = Client::new();
+        let sm = StateMachine::new();
+        sm.init::<Idle>(&mut client);
+        step(&sm, &mut client, Transition::to::<Walk>());
+        assert_eq!(sm.current_state_name().as_deref(), Some("Walk"));
+        // Built only once Walk is gone
+        let lazy = Transition::with("Idle", |sm: &StateMachine<Client>, client: &mut Client| {
+            client.log.push(String::from("build"));
+            sm.transit::<Idle>(client)
+        });
+        step(&sm, &mut client, lazy);
+
+        assert_eq!(sm.current_state_name().as_deref(), Some("Idle"));
+        assert_eq!(
+            client.log,
+            [
+                "enter Idle",
+                "exit Idle",
+                "enter Walk",
+                "exit Walk",
+                "build",
+                "enter Idle",
+            ]
+        );
+    }
+
+    #[test]
+    fn switches_pushes_and_pops() {
+        let mut client = Client::new();
+        let (sm, tracer) = machine(&mut client);
+        step(&sm, &mut client, Transition::to::<Walk>().because("bored"));
+        step(&sm, &mut client, Transition::push::<Nap>());
+        assert_eq!(sm.stack_names(), ["Walk", "Nap"]);
+        step(&sm, &mut client, Transition::pop().because("rested"));
+        // Nothing underneath to pop back to, so nothing happens
+        step(&sm, &mut client, Transition::pop());
+
+        assert_eq!(sm.current_state_name().as_deref(), Some("Walk"));
+        assert_eq!(
+            client.log,
+            [
+                "enter Idle",
+                "exit Idle",
+                "enter Walk",
+                "pause Walk",
+                "enter Nap",
+                "exit Nap",
+                "resume Walk",
+            ]
+        );
+        assert_eq!(
+            history(&tracer),
+            [
+                "0.000s * -> Idle (init)",
+                "0.500s Idle -> Walk: bored",
+                "1.000s Walk -> Nap (push)",
+                "1.500s Nap -> Walk (pop): rested",
+            ]
+        );
+    }
+
+    #[test]
+    fn vetoed_transitions_change_nothing() {
+        let mut client = Client::new();
+        let (sm, tracer) = machine(&mut client);
+        sm.guard(|_, record| record.to != "Nap");
+        step(&sm, &mut client, Transition::to::<Nap>());
+        step(&sm, &mut client, Transition::to::<Walk>());
+
+        assert_eq!(client.log, ["enter Idle", "exit Idle", "enter Walk"]);
+        assert_eq!(
+            history(&tracer),
+            [
+                "0.000s * -> Idle (init)",
+                "0.500s Idle -> Nap [vetoed]",
+                "1.000s Idle -> Walk",
+            ]
+        );
+    }
+
+    #[test]
+    fn tracer_keeps_the_latest() {
+        let mut client = Client::new();
+        let sm = StateMachine::new();
+        let tracer = Tracer::new(2, |client: &Client| client.clock.now());
+        sm.set_tracer(tracer.clone());
+        sm.init::<Idle>(&mut client);
+        step(&sm, &mut client, Transition::to::<Walk>());
+        step(&sm, &mut client, Transition::to::<Idle>());
+
+        assert_eq!(
+            history(&tracer),
+            ["0.500s Idle -> Walk", "1.000s Walk -> Idle"]
+        );
+    }
 }
@@ -1,4 +1,15 @@
 pub mod anim;
+pub mod beat;
+pub mod behavior;
+pub mod bt;
+pub mod clock;
 pub mod dwindow;
 pub mod event;
+pub mod frame;
 pub mod fsm;
+pub mod observer;
+pub mod rng;
+pub mod term;
+pub mod text;
+pub mod timer;
+pub mod utility;
@@ -0,0 +1,301 @@
+use crate::event::{DEvent, EventQueue};
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    sync::{mpsc, Arc, Weak},
+    thread,
+};
+
+type Callback<E> = Arc<dyn Fn(&E) + Send + Sync>;
+
+struct Inner<E> {
+    next_id: u64,
+    callbacks: Vec<(u64, Callback<E>)>,
+    // Feeds the delivery thread, started by the first `post`
+    posted: Option<mpsc::Sender<E>>,
+}
+
+/**
+ * Callbacks to tell about events of type `E`, for services to report
+ * changes from whatever thread they happen on
+ *
+ * Callbacks are called with no lock held, so they can subscribe,
+ * unsubscribe or call back into whoever notified. Each `subscribe`
+ * hands back a `Subscription` that unsubscribes when dropped. Cloning
+ * gives another handle to the same callbacks.
+ */
+pub struct Observers<E> {
+    inner: Arc<Mutex<Inner<E>>>,
+}
+
+impl<E> Clone for Observers<E> {
+    fn clone(&self) -> Self {
+        Observers {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<E: 'static + Send> Observers<E> {
+    pub fn new() -> Observers<E> {
+        Observers {
+            inner: Arc::new(Mutex::new(Inner {
+                next_id: 0,
+                callbacks: Vec::new(),
+                posted: None,
+            })),
+        }
+    }
+
+    /**
+     * Call `callback` with every event until the subscription drops
+     */
+    pub fn subscribe<F>(&self, callback: F) -> Subscription
+    where
+        F: 'static + Fn(&E) + Send + Sync,
+    {
+        let id = {
+            let mut inner = self.inner.lock();
+            let id = inner.next_id;
+            inner.next_id += 1;
+            inner.callbacks.push((id, Arc::new(callback)));
+            id
+        };
+        let weak = Arc::downgrade(&self.inner);
+        Subscription {
+            unsubscribe: Some(Box::new(move || {
+                if let Some(inner) = weak.upgrade() {
+                    // Dropped once unlocked, the callback may hold
+                    // subscriptions of its own
+                    let removed = {
+                        let mut inner = inner.lock();
+                        let index = inner.callbacks.iter().position(|(other, _)| *other == id);
+                        index.map(|index| inner.callbacks.remove(index))
+                    };
+                    drop(removed);
+                }
+            })),
+        }
+    }
+
+    /**
+     * Push every event onto a queue, mapped to a `DEvent` or skipped,
+     * so the game thread sees it on its next tick instead of whenever
+     * it happens
+     */
+    pub fn forward_with<F>(&self, queue: &EventQueue, map: F) -> Subscription
+    where
+        F: 'static + Fn(&E) -> Option<DEvent> + Send + Sync,
+    {
+        let queue = queue.clone();
+        self.subscribe(move |event| {
+            if let Some(event) = map(event) {
+                queue.push(event);
+            }
+        })
+    }
+
+    /**
+     * Push every event onto a queue as a `DEvent::Custom`
+     */
+    pub fn forward(&self, queue: &EventQueue) -> Subscription
+    where
+        E: Any + Clone + Sync,
+    {
+        self.forward_with(queue, |event| Some(DEvent::custom(event.clone())))
+    }
+
+    /**
+     * Call every callback now, on this thread
+     *
+     * A callback unsubscribed by another one during the same
+     * notification may still be called this once
+     */
+    pub fn notify(&self, event: &E) {
+        deliver(&self.inner, event);
+    }
+
+    /**
+     * Queue an event to be delivered on a thread of its own, in the
+     * order posted, for notifying while holding a lock callbacks might
+     * need, or from a thread that shouldn't wait on them
+     */
+    pub fn post(&self, event: E) {
+        let mut inner = self.inner.lock();
+        let sender = match &inner.posted {
+            Some(sender) => sender.clone(),
+            None => {
+                let (sender, receiver) = mpsc::channel();
+                let weak = Arc::downgrade(&self.inner);
+                thread::spawn(move || run_posted(weak, receiver));
+                inner.posted = Some(sender.clone());
+                sender
+            }
+        };
+        drop(inner);
+        let _ = sender.send(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().callbacks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<E: 'static + Send> Default for Observers<E> {
+    fn default() -> Self {
+        Observers::new()
+    }
+}
+
+/**
+ * Snapshot the callbacks, then call them with the lock released
+ */
+fn deliver<E>(inner: &Mutex<Inner<E>>, event: &E) {
+    let callbacks = inner
+        .lock()
+        .callbacks
+        .iter()
+        .map(|(_, callback)| callback.clone())
+        .collect::<Vec<_>>();
+    for callback in callbacks {
+        callback(event);
+    }
+}
+
+/**
+ * Delivery thread behind `post`, ends once every `Observers` handle is
+ * gone and with it the sending end
+ */
+fn run_posted<E>(inner: Weak<Mutex<Inner<E>>>, receiver: mpsc::Receiver<E>) {
+    for event in receiver {
+        match inner.upgrade() {
+            Some(inner) => deliver(&inner, &event),
+            None => return,
+        }
+    }
+}
+
+/**
+ * Keeps a callback subscribed for as long as it's alive
+ */
+#[must_use = "the callback is unsubscribed as soon as this is dropped"]
+pub struct Subscription {
+    unsubscribe: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl Subscription {
+    /**
+     * Unsubscribe now, same as dropping it
+     */
+    pub fn cancel(self) {}
+
+    /**
+     * Stay subscribed for as long as the observers exist
+     */
+    pub fn detach(mut self) {
+        self.unsubscribe = None;
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.unsubscribe.take() {
+            unsubscribe();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Everything the callbacks saw, in order
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn logger(log: &Log, name: &'static str) -> impl Fn(&u32) + Send + Sync {
+        let log = log.clone();
+        move |event| log.lock().push(format!("{} {}", name, event))
+    }
+
+    #[test]
+    fn unsubscribes_on_drop_unless_detached() {
+        let observers = Observers::new();
+        let log = Log::default();
+        let dropped = observers.subscribe(logger(&log, "dropped"));
+        let cancelled = observers.subscribe(logger(&log, "cancelled"));
+        observers.subscribe(logger(&log, "detached")).detach();
+        observers.notify(&1);
+        drop(dropped);
+        cancelled.cancel();
+        observers.notify(&2);
+        assert_eq!(observers.len(), 1);
+        assert_eq!(
+            *log.lock(),
+            ["dropped 1", "cancelled 1", "detached 1", "detached 2"]
+        );
+    }
+
+    #[test]
+    fn callbacks_can_reenter() {
+        let observers = Observers::new();
+        let log = Log::default();
+        let subscriptions = Mutex::new(Vec::new());
+        let (handle, inner_log) = (observers.clone(), log.clone());
+        let _outer = observers.subscribe(move |event: &u32| {
+            inner_log.lock().push(format!("outer {}", event));
+            // Subscribe and notify again from inside a notification
+            if *event == 1 {
+                let subscription = handle.subscribe(logger(&inner_log, "inner"));
+                subscriptions.lock().push(subscription);
+                handle.notify(&2);
+            }
+        });
+        observers.notify(&1);
+        assert_eq!(*log.lock(), ["outer 1", "outer 2", "inner 2"]);
+        assert_eq!(observers.len(), 2);
+    }
+
+    #[test]
+    fn posts_in_order_on_another_thread() {
+        let observers = Observers::new();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let _subscription = observers.subscribe(move |event: &u32| {
+            let _ = sender.lock().send((*event, thread::current().id()));
+        });
+        for event in 0..10 {
+            observers.post(event);
+        }
+        let delivered = (0..10)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect::<Vec<_>>();
+        let events = delivered
+            .iter()
+            .map(|(event, _)| *event)
+            .collect::<Vec<_>>();
+        assert_eq!(events, (0..10).collect::<Vec<_>>());
+        let delivery = delivered[0].1;
+        assert_ne!(delivery, thread::current().id());
+        assert!(delivered.iter().all(|(_, thread)| *thread == delivery));
+    }
+
+    #[test]
+    fn forwards_as_custom_events() {
+        let observers = Observers::new();
+        let queue = EventQueue::new();
+        let _subscription = observers.forward(&queue);
+        observers.notify(&7u32);
+        observers.notify(&8u32);
+        let events = queue
+            .drain()
+            .iter()
+            .map(|event| event.as_custom::<u32>().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(events, [Some(7), Some(8)]);
+    }
+}
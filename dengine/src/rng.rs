@@ -0,0 +1,78 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/**
+ * Small seeded random number generator (PCG32)
+ *
+ * Implemented here rather than pulled from `rand` so a seed gives the
+ * same numbers forever, which recordings rely on. Not for anything
+ * security related.
+ */
+#[derive(Clone, Debug)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { seed, state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /**
+     * Seeded from whatever randomness std has, for real runs. Read
+     * `seed()` to reproduce one later.
+     */
+    pub fn from_entropy() -> Rng {
+        Rng::new(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /**
+     * Uniform in `[0, 1)`
+     */
+    pub fn f32(&mut self) -> f32 {
+        // 24 bits is all the precision an f32 has in that range
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /**
+     * Uniform in `[min, max)`
+     */
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.f32() * (max - min)
+    }
+
+    /**
+     * Uniform in `0..n`, 0 if `n` is 0
+     */
+    pub fn below(&mut self, n: u32) -> u32 {
+        ((self.next_u32() as u64 * n as u64) >> 32) as u32
+    }
+
+    /**
+     * True with probability `p`
+     */
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.f32() < p
+    }
+}
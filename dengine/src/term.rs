@@ -0,0 +1,373 @@
+use crate::frame::Frame;
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Take Ctrl-C as a request to stop instead of being killed by it, so a
+ * drawing loop that checks `interrupted()` gets to `end()` and bring
+ * the cursor back
+ */
+pub fn catch_interrupt() {
+    signal::install();
+}
+
+/**
+ * Whether Ctrl-C was pressed since `catch_interrupt()`
+ */
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+mod signal {
+    use std::sync::atomic::Ordering;
+
+    extern "C" fn handle(_signal: libc::c_int) {
+        super::INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn install() {
+        let handle = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        unsafe {
+            libc::signal(libc::SIGINT, handle);
+            libc::signal(libc::SIGTERM, handle);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod signal {
+    use std::sync::atomic::Ordering;
+    use winapi::{
+        shared::minwindef::{BOOL, DWORD, FALSE, TRUE},
+        um::{
+            consoleapi::SetConsoleCtrlHandler,
+            wincon::{CTRL_BREAK_EVENT, CTRL_C_EVENT},
+        },
+    };
+
+    unsafe extern "system" fn handle(kind: DWORD) -> BOOL {
+        match kind {
+            CTRL_C_EVENT | CTRL_BREAK_EVENT => {
+                super::INTERRUPTED.store(true, Ordering::SeqCst);
+                TRUE
+            }
+            _ => FALSE,
+        }
+    }
+
+    pub fn install() {
+        unsafe {
+            SetConsoleCtrlHandler(Some(handle), TRUE);
+        }
+    }
+}
+
+/**
+ * How pixels get into the terminal
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TermGraphics {
+    /** Two pixels per cell using `▀` with truecolor fg/bg, works almost anywhere */
+    HalfBlock,
+    /** DEC sixel graphics (xterm -ti vt340, mlterm, foot, WezTerm...) */
+    Sixel,
+    /** kitty graphics protocol (kitty, WezTerm, Konsole) */
+    Kitty,
+}
+
+impl TermGraphics {
+    /**
+     * Best guess from the environment
+     *
+     * Terminals can't be reliably queried for sixel support without
+     * reading replies from the tty, so `DENGINE_GRAPHICS` overrides
+     */
+    pub fn detect() -> TermGraphics {
+        if let Some(graphics) = env::var("DENGINE_GRAPHICS")
+            .ok()
+            .and_then(|name| TermGraphics::from_name(&name))
+        {
+            return graphics;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var("KITTY_WINDOW_ID").is_ok() || term.contains("kitty") {
+            TermGraphics::Kitty
+        } else if program == "WezTerm" || term.contains("mlterm") || term.starts_with("foot") {
+            TermGraphics::Sixel
+        } else {
+            TermGraphics::HalfBlock
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TermGraphics> {
+        match name {
+            "halfblock" | "half" => Some(TermGraphics::HalfBlock),
+            "sixel" => Some(TermGraphics::Sixel),
+            "kitty" => Some(TermGraphics::Kitty),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Draws `Frame`s into a terminal, each draw replaces the last one
+ */
+pub struct TermRenderer<W: Write> {
+    out: W,
+    graphics: TermGraphics,
+    scale: u32,
+    background: [u8; 3],
+}
+
+impl<W: Write> TermRenderer<W> {
+    pub fn new(out: W, graphics: TermGraphics) -> TermRenderer<W> {
+        TermRenderer {
+            out,
+            graphics,
+            scale: 1,
+            background: [0, 0, 0],
+        }
+    }
+
+    /**
+     * Integer upscale for sixel and kitty, where one frame pixel would
+     * otherwise be one (tiny) screen pixel. Ignored for half-blocks.
+     */
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    /**
+     * Color transparent pixels are blended over
+     */
+    pub fn background(mut self, background: [u8; 3]) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn graphics(&self) -> TermGraphics {
+        self.graphics
+    }
+
+    /**
+     * Clear the screen and hide the cursor, call once before the first
+     * `draw()`, and `catch_interrupt()` first to still get to `end()`
+     * on Ctrl-C
+     */
+    pub fn begin(&mut self) -> io::Result<()> {
+        write!(self.out, "\x1b[2J\x1b[?25l")?;
+        self.out.flush()
+    }
+
+    /**
+     * Restore the cursor and leave it under the image
+     */
+    pub fn end(&mut self) -> io::Result<()> {
+        if self.graphics == TermGraphics::Kitty {
+            write!(self.out, "\x1b_Ga=d,d=i,i=1\x1b\\")?;
+        }
+        writeln!(self.out, "\x1b[0m\x1b[?25h")?;
+        self.out.flush()
+    }
+
+    pub fn draw(&mut self, frame: &Frame) -> io::Result<()> {
+        write!(self.out, "\x1b[H")?;
+        match self.graphics {
+            TermGraphics::HalfBlock => self.draw_half_block(frame)?,
+            TermGraphics::Sixel => self.draw_sixel(frame)?,
+            TermGraphics::Kitty => self.draw_kitty(frame)?,
+        }
+        self.out.flush()
+    }
+
+    fn blend(&self, pixel: &[u8]) -> [u8; 3] {
+        let alpha = pixel[3] as u32;
+        let mut color = [0; 3];
+        for (i, channel) in color.iter_mut().enumerate() {
+            *channel =
+                ((pixel[i] as u32 * alpha + self.background[i] as u32 * (255 - alpha)) / 255) as u8;
+        }
+        color
+    }
+
+    fn pixel(frame: &Frame, x: u32, y: u32) -> &[u8] {
+        let i = ((y * frame.width + x) * 4) as usize;
+        &frame.buffer[i..i + 4]
+    }
+
+    fn draw_half_block(&mut self, frame: &Frame) -> io::Result<()> {
+        for y in (0..frame.height).step_by(2) {
+            for x in 0..frame.width {
+                let top = self.blend(Self::pixel(frame, x, y));
+                let bottom = match y + 1 < frame.height {
+                    true => self.blend(Self::pixel(frame, x, y + 1)),
+                    false => self.background,
+                };
+                write!(
+                    self.out,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                )?;
+            }
+            write!(self.out, "\x1b[0m\r\n")?;
+        }
+        Ok(())
+    }
+
+    fn draw_sixel(&mut self, frame: &Frame) -> io::Result<()> {
+        let width = frame.width * self.scale;
+        let height = frame.height * self.scale;
+
+        // Pixel art has few colors, so an exact palette is usually fine,
+        // anything past the 256 registers maps to the closest one so far
+        let mut palette: HashMap<[u8; 3], usize> = HashMap::new();
+        let mut indices = Vec::with_capacity((frame.width * frame.height) as usize);
+        for pixel in frame.buffer.chunks_exact(4) {
+            let color = self.blend(pixel);
+            let index = match palette.get(&color) {
+                Some(index) => *index,
+                None if palette.len() < 256 => {
+                    let index = palette.len();
+                    palette.insert(color, index);
+                    index
+                }
+                None => {
+                    *palette
+                        .iter()
+                        .min_by_key(|(other, _)| {
+                            (0..3)
+                                .map(|i| (other[i] as i32 - color[i] as i32).pow(2))
+                                .sum::<i32>()
+                        })
+                        .unwrap()
+                        .1
+                }
+            };
+            indices.push(index);
+        }
+
+        write!(self.out, "\x1bPq\"1;1;{};{}", width, height)?;
+        for (color, index) in &palette {
+            write!(
+                self.out,
+                "#{};2;{};{};{}",
+                index,
+                color[0] as u32 * 100 / 255,
+                color[1] as u32 * 100 / 255,
+                color[2] as u32 * 100 / 255
+            )?;
+        }
+
+        // Sixels are bands of 6 rows, each color in a band is one pass
+        for band in (0..height).step_by(6) {
+            for index in 0..palette.len() {
+                let mut line = Vec::with_capacity(width as usize);
+                let mut used = false;
+                for x in 0..width {
+                    let mut bits = 0;
+                    for row in 0..6 {
+                        let y = band + row;
+                        if y >= height {
+                            break;
+                        }
+                        let i = (y / self.scale * frame.width + x / self.scale) as usize;
+                        if indices[i] == index {
+                            bits |= 1 << row;
+                        }
+                    }
+                    used |= bits != 0;
+                    line.push(63 + bits as u8);
+                }
+                if used {
+                    write!(self.out, "#{}", index)?;
+                    write_run_length(&mut self.out, &line)?;
+                    write!(self.out, "$")?;
+                }
+            }
+            write!(self.out, "-")?;
+        }
+        write!(self.out, "\x1b\\")
+    }
+
+    fn draw_kitty(&mut self, frame: &Frame) -> io::Result<()> {
+        let width = frame.width * self.scale;
+        let height = frame.height * self.scale;
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(Self::pixel(frame, x / self.scale, y / self.scale));
+            }
+        }
+
+        // Reuse image id 1 so each draw replaces the previous one
+        let encoded = base64(&data);
+        let chunks = encoded.as_bytes().chunks(4096).collect::<Vec<_>>();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = (i + 1 < chunks.len()) as u8;
+            if i == 0 {
+                write!(
+                    self.out,
+                    "\x1b_Ga=T,f=32,i=1,q=2,s={},v={},m={};",
+                    width, height, more
+                )?;
+            } else {
+                write!(self.out, "\x1b_Gm={};", more)?;
+            }
+            self.out.write_all(chunk)?;
+            write!(self.out, "\x1b\\")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_run_length<W: Write>(out: &mut W, line: &[u8]) -> io::Result<()> {
+    let mut i = 0;
+    while i < line.len() {
+        let mut run = 1;
+        while i + run < line.len() && line[i + run] == line[i] {
+            run += 1;
+        }
+        if run > 3 {
+            write!(out, "!{}{}", run, line[i] as char)?;
+        } else {
+            for _ in 0..run {
+                out.write_all(&[line[i]])?;
+            }
+        }
+        i += run;
+    }
+    Ok(())
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        out.push(TABLE[(n >> 18) as usize & 63] as char);
+        out.push(TABLE[(n >> 12) as usize & 63] as char);
+        out.push(match chunk.len() > 1 {
+            true => TABLE[(n >> 6) as usize & 63] as char,
+            false => '=',
+        });
+        out.push(match chunk.len() > 2 {
+            true => TABLE[n as usize & 63] as char,
+            false => '=',
+        });
+    }
+    out
+}
@@ -0,0 +1,149 @@
+use crate::frame::Frame;
+
+/** Pixels a glyph is wide, not counting the gap after it */
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+/** Pixels between glyphs, and between lines */
+pub const SPACING: u32 = 1;
+
+// Rows top to bottom, the left column in the highest of 3 bits.
+// Lowercase is drawn as uppercase, anything missing as `?`.
+const GLYPHS: &[(char, [u8; 5])] = &[
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('"', [0b101, 0b101, 0b000, 0b000, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('&', [0b010, 0b101, 0b010, 0b101, 0b011]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('♪', [0b011, 0b010, 0b010, 0b110, 0b110]),
+];
+
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == c)
+        .or_else(|| GLYPHS.iter().find(|(glyph, _)| *glyph == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
+
+/**
+ * Pixel size of one line of text in the built-in font
+ */
+pub fn measure(line: &str) -> (u32, u32) {
+    let count = line.chars().count() as u32;
+    match count {
+        0 => (0, GLYPH_HEIGHT),
+        _ => (count * (GLYPH_WIDTH + SPACING) - SPACING, GLYPH_HEIGHT),
+    }
+}
+
+/**
+ * Split text into lines no wider than `max_width` pixels, breaking
+ * between words where possible
+ *
+ * Anything past `max_lines` is cut off, ending the last line with `..`
+ */
+pub fn wrap(text: &str, max_width: u32, max_lines: usize) -> Vec<String> {
+    let per_line = ((max_width + SPACING) / (GLYPH_WIDTH + SPACING)).max(1) as usize;
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word.chars().collect::<Vec<_>>();
+        let length = line.chars().count();
+        if length > 0 && length + 1 + word.len() <= per_line {
+            line.push(' ');
+            line.extend(word);
+            continue;
+        }
+        if length > 0 {
+            lines.push(line);
+        }
+        // Hard break words too long for a line of their own
+        while word.len() > per_line {
+            lines.push(word.drain(..per_line).collect());
+        }
+        line = word.into_iter().collect();
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            let mut chars = last.chars().collect::<Vec<_>>();
+            chars.truncate(per_line.saturating_sub(2));
+            *last = chars.into_iter().collect::<String>().trim_end().to_string() + "..";
+        }
+    }
+    lines
+}
+
+/**
+ * Draw one line with its top-left corner at `x`, `y`, clipping
+ * anything outside the buffer
+ */
+pub fn draw(buffer: &mut Frame, line: &str, x: i32, y: i32, color: [u8; 4]) {
+    let (width, height) = buffer.size();
+    for (i, c) in line.chars().enumerate() {
+        let left = x + (i as u32 * (GLYPH_WIDTH + SPACING)) as i32;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let (px, py) = (left + column as i32, y + row as i32);
+                if px < 0 || py < 0 || px >= width as i32 || py >= height as i32 {
+                    continue;
+                }
+                let i = ((py as u32 * width + px as u32) * 4) as usize;
+                buffer.get_mut()[i..i + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
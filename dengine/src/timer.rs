@@ -0,0 +1,514 @@
+use crate::event::DEvent;
+use parking_lot::Mutex;
+use std::{mem, sync::Arc};
+
+/**
+ * Countdown driven by update deltas
+ *
+ * Only moves when ticked, so one kept in a state freezes whenever that
+ * state isn't being updated, like while another state is pushed over it
+ */
+#[derive(Clone, Debug)]
+pub struct Timer {
+    duration: f32,
+    elapsed: f32,
+    repeating: bool,
+    paused: bool,
+    finished: bool,
+}
+
+impl Timer {
+    /**
+     * Goes off once after `duration` seconds
+     */
+    pub fn new(duration: f32) -> Timer {
+        Timer {
+            duration,
+            elapsed: 0.0,
+            repeating: false,
+            paused: false,
+            finished: false,
+        }
+    }
+
+    /**
+     * Goes off every `period` seconds
+     */
+    pub fn repeating(period: f32) -> Timer {
+        Timer {
+            repeating: true,
+            ..Timer::new(period)
+        }
+    }
+
+    /**
+     * Advance by `delta`, true if the timer went off
+     *
+     * Goes off at most once per tick, a repeating timer that fell more
+     * than a period behind catches up over the next ticks
+     */
+    pub fn tick(&mut self, delta: f32) -> bool {
+        if self.paused || self.finished {
+            return false;
+        }
+        self.elapsed += delta;
+        if self.elapsed < self.duration {
+            return false;
+        }
+        if self.repeating {
+            self.elapsed = (self.elapsed - self.duration).max(0.0);
+        } else {
+            self.finished = true;
+        }
+        true
+    }
+
+    /**
+     * A one-shot timer that already went off
+     */
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn remaining(&self) -> f32 {
+        (self.duration - self.elapsed).max(0.0)
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+    }
+
+    pub fn restart_with(&mut self, duration: f32) {
+        self.duration = duration;
+        self.restart();
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+/**
+ * Rate limit for something that shouldn't happen too often
+ *
+ * Starts out ready
+ */
+#[derive(Clone, Debug)]
+pub struct Cooldown {
+    duration: f32,
+    remaining: f32,
+}
+
+impl Cooldown {
+    pub fn new(duration: f32) -> Cooldown {
+        Cooldown {
+            duration,
+            remaining: 0.0,
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.remaining = (self.remaining - delta).max(0.0);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    /**
+     * If ready, start cooling down and return true
+     */
+    pub fn trigger(&mut self) -> bool {
+        if !self.is_ready() {
+            return false;
+        }
+        self.remaining = self.duration;
+        true
+    }
+
+    pub fn remaining(&self) -> f32 {
+        self.remaining
+    }
+
+    /**
+     * Make it ready again right away
+     */
+    pub fn reset(&mut self) {
+        self.remaining = 0.0;
+    }
+}
+
+/**
+ * Same ids that `DEvent::Timer` carries
+ */
+pub type TimerId = u32;
+
+enum Job<T> {
+    Event,
+    Once(Box<dyn FnOnce(&mut T) + Send>),
+    Every(Box<dyn FnMut(&mut T) + Send>),
+}
+
+struct Entry<T> {
+    id: TimerId,
+    due: f64,
+    period: Option<f64>,
+    job: Job<T>,
+}
+
+struct Schedule<T> {
+    now: f64,
+    paused: bool,
+    next_id: TimerId,
+    entries: Vec<Entry<T>>,
+    // Taken out of `entries` while their callbacks run
+    running: Vec<TimerId>,
+    cancelled: Vec<TimerId>,
+}
+
+/**
+ * Timers and delayed callbacks on the engine clock
+ *
+ * Time only moves in `update`, which the game loop calls with the same
+ * deltas it updates the state machine with, so everything scheduled
+ * here freezes along with the game while paused and replays the same
+ * way every time.
+ *
+ * Cloning gives another handle to the same schedule, so states and
+ * services on other threads can schedule work that runs on the game
+ * thread. Plain timers show up as `DEvent::Timer` from `update`,
+ * callbacks get the client when they're due.
+ *
+ * Non-finite times are rejected: something scheduled for NaN or
+ * infinite seconds never goes off, and such a delta doesn't move time.
+ */
+pub struct Scheduler<T> {
+    schedule: Arc<Mutex<Schedule<T>>>,
+}
+
+impl<T> Clone for Scheduler<T> {
+    fn clone(&self) -> Self {
+        Scheduler {
+            schedule: Arc::clone(&self.schedule),
+        }
+    }
+}
+
+impl<T> Scheduler<T> {
+    pub fn new() -> Scheduler<T> {
+        Scheduler {
+            schedule: Arc::new(Mutex::new(Schedule {
+                now: 0.0,
+                paused: false,
+                next_id: 1,
+                entries: Vec::new(),
+                running: Vec::new(),
+                cancelled: Vec::new(),
+            })),
+        }
+    }
+
+    /**
+     * `DEvent::Timer` once, `secs` from now
+     */
+    pub fn after(&self, secs: f32) -> TimerId {
+        self.add(secs, None, Job::Event)
+    }
+
+    /**
+     * `DEvent::Timer` every `secs` until cancelled
+     */
+    pub fn every(&self, secs: f32) -> TimerId {
+        self.add(secs, Some(secs), Job::Event)
+    }
+
+    /**
+     * Run `call` on the game thread once, `secs` from now
+     */
+    pub fn call_after<F>(&self, secs: f32, call: F) -> TimerId
+    where
+        F: 'static + FnOnce(&mut T) + Send,
+    {
+        self.add(secs, None, Job::Once(Box::new(call)))
+    }
+
+    /**
+     * Run `call` on the game thread every `secs` until cancelled
+     */
+    pub fn call_every<F>(&self, secs: f32, call: F) -> TimerId
+    where
+        F: 'static + FnMut(&mut T) + Send,
+    {
+        self.add(secs, Some(secs), Job::Every(Box::new(call)))
+    }
+
+    fn add(&self, secs: f32, period: Option<f32>, job: Job<T>) -> TimerId {
+        let mut schedule = self.schedule.lock();
+        let id = schedule.next_id;
+        schedule.next_id = schedule.next_id.wrapping_add(1).max(1);
+        // Never due, so it isn't kept, the id just won't be scheduled
+        if !secs.is_finite() {
+            return id;
+        }
+        let due = schedule.now + secs.max(0.0) as f64;
+        schedule.entries.push(Entry {
+            id,
+            due,
+            period: period.map(|period| period.max(0.0) as f64),
+            job,
+        });
+        id
+    }
+
+    /**
+     * Returns whether it was still scheduled. Safe to call from inside
+     * the callback being cancelled.
+     */
+    pub fn cancel(&self, id: TimerId) -> bool {
+        let mut schedule = self.schedule.lock();
+        let before = schedule.entries.len();
+        schedule.entries.retain(|entry| entry.id != id);
+        if schedule.entries.len() != before {
+            return true;
+        }
+        if schedule.running.contains(&id) {
+            schedule.cancelled.push(id);
+            return true;
+        }
+        false
+    }
+
+    /**
+     * Seconds until `id` is next due, None if it isn't scheduled
+     */
+    pub fn remaining(&self, id: TimerId) -> Option<f32> {
+        let schedule = self.schedule.lock();
+        schedule
+            .entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| (entry.due - schedule.now).max(0.0) as f32)
+    }
+
+    /**
+     * Seconds of unpaused time seen by `update`
+     */
+    pub fn now(&self) -> f64 {
+        self.schedule.lock().now
+    }
+
+    pub fn pause(&self) {
+        self.schedule.lock().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.schedule.lock().paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.schedule.lock().paused
+    }
+
+    /**
+     * Advance by `delta` and run whatever came due, in the order it was
+     * due. Returns the events of plain timers that went off.
+     *
+     * Callbacks run without the schedule locked, so they can schedule
+     * and cancel freely. Like `Timer`, each goes off at most once per
+     * update.
+     */
+    pub fn update(&self, client: &mut T, delta: f32) -> Vec<DEvent> {
+        let mut due = {
+            let mut schedule = self.schedule.lock();
+            if schedule.paused || !delta.is_finite() {
+                return Vec::new();
+            }
+            schedule.now += delta as f64;
+            let now = schedule.now;
+            let (due, waiting): (Vec<_>, Vec<_>) = mem::take(&mut schedule.entries)
+                .into_iter()
+                .partition(|entry| entry.due <= now);
+            schedule.entries = waiting;
+            schedule.running = due.iter().map(|entry| entry.id).collect();
+            due
+        };
+        // Only finite times get in, so there's always an order
+        due.sort_by(|a, b| a.due.partial_cmp(&b.due).unwrap().then(a.id.cmp(&b.id)));
+
+        let mut events = Vec::new();
+        let mut again = Vec::new();
+        for entry in due {
+            let job = match entry.job {
+                Job::Event => {
+                    events.push(DEvent::Timer(entry.id));
+                    Some(Job::Event)
+                }
+                Job::Once(call) => {
+                    call(client);
+                    None
+                }
+                Job::Every(mut call) => {
+                    call(client);
+                    Some(Job::Every(call))
+                }
+            };
+            if let (Some(job), Some(period)) = (job, entry.period) {
+                again.push(Entry {
+                    id: entry.id,
+                    due: entry.due + period,
+                    period: entry.period,
+                    job,
+                });
+            }
+        }
+
+        let mut schedule = self.schedule.lock();
+        let cancelled = mem::take(&mut schedule.cancelled);
+        schedule.running.clear();
+        schedule.entries.extend(
+            again
+                .into_iter()
+                .filter(|entry| !cancelled.contains(&entry.id)),
+        );
+        events
+    }
+}
+
+impl<T> Default for Scheduler<T> {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everything that went off, in order, with the scheduler's time
+    type Log = Vec<String>;
+
+    fn update(scheduler: &Scheduler<Log>, log: &mut Log, delta: f32) {
+        for event in scheduler.update(log, delta) {
+            let now = scheduler.now();
+            log.push(format!("{:?} at {}", event, now));
+        }
+    }
+
+    #[test]
+    fn runs_in_due_order() {
+        let scheduler = Scheduler::new();
+        let mut log = Log::new();
+        let late = scheduler.after(1.5);
+        scheduler.call_after(1.0, |log: &mut Log| log.push(String::from("call")));
+        let early = scheduler.after(0.5);
+        assert_eq!((late, early), (1, 3));
+
+        update(&scheduler, &mut log, 0.25);
+        assert!(log.is_empty());
+        // Everything comes due in one update, still in order
+        update(&scheduler, &mut log, 2.0);
+        assert_eq!(log, ["call", "Timer(3) at 2.25", "Timer(1) at 2.25"]);
+    }
+
+    #[test]
+    fn repeats_until_cancelled() {
+        let scheduler = Scheduler::new();
+        let mut log = Log::new();
+        let every = scheduler.every(1.0);
+        let cancelled = scheduler.after(2.0);
+        for _ in 0..3 {
+            update(&scheduler, &mut log, 1.0);
+        }
+        assert!(!scheduler.cancel(cancelled));
+        assert!(scheduler.cancel(every));
+        update(&scheduler, &mut log, 1.0);
+        assert_eq!(
+            log,
+            [
+                "Timer(1) at 1",
+                "Timer(1) at 2",
+                "Timer(2) at 2",
+                "Timer(1) at 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn callbacks_can_cancel_themselves() {
+        let scheduler = Scheduler::new();
+        let mut log = Log::new();
+        let handle = scheduler.clone();
+        let id = scheduler.call_every(1.0, move |log: &mut Log| {
+            log.push(format!("tick at {}", handle.now()));
+            if log.len() == 2 {
+                handle.cancel(1);
+            }
+        });
+        assert_eq!(id, 1);
+        for _ in 0..4 {
+            update(&scheduler, &mut log, 1.0);
+        }
+        assert_eq!(log, ["tick at 1", "tick at 2"]);
+        assert_eq!(scheduler.remaining(id), None);
+    }
+
+    #[test]
+    fn pausing_freezes_time() {
+        let scheduler = Scheduler::new();
+        let mut log = Log::new();
+        let id = scheduler.after(1.0);
+        update(&scheduler, &mut log, 0.5);
+        scheduler.pause();
+        update(&scheduler, &mut log, 5.0);
+        assert_eq!(scheduler.remaining(id), Some(0.5));
+        scheduler.resume();
+        update(&scheduler, &mut log, 0.5);
+        assert_eq!(log, ["Timer(1) at 1"]);
+    }
+
+    #[test]
+    fn rejects_non_finite_times() {
+        let scheduler = Scheduler::new();
+        let mut log = Log::new();
+        let never = scheduler.after(f32::NAN);
+        scheduler.every(f32::INFINITY);
+        scheduler.after(1.0);
+        assert_eq!(scheduler.remaining(never), None);
+        update(&scheduler, &mut log, f32::NAN);
+        update(&scheduler, &mut log, f32::INFINITY);
+        assert!(scheduler.now().abs() < f64::EPSILON);
+        update(&scheduler, &mut log, 1.0);
+        assert_eq!(log, ["Timer(3) at 1"]);
+    }
+
+    #[test]
+    fn timers_go_off_once_per_tick() {
+        let mut timer = Timer::repeating(1.0);
+        assert!(timer.tick(2.5));
+        assert!(timer.tick(0.0));
+        assert!(!timer.tick(0.25));
+        assert!((timer.elapsed() - 0.75).abs() < f32::EPSILON);
+
+        let mut once = Timer::new(1.0);
+        assert!(once.tick(1.0));
+        assert!(!once.tick(1.0));
+        assert!(once.finished());
+    }
+}
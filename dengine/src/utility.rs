@@ -0,0 +1,266 @@
+use std::fmt;
+
+/**
+ * How `Utility::choose` turns scores into a pick
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /** Always the best score, first one wins ties */
+    Highest,
+    /** Random, proportional to score */
+    Weighted,
+}
+
+type ScoreFn<C> = dyn Fn(&C) -> f32 + Send + Sync;
+
+/**
+ * Picks between named candidates that each score themselves from a
+ * context, usually the game struct
+ *
+ * Scores are clamped to 0 or more, a candidate scoring 0 is never
+ * picked. The caller decides what each name means.
+ */
+pub struct Utility<C> {
+    selection: Selection,
+    candidates: Vec<(String, Box<ScoreFn<C>>)>,
+}
+
+impl<C> Utility<C> {
+    pub fn new(selection: Selection) -> Utility<C> {
+        Utility {
+            selection,
+            candidates: Vec::new(),
+        }
+    }
+
+    pub fn candidate<F>(mut self, name: &str, score: F) -> Self
+    where
+        F: 'static + Fn(&C) -> f32 + Send + Sync,
+    {
+        self.candidates.push((String::from(name), Box::new(score)));
+        self
+    }
+
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    /**
+     * Score every candidate without picking one
+     */
+    pub fn score(&self, context: &C) -> Scores {
+        Scores::new(
+            self.candidates
+                .iter()
+                .map(|(name, score)| (name.as_str(), score(context))),
+        )
+    }
+
+    /**
+     * Score every candidate and pick one, `roll` is a uniform random
+     * number in `[0, 1)` and only matters for `Selection::Weighted`
+     */
+    pub fn choose(&self, context: &C, roll: f32) -> Scores {
+        let mut scores = self.score(context);
+        scores.pick(self.selection, roll);
+        scores
+    }
+}
+
+/**
+ * Result of scoring, kept around so decisions can be inspected
+ *
+ * Displays as `walk 0.62* | rest 0.20 | wag 0.00`, the star marking
+ * the pick
+ */
+#[derive(Clone, Debug)]
+pub struct Scores {
+    scores: Vec<(String, f32)>,
+    chosen: Option<usize>,
+}
+
+impl Scores {
+    /**
+     * Scores worked out somewhere else, to pick from like a `Utility`
+     * would. Names don't have to be unique.
+     */
+    pub fn new<'a, I>(scores: I) -> Scores
+    where
+        I: IntoIterator<Item = (&'a str, f32)>,
+    {
+        let scores = scores
+            .into_iter()
+            .map(|(name, value)| {
+                // NaN fails the comparison and ends up 0 too
+                let value = if value > 0.0 { value } else { 0.0 };
+                (String::from(name), value)
+            })
+            .collect();
+        Scores {
+            scores,
+            chosen: None,
+        }
+    }
+
+    /**
+     * Pick a candidate, replacing any earlier pick, `roll` is a uniform
+     * random number in `[0, 1)` and only matters for
+     * `Selection::Weighted`
+     */
+    pub fn pick(&mut self, selection: Selection, roll: f32) {
+        self.chosen = match selection {
+            Selection::Highest => {
+                let mut best = 0.0;
+                let mut chosen = None;
+                for (i, (_, score)) in self.scores.iter().enumerate() {
+                    if *score > best {
+                        best = *score;
+                        chosen = Some(i);
+                    }
+                }
+                chosen
+            }
+            Selection::Weighted => {
+                let total = self.scores.iter().map(|(_, score)| score).sum::<f32>();
+                let mut roll = roll * total;
+                let mut chosen = None;
+                for (i, (_, score)) in self.scores.iter().enumerate() {
+                    if *score <= 0.0 {
+                        continue;
+                    }
+                    // Remember the last positive one in case rounding
+                    // leaves the roll just past the end
+                    chosen = Some(i);
+                    if roll < *score {
+                        break;
+                    }
+                    roll -= score;
+                }
+                chosen
+            }
+        };
+    }
+
+    /**
+     * Name of the picked candidate, None if nothing scored above 0 or
+     * nothing was picked
+     */
+    pub fn chosen(&self) -> Option<&str> {
+        self.chosen.map(|i| self.scores[i].0.as_str())
+    }
+
+    /**
+     * Position of the picked candidate, for telling apart candidates
+     * with the same name
+     */
+    pub fn chosen_index(&self) -> Option<usize> {
+        self.chosen
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.scores
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|(_, score)| *score)
+    }
+
+    /**
+     * Every candidate with its score, in the order they were added
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.scores
+            .iter()
+            .map(|(name, score)| (name.as_str(), *score))
+    }
+}
+
+impl fmt::Display for Scores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, score)) in self.scores.iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{} {:.2}", name, score)?;
+            if self.chosen == Some(i) {
+                write!(f, "*")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * 0 at `from`, 1 at `to`, linear in between and clamped outside, for
+ * turning things like seconds since something into a score
+ */
+pub fn ramp(value: f32, from: f32, to: f32) -> f32 {
+    ((value - from) / (to - from)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    struct Mood {
+        energy: f32,
+    }
+
+    fn utility(selection: Selection) -> Utility<Mood> {
+        Utility::new(selection)
+            .candidate("walk", |mood: &Mood| mood.energy)
+            .candidate("rest", |mood: &Mood| 1.0 - mood.energy)
+            .candidate("broken", |_: &Mood| f32::NAN)
+    }
+
+    #[test]
+    fn highest_takes_the_first_best() {
+        let utility = utility(Selection::Highest);
+        let scores = utility.choose(&Mood { energy: 0.5 }, 0.99);
+        assert_eq!(scores.chosen(), Some("walk"));
+        assert_eq!(scores.to_string(), "walk 0.50* | rest 0.50 | broken 0.00");
+        let scores = utility.choose(&Mood { energy: 0.25 }, 0.0);
+        assert_eq!(scores.chosen(), Some("rest"));
+    }
+
+    #[test]
+    fn weighted_rolls_in_proportion() {
+        let utility = utility(Selection::Weighted);
+        let mood = Mood { energy: 0.75 };
+        assert_eq!(utility.choose(&mood, 0.0).chosen(), Some("walk"));
+        assert_eq!(utility.choose(&mood, 0.74).chosen(), Some("walk"));
+        assert_eq!(utility.choose(&mood, 0.76).chosen(), Some("rest"));
+        // Rounding past the end still lands on something that scored
+        assert_eq!(utility.choose(&mood, 1.0).chosen(), Some("rest"));
+
+        let mut rng = Rng::new(7);
+        let picks = (0..8)
+            .map(|_| {
+                utility
+                    .choose(&mood, rng.f32())
+                    .chosen()
+                    .unwrap()
+                    .chars()
+                    .next()
+                    .unwrap()
+            })
+            .collect::<String>();
+        assert_eq!(picks, "wrwrwwww");
+    }
+
+    #[test]
+    fn nothing_scoring_picks_nothing() {
+        let mut scores = Scores::new(vec![("walk", 0.0), ("rest", -1.0), ("nan", f32::NAN)]);
+        scores.pick(Selection::Weighted, 0.5);
+        assert_eq!(scores.chosen(), None);
+        scores.pick(Selection::Highest, 0.5);
+        assert_eq!(scores.chosen(), None);
+        assert_eq!(scores.get("rest"), Some(0.0));
+    }
+
+    #[test]
+    fn ramps_between_ends() {
+        let ramped = [0.0, 10.0, 20.0].map(|value| ramp(value, 5.0, 15.0));
+        assert_eq!(format!("{:?}", ramped), "[0.0, 0.5, 1.0]");
+    }
+}
@@ -1,29 +1,82 @@
 use crate::{
-    services::media::MediaPlaybackStatus,
-    services::{media::MediaSession, Services},
-    states::IdleState,
+    replay::{self, Inputs, WorkArea},
+    services::{
+        activity::ActivityService,
+        audio::AudioService,
+        media::{MediaCommand, MediaState, Metadata},
+        metrics::MetricsEvent,
+        Services,
+    },
+    states::{AwakeState, CuriousState},
 };
 use dengine::{
-    anim::AnimManager,
-    dwindow::{DWindow, DWindowBuilder, FrameBuffer},
-    fsm::StateMachine,
+    anim::{AnimManager, Bubble, DrawList, Sprite},
+    beat::Beat,
+    behavior::{Actor, BehaviorError, Behaviors},
+    clock::{Clock, SystemClock},
+    dwindow::{DWindow, DWindowBuilder, Frame, FrameBuffer, Surfaces, WindowMode},
+    event::{DEvent, EventQueue, EventTranslator, MouseButton},
+    fsm::{DState, StateMachine, Tracer},
+    observer::Subscription,
+    rng::Rng,
+    timer::{Cooldown, Scheduler, Timer},
+    utility::{ramp, Scores, Selection, Utility},
 };
-use pixels::Pixels;
+use parking_lot::Mutex;
 use std::{
+    env, fs, mem,
     ops::DerefMut,
+    path::Path,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    thread,
+    time::{Duration, Instant},
 };
 use winit::{
-    dpi::PhysicalSize,
     event::Event,
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
-    window::Window,
 };
 
+// Sprite sheets and the behavior file, relative to the working directory
+const ASSETS_DIR: &str = "assets";
+const BEHAVIORS_FILE: &str = "behaviors.toml";
+// Screen pixels per sprite pixel
+const SCALE: f32 = 4.0;
+// States the behavior file can run with `code = "..."`, see `enter_code`
+const CODE_STATES: &[&str] = &["curious"];
+// Flags the behavior file can wait on with `when`, see `Actor::flag`
+const FLAGS: &[&str] = &["media_playing", "cpu_busy", "battery_low", "charged"];
+const MOOD_PER_CLICK: f32 = 0.25;
+// Seconds for mood to settle most of the way back to neutral
+const MOOD_DECAY: f32 = 60.0;
+// Transitions kept for `transition_history`
+const HISTORY: usize = 200;
+// Set to print every transition as it happens
+const TRACE_ENV: &str = "DIGIT_TRACE";
+// Chance of announcing a new song, at most once per cooldown
+const ANNOUNCE_CHANCE: f32 = 0.5;
+const ANNOUNCE_COOLDOWN: f32 = 120.0;
+const ANNOUNCE_SECONDS: f32 = 6.0;
+// Widest a line of speech gets, in sprite pixels
+const BUBBLE_WIDTH: u32 = 80;
+// Anims played on the beat when there is one, each loop spanning two
+// beats so every other beat starts it over
+const ON_BEAT: &[&str] = &["dancing"];
+// Seconds the user has to be away before Digit goes to sleep, can be
+// changed with DIGIT_SLEEP_AFTER
+const SLEEP_AFTER: f32 = 5.0 * 60.0;
+const SLEEP_ENV: &str = "DIGIT_SLEEP_AFTER";
+// Overlay mode draws Digit onto a still, desktop-sized frame instead of
+// dragging a tiny window around, but needs click-through and the cursor
+// position, which only Windows has
+#[cfg(windows)]
+const WINDOW_MODE: WindowMode = WindowMode::Overlay;
+#[cfg(not(windows))]
+const WINDOW_MODE: WindowMode = WindowMode::Sprite;
+
 /**
  * Custom user window events
  * Exists here to be pluggable into DWindow
@@ -38,73 +91,372 @@ enum DigitWindowEvent {
  */
 pub struct Digit {
     sm: Option<StateMachine<Digit>>,
-    window: DWindow,
-    anim_manager: AnimManager,
+    // None until `open_window`, and for good when simulated
+    window: Option<DWindow>,
+    scale: f32,
+    anim_manager: Arc<AnimManager>,
+    sprite: Sprite,
+    anim: String,
+    on_second_beat: bool,
+    last_phase: f32,
+    props: Vec<Sprite>,
+    bubble: Sprite,
+    bubble_timer: Option<Timer>,
+    announce: Cooldown,
+    last_track: Option<String>,
+    behaviors: Option<Arc<Behaviors>>,
+    behaviors_hash: u64,
+    utility: Utility<Digit>,
+    last_scores: Option<Scores>,
+    mood: f32,
+    since_walk: f32,
+    last_x: f32,
+    sleep_after: f32,
+    clock: Box<dyn Clock>,
+    rng: Rng,
+    scheduler: Scheduler<Digit>,
+    tracer: Tracer<Digit>,
+    inputs: Inputs,
     dancing: Arc<AtomicBool>,
+    track: Arc<Mutex<Option<String>>>,
+    // Unsubscribes from the media service along with him
+    _media: Option<Subscription>,
+    // Thresholds crossed since the last poll, and which side of them
+    // the machine is on as of this tick
+    metrics: Arc<Mutex<Vec<MetricsEvent>>>,
+    _metrics: Option<Subscription>,
+    cpu_busy: bool,
+    battery_low: bool,
+    charged: bool,
 }
 
 impl Digit {
     pub fn new() -> Digit {
+        Digit::with_context(Box::new(SystemClock::new()), Rng::from_entropy())
+    }
+
+    /**
+     * Everything Digit does reads time and randomness from `clock` and
+     * `rng`, so a `ManualClock` and a fixed seed give the same run every
+     * time for the same update deltas and `Inputs`
+     */
+    pub fn with_context(clock: Box<dyn Clock>, rng: Rng) -> Digit {
         // Mark process as DPI aware so that the OS reports correct
         // monitor size even when scaled
         crate::set_process_dpi_aware();
 
+        let mut digit = Digit::headless(Path::new(ASSETS_DIR), clock, rng);
+        digit.open_window();
+        // Start services, then sync dancing and the track with media on OS
+        Services::global().start();
+        digit.connect_services();
+        digit
+    }
+
+    /**
+     * Digit with no window and no services, for tests and simulations
+     * driven through `update_with`, with sprite sheets and the behavior
+     * file read from `assets`
+     *
+     * Rendering does nothing until `open_window`, and dancing, the track
+     * and metrics only come from `Inputs` until `connect_services`
+     */
+    pub fn headless(assets: &Path, clock: Box<dyn Clock>, rng: Rng) -> Digit {
         // Initialize and register all animations
         let mut anim_manager = AnimManager::new();
-        register_animations(&mut anim_manager);
+        register_animations(&mut anim_manager, assets);
+
+        // The behavior file replaces the built-in awake states if it loads
+        let source = fs::read_to_string(assets.join(BEHAVIORS_FILE));
+        let behaviors_hash = source
+            .as_ref()
+            .map_or(0, |source| replay::hash(source.as_bytes()));
+        let behaviors = source
+            .map_err(BehaviorError::from)
+            .and_then(|source| Behaviors::parse(&source))
+            .and_then(|behaviors| {
+                behaviors.register_anims(assets, &mut anim_manager)?;
+                behaviors.check_code(CODE_STATES)?;
+                behaviors.check_flags(FLAGS)?;
+                Ok(behaviors)
+            });
+        let behaviors = match behaviors {
+            Ok(behaviors) => Some(behaviors),
+            Err(err) => {
+                eprintln!("{}, using built-in behaviors", err);
+                None
+            }
+        };
+
+        // Digit himself, drawn above any props
+        let mut sprite = Sprite::new();
+        sprite.z = 1;
+        // Speech bubble above him, hidden until he says something
+        let mut bubble = Sprite::new();
+        bubble.z = 2;
+        bubble.visible = false;
+
+        // State machine starts out as None and is initialized on the first
+        // update, so that the first state can access the data struct and
+        // sees that tick's inputs, which are the recorded ones in a replay
+        Digit {
+            sm: None,
+            window: None,
+            scale: SCALE,
+            anim_manager: Arc::new(anim_manager),
+            sprite,
+            anim: String::new(),
+            on_second_beat: false,
+            last_phase: 0.0,
+            props: Vec::new(),
+            bubble,
+            bubble_timer: None,
+            announce: Cooldown::new(ANNOUNCE_COOLDOWN),
+            last_track: None,
+            behaviors,
+            behaviors_hash,
+            utility: register_scores(),
+            last_scores: None,
+            mood: 0.5,
+            since_walk: 0.0,
+            last_x: 0.0,
+            sleep_after: sleep_after(),
+            clock,
+            rng,
+            scheduler: Scheduler::new(),
+            tracer: Tracer::new(HISTORY, |digit: &Digit| digit.clock().now())
+                .echo(env::var_os(TRACE_ENV).is_some()),
+            inputs: Inputs::new(),
+            dancing: Arc::new(AtomicBool::new(false)),
+            track: Arc::new(Mutex::new(None)),
+            _media: None,
+            metrics: Arc::new(Mutex::new(Vec::new())),
+            _metrics: None,
+            cpu_busy: false,
+            battery_low: false,
+            charged: false,
+        }
+    }
 
+    /**
+     * Put Digit on the taskbar in a window of his own, or an overlay
+     * over every monitor, and render the first frame. Does nothing if
+     * the window is already open.
+     */
+    pub fn open_window(&mut self) {
+        if self.window.is_some() {
+            return;
+        }
         // Create window with event handler `render_loop()`
-        let height = 32.0;
-        let scale = 4.0;
-        let window = DWindowBuilder::<DigitWindowEvent>::new()
-            .pos(32, crate::get_taskbar_height() - (height * scale) as i32)
+        let x = 32;
+        let y = crate::get_taskbar_height() - (32.0 * self.scale) as i32;
+        let window = match DWindowBuilder::<DigitWindowEvent>::new()
+            .pos(x, y)
             .size(32, 32)
-            .scale(scale)
+            .scale(self.scale)
             .title("Digit")
+            .mode(WINDOW_MODE)
             .loop_fn(render_loop)
-            .build();
-
-        // State machine must start out as None to be initialized later
-        let mut digit = Digit {
-            sm: None,
-            window,
-            anim_manager,
-            dancing: Arc::new(AtomicBool::new(false)),
+            .build()
+        {
+            Ok(window) => window,
+            Err(err) => {
+                eprintln!("can't open a window: {}", err);
+                return;
+            }
         };
-        // Sync dancing variable with media status on OS
-        register_media_callback(&digit.dancing);
-
-        // Construct state machine and run initialization with beginning state
-        // This is why we needed the state machine to be None, so that the first
-        // state can access the data struct without having a separate init method
-        let sm = StateMachine::new();
-        sm.init::<IdleState>(&mut digit);
-        digit.sm = Some(sm);
+        self.set_position(x as f32, y as f32);
 
         // Render first frame
-        digit.window.swap_buffers();
-        digit
+        window.swap_buffers();
+        self.window = Some(window);
+    }
+
+    /**
+     * Follow media and metrics from the global services, which should
+     * already be started. Does nothing if already connected, or for
+     * services the registry doesn't have.
+     */
+    pub fn connect_services(&mut self) {
+        if self._media.is_none() {
+            self._media = register_media_callback(&self.dancing, &self.track);
+        }
+        if self._metrics.is_none() {
+            self._metrics = register_metrics_callback(&self.metrics);
+        }
+    }
+
+    /**
+     * Name of the current behavior state, None while the state machine
+     * itself is running (states can ask the machine they're given)
+     */
+    pub fn state_name(&self) -> Option<String> {
+        self.sm.as_ref().and_then(|sm| sm.current_state_name())
+    }
+
+    /**
+     * Active state at every level of nesting, like `AwakeState/WalkState`
+     */
+    pub fn state_path(&self) -> Option<String> {
+        self.sm.as_ref().map(|sm| sm.active_path().join("/"))
+    }
+
+    /**
+     * Recent transitions at every level of nesting, oldest first, one
+     * per line with why they happened
+     */
+    pub fn transition_history(&self) -> String {
+        self.tracer.dump()
+    }
+
+    /**
+     * States loaded from the behavior file, None if it failed to load
+     */
+    pub fn behaviors(&self) -> Option<&Arc<Behaviors>> {
+        self.behaviors.as_ref()
+    }
+
+    /**
+     * Hash of the behavior file as it was read, 0 if it couldn't be,
+     * so recordings can tell whether they still replay the same
+     */
+    pub fn behaviors_hash(&self) -> u64 {
+        self.behaviors_hash
     }
 
     pub fn is_dancing(&self) -> bool {
-        self.dancing.load(Ordering::SeqCst)
+        self.inputs.dancing
+    }
+
+    /**
+     * Gather this tick's input from the OS and the window thread
+     */
+    pub fn poll_inputs(&self) -> Inputs {
+        let work_area = crate::get_work_area(self.sprite.x as i32, self.sprite.y as i32);
+        let window = self.window.as_ref();
+        let origin = window.map_or((0, 0), |window| {
+            let origin = window.origin();
+            (origin.x, origin.y)
+        });
+        Inputs {
+            events: window
+                .map(|window| window.events().drain().into_iter().collect())
+                .unwrap_or_default(),
+            dancing: self.dancing.load(Ordering::SeqCst),
+            track: self.track.lock().clone(),
+            // Skipped for a tick while a retry is busy starting them
+            beat: Services::global()
+                .try_lock::<AudioService>()
+                .and_then(|audio| audio.beat()),
+            idle: Services::global()
+                .try_lock::<ActivityService>()
+                .and_then(|activity| activity.idle_time())
+                .map(|idle| idle.as_secs() as f32),
+            metrics: mem::take(&mut *self.metrics.lock()),
+            cursor: window
+                .and_then(DWindow::cursor)
+                .map(|cursor| (cursor.x, cursor.y)),
+            work_area,
+            origin,
+            hour: self.clock.hour(),
+        }
     }
 
     /**
      * Update function to be run every frame
      */
     pub fn update(&mut self, delta: f32) {
-        // Update state machine
-        if let Some(sm) = self.sm.take() {
-            sm.update(self, delta);
-            self.sm = Some(sm);
-        } else {
-            panic!("state machine gone (what the state machine doin)");
+        let inputs = self.poll_inputs();
+        self.update_with(delta, inputs);
+    }
+
+    /**
+     * Update with inputs from somewhere other than the OS, like a
+     * recording
+     */
+    pub fn update_with(&mut self, delta: f32, inputs: Inputs) {
+        self.inputs = inputs;
+        let events = mem::take(&mut self.inputs.events);
+        let metrics = mem::take(&mut self.inputs.metrics);
+        // Update state machine, starting it on the very first tick
+        let sm = match self.sm.take() {
+            Some(sm) => sm,
+            None => {
+                let sm = StateMachine::new();
+                sm.set_tracer(self.tracer.clone());
+                sm.init::<AwakeState>(self);
+                sm
+            }
+        };
+        // Deliver everything the window thread queued since last tick
+        for event in &events {
+            if self.is_clicked(event) {
+                self.mood = (self.mood + MOOD_PER_CLICK).min(1.0);
+            }
+            sm.handle_event(self, event);
+        }
+        // The machine crossing a threshold, as custom events
+        for event in &metrics {
+            match event {
+                MetricsEvent::CpuBusy => self.cpu_busy = true,
+                MetricsEvent::CpuCalm => self.cpu_busy = false,
+                MetricsEvent::BatteryLow => self.battery_low = true,
+                MetricsEvent::BatteryRecovered => self.battery_low = false,
+                MetricsEvent::Charged => self.charged = true,
+                MetricsEvent::Unplugged => self.charged = false,
+                _ => (),
+            }
+            sm.handle_event(self, &DEvent::custom(*event));
+        }
+        // Then whatever was scheduled, and timers going off
+        for event in self.scheduler.clone().update(self, delta) {
+            sm.handle_event(self, &event);
+        }
+        sm.update(self, delta);
+        self.sm = Some(sm);
+        self.inputs.events = events;
+        self.inputs.metrics = metrics;
+        // Mood drifts back to neutral, and any movement counts as a walk
+        self.mood += (0.5 - self.mood) * (delta / MOOD_DECAY).min(1.0);
+        self.since_walk += delta;
+        if (self.sprite.x - self.last_x).abs() > 0.0 {
+            self.last_x = self.sprite.x;
+            self.since_walk = 0.0;
+        }
+        // A new song gets announced every now and then
+        self.announce.tick(delta);
+        if self.inputs.track != self.last_track {
+            self.last_track = self.inputs.track.clone();
+            if let Some(track) = self.last_track.clone() {
+                if self.announce.is_ready() && self.rng.f32() < ANNOUNCE_CHANCE {
+                    self.announce.trigger();
+                    self.say(&format!("♪ {}", track), ANNOUNCE_SECONDS);
+                }
+            }
+        }
+        if let Some(timer) = &mut self.bubble_timer {
+            if timer.tick(delta) {
+                self.bubble_timer = None;
+                self.bubble.visible = false;
+            }
+        }
+        self.follow_beat();
+        // Update animations
+        self.sprite.update(delta);
+        for prop in &mut self.props {
+            prop.update(delta);
+        }
+        self.place_bubble();
+        // Update window, which only moves to follow Digit in sprite mode
+        if let Some((x, y, _, _)) = self.sprite_frame() {
+            if let Some(window) = &mut self.window {
+                window.x = x;
+                window.y = y;
+            }
+        }
+        if let Some(window) = &self.window {
+            window.update(delta);
         }
-        // Update animation
-        self.anim_manager.update(delta);
-        // Update window
-        self.window.update(delta);
     }
 
     /**
@@ -112,14 +464,31 @@ impl Digit {
      * Maybe could cut back on when it runs to optimize CPU usage
      */
     pub fn render(&self) {
+        let window = match &self.window {
+            Some(window) => window,
+            None => return,
+        };
         // Lock back buffer that contains the previous frame
-        let mut frame = self.window.framebuffer().get_back_buffer();
+        let mut frame = window.framebuffer().get_back_buffer();
         // Zero out previous frame
         for byte in frame.get_mut().deref_mut() {
             *byte = 0;
         }
-        // Render current animation on window frame
-        self.anim_manager.draw(&mut frame);
+        // A sprite mode window only ever shows Digit and what he says, so
+        // fit the frame to them, an overlay frame covers however the
+        // monitors are laid out
+        let size = match window.mode() {
+            WindowMode::Sprite => self
+                .sprite_frame()
+                .map(|(_, _, width, height)| (width, height)),
+            WindowMode::Overlay => window.frame_size(),
+        };
+        if let Some((width, height)) = size {
+            if frame.size() != (width, height) {
+                frame.set_size(width, height);
+            }
+        }
+        self.draw(&mut frame, |x, y| window.to_frame(x, y));
         // Make transparent pixels fully black just in case
         for pixel in frame.get_mut().chunks_exact_mut(4) {
             if pixel[3] == 0 {
@@ -130,119 +499,858 @@ impl Digit {
         }
         // Drop frame manually to unlock it and enable swapping frame buffers
         drop(frame);
-        self.window.swap_buffers();
+        window.swap_buffers();
+    }
+
+    /**
+     * Render every sprite on a frame, `to_frame` maps world positions to
+     * frame pixels
+     */
+    fn draw<F>(&self, frame: &mut Frame, to_frame: F)
+    where
+        F: Fn(f32, f32) -> (i32, i32),
+    {
+        let mut draw_list = DrawList::new();
+        draw_list.push(&self.sprite);
+        draw_list.extend(&self.props);
+        draw_list.push(&self.bubble);
+        draw_list.draw(frame, to_frame);
+    }
+
+    /**
+     * World position and frame size a sprite mode window needs to show
+     * Digit, grown to fit the bubble over his head while he talks
+     */
+    fn sprite_frame(&self) -> Option<(f32, f32, u32, u32)> {
+        let (width, height) = self.sprite.size()?;
+        let scale = self.scale;
+        let (mut left, mut top) = (self.sprite.x, self.sprite.y);
+        let mut right = left + width as f32 * scale;
+        let bottom = top + height as f32 * scale;
+        if let (true, Some((bubble_width, _))) = (self.bubble.visible, self.bubble.size()) {
+            left = left.min(self.bubble.x);
+            top = top.min(self.bubble.y);
+            right = right.max(self.bubble.x + bubble_width as f32 * scale);
+        }
+        Some((
+            left,
+            top,
+            ((right - left) / scale).ceil() as u32,
+            ((bottom - top) / scale).ceil() as u32,
+        ))
+    }
+
+    /**
+     * Show a speech bubble above Digit for a while, replacing whatever
+     * he was saying
+     */
+    pub fn say(&mut self, text: &str, seconds: f32) {
+        let bubble = Bubble::new(text, BUBBLE_WIDTH);
+        self.bubble.playback.set_anim(Arc::new(bubble));
+        self.bubble.visible = true;
+        self.bubble_timer = Some(Timer::new(seconds));
+        self.place_bubble();
+    }
+
+    pub fn is_saying(&self) -> bool {
+        self.bubble.visible
+    }
+
+    /**
+     * Keep the bubble centered over Digit's head
+     */
+    fn place_bubble(&mut self) {
+        if let (Some((width, _)), Some((bubble_width, bubble_height))) =
+            (self.sprite.size(), self.bubble.size())
+        {
+            let scale = self.scale;
+            self.bubble.x = self.sprite.x + (width as f32 - bubble_width as f32) * scale / 2.0;
+            self.bubble.y = self.sprite.y - bubble_height as f32 * scale;
+        }
+    }
+
+    /**
+     * 0 is grumpy, 1 is delighted, clicks cheer Digit up
+     */
+    pub fn mood(&self) -> f32 {
+        self.mood
+    }
+
+    /**
+     * Seconds since Digit last moved
+     */
+    pub fn since_walk(&self) -> f32 {
+        self.since_walk
+    }
+
+    /**
+     * Seconds since the user last touched keyboard or mouse, None when
+     * the OS can't tell
+     */
+    pub fn user_idle(&self) -> Option<f32> {
+        self.inputs.idle
+    }
+
+    /**
+     * Whether the user has been idle long enough for Digit to sleep,
+     * never when it can't be told
+     */
+    pub fn is_user_away(&self) -> bool {
+        match self.inputs.idle {
+            Some(idle) => idle >= self.sleep_after,
+            None => false,
+        }
+    }
+
+    /**
+     * Whether the CPU has been pegged for a while
+     */
+    pub fn is_cpu_busy(&self) -> bool {
+        self.cpu_busy
+    }
+
+    /**
+     * Whether the battery is running low, never while plugged in
+     */
+    pub fn is_battery_low(&self) -> bool {
+        self.battery_low
+    }
+
+    /**
+     * Whether the battery finished charging and is still plugged in
+     */
+    pub fn is_charged(&self) -> bool {
+        self.charged
+    }
+
+    /**
+     * 1 during the day, lower at night, and lower still when the
+     * machine is worn out
+     */
+    fn energy(&self) -> f32 {
+        let energy = match self.inputs.hour {
+            8..=20 => 1.0,
+            6..=7 | 21..=22 => 0.6,
+            _ => 0.3,
+        };
+        let worn = match (self.battery_low, self.cpu_busy) {
+            (true, _) => 0.4,
+            (false, true) => 0.7,
+            (false, false) => 1.0,
+        };
+        energy * worn
+    }
+
+    /**
+     * How far the cursor is from Digit, in world pixels
+     */
+    fn cursor_distance(&self) -> Option<f32> {
+        let (x, y) = self.inputs.cursor?;
+        let (width, height) = self.sprite.size()?;
+        let scale = self.scale;
+        let dx = x as f32 - (self.sprite.x + width as f32 * scale / 2.0);
+        let dy = y as f32 - (self.sprite.y + height as f32 * scale / 2.0);
+        Some((dx * dx + dy * dy).sqrt())
+    }
+
+    /**
+     * What every candidate in `register_scores` would score right now
+     */
+    pub fn scores(&self) -> Scores {
+        self.utility.score(self)
+    }
+
+    /**
+     * Pick what to do next, the result is kept for `last_scores`
+     */
+    pub fn choose(&mut self) -> Scores {
+        let scores = self.utility.score(self);
+        self.choose_from(scores, self.utility.selection())
+    }
+
+    /**
+     * Pick from scores worked out elsewhere, like the options of a
+     * behavior file state, and keep the result for `last_scores`
+     */
+    pub fn choose_from(&mut self, mut scores: Scores, selection: Selection) -> Scores {
+        let roll = self.rng.f32();
+        scores.pick(selection, roll);
+        self.last_scores = Some(scores.clone());
+        scores
+    }
+
+    /**
+     * Scores behind the last `choose` or behavior file decision, for
+     * debugging decisions
+     */
+    pub fn last_scores(&self) -> Option<&Scores> {
+        self.last_scores.as_ref()
+    }
+
+    /**
+     * What this tick's update was given
+     */
+    pub fn inputs(&self) -> &Inputs {
+        &self.inputs
+    }
+
+    /**
+     * Cursor in world coordinates as of this tick
+     */
+    pub fn cursor(&self) -> Option<(i32, i32)> {
+        self.inputs.cursor
     }
 
-    pub fn window(&self) -> &DWindow {
-        &self.window
+    /**
+     * Usable area of the monitor Digit was on at the start of this tick
+     */
+    pub fn work_area(&self) -> Option<WorkArea> {
+        self.inputs.work_area
+    }
+
+    /**
+     * Move Digit without it counting as a walk, like on startup
+     */
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.sprite.x = x;
+        self.sprite.y = y;
+        self.last_x = x;
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /**
+     * The only randomness behavior should use
+     */
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /**
+     * Timers and delayed callbacks on the game clock, clone it to
+     * schedule from a service
+     */
+    pub fn scheduler(&self) -> &Scheduler<Digit> {
+        &self.scheduler
+    }
+
+    /**
+     * None until `open_window`
+     */
+    pub fn window(&self) -> Option<&DWindow> {
+        self.window.as_ref()
+    }
+
+    pub fn window_mut(&mut self) -> Option<&mut DWindow> {
+        self.window.as_mut()
     }
 
-    pub fn window_mut(&mut self) -> &mut DWindow {
-        &mut self.window
+    /**
+     * Screen pixels per sprite pixel
+     */
+    pub fn scale(&self) -> f32 {
+        self.scale
     }
 
-    pub fn anims(&self) -> &AnimManager {
+    /**
+     * Shared animation library, clone the `Arc` to use it from
+     * another thread
+     */
+    pub fn anims(&self) -> &Arc<AnimManager> {
         &self.anim_manager
     }
+
+    /**
+     * Digit's own sprite, its position is Digit's position in the world
+     */
+    pub fn sprite(&self) -> &Sprite {
+        &self.sprite
+    }
+
+    pub fn sprite_mut(&mut self) -> &mut Sprite {
+        &mut self.sprite
+    }
+
+    /**
+     * Extra entities drawn alongside Digit, only visible outside his
+     * own box in overlay mode
+     */
+    pub fn props_mut(&mut self) -> &mut Vec<Sprite> {
+        &mut self.props
+    }
+
+    /**
+     * Whether an event is a left click that landed on Digit himself
+     */
+    pub fn is_clicked(&self, event: &DEvent) -> bool {
+        match event {
+            DEvent::MousePressed {
+                button: MouseButton::Left,
+                x,
+                y,
+            } => self.is_over(*x, *y),
+            _ => false,
+        }
+    }
+
+    /**
+     * How far the wheel was scrolled over Digit, positive is up, None if
+     * the event isn't a scroll over him
+     */
+    pub fn scrolled(&self, event: &DEvent) -> Option<f32> {
+        match event {
+            DEvent::MouseWheel { delta, x, y } if self.is_over(*x, *y) => Some(*delta),
+            _ => None,
+        }
+    }
+
+    /**
+     * Whether a point in frame pixels is inside Digit's box
+     */
+    fn is_over(&self, x: f32, y: f32) -> bool {
+        if let Some((width, height)) = self.sprite.size() {
+            // Same as `DWindow::to_frame`, but with this tick's origin
+            let scale = self.scale;
+            let (origin_x, origin_y) = self.inputs.origin;
+            let left = ((self.sprite.x - origin_x as f32) / scale).floor() as i32;
+            let top = ((self.sprite.y - origin_y as f32) / scale).floor() as i32;
+            return x >= left as f32
+                && y >= top as f32
+                && x < (left + width as i32) as f32
+                && y < (top + height as i32) as f32;
+        }
+        false
+    }
+
+    /**
+     * Whether there's a song, playing or paused, for Digit to control
+     */
+    pub fn has_media(&self) -> bool {
+        self.inputs.dancing || self.inputs.track.is_some()
+    }
+
+    /**
+     * Send a command to whatever is playing, without waiting for the
+     * player to answer
+     */
+    pub fn control_media(&self, command: MediaCommand) {
+        thread::spawn(move || match Services::media() {
+            Some(media) => {
+                if let Err(err) = media.control(command) {
+                    eprintln!("couldn't {}: {}", command, err);
+                }
+            }
+            None => eprintln!("couldn't {}: no media service", command),
+        });
+    }
+
+    pub fn set_anim(&mut self, name: &str) {
+        self.sprite.set_anim(&self.anim_manager, name);
+        self.anim = String::from(name);
+    }
+
+    /**
+     * Beat of what's playing as of this tick, None without audio or a
+     * steady beat
+     */
+    pub fn beat(&self) -> Option<Beat> {
+        self.inputs.beat
+    }
+
+    /**
+     * Play anims that dance on the beat at its tempo and lined up with
+     * it, or at their own fps when there's no beat
+     */
+    fn follow_beat(&mut self) {
+        let beat = match self.inputs.beat {
+            Some(beat) if ON_BEAT.contains(&self.anim.as_str()) => beat,
+            _ => {
+                self.sprite.playback.set_speed(1.0);
+                return;
+            }
+        };
+        if beat.phase < self.last_phase {
+            self.on_second_beat = !self.on_second_beat;
+        }
+        self.last_phase = beat.phase;
+        let anim = match self.sprite.playback.anim() {
+            Some(anim) => anim.clone(),
+            None => return,
+        };
+        let per_beat = anim.frames() as f32 / 2.0;
+        let fps = beat.bpm / 60.0 * per_beat;
+        self.sprite.playback.set_speed(fps / anim.fps() as f32);
+        let beats = self.on_second_beat as u32 as f32 + beat.phase;
+        self.sprite.playback.set_position(beats * per_beat);
+    }
+
+    pub fn set_flipped(&mut self, flipped: bool) {
+        self.sprite.set_flipped(flipped);
+    }
+}
+
+impl Default for Digit {
+    fn default() -> Self {
+        Digit::new()
+    }
+}
+
+impl Actor for Digit {
+    fn play(&mut self, anim: &str) {
+        self.set_anim(anim);
+    }
+
+    fn set_flipped(&mut self, flipped: bool) {
+        Digit::set_flipped(self, flipped);
+    }
+
+    fn x(&self) -> f32 {
+        self.sprite.x
+    }
+
+    fn set_x(&mut self, x: f32) {
+        self.sprite.x = x;
+    }
+
+    fn walk_bounds(&self) -> Option<(f32, f32)> {
+        let width = match self.sprite.size() {
+            Some((width, _)) => width as f32 * self.scale,
+            None => 0.0,
+        };
+        self.inputs.work_area.map(|area| {
+            let left = area.left as f32;
+            (left, (area.right as f32 - width).max(left))
+        })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        match name {
+            "media_playing" => self.is_dancing(),
+            "cpu_busy" => self.cpu_busy,
+            "battery_low" => self.battery_low,
+            "charged" => self.charged,
+            _ => false,
+        }
+    }
+
+    fn is_clicked(&self, event: &DEvent) -> bool {
+        Digit::is_clicked(self, event)
+    }
+
+    fn score(&self, name: &str) -> f32 {
+        self.utility.score(self).get(name).unwrap_or(0.0)
+    }
+
+    fn random(&mut self) -> f32 {
+        self.rng.f32()
+    }
+
+    fn choose(&mut self, options: Scores) -> Scores {
+        self.choose_from(options, Selection::Weighted)
+    }
+
+    fn enter_code(
+        &mut self,
+        sm: &StateMachine<Digit>,
+        name: &str,
+    ) -> Option<Box<dyn DState<Digit>>> {
+        match name {
+            "curious" => Some(CuriousState::enter(sm, self)),
+            _ => None,
+        }
+    }
 }
 
 fn render_loop(
-    scale: f32,
     framebuffer: &FrameBuffer,
-    pixels: Pixels,
-    window: &Window,
+    events: &EventQueue,
+    surfaces: Surfaces,
     event_loop: EventLoop<DigitWindowEvent>,
 ) {
-    let PhysicalSize {
-        mut width,
-        mut height,
-    } = window.inner_size();
+    let mut translator = EventTranslator::new(surfaces.scale());
+    add_windows(&mut translator, &surfaces);
+    translator.poll_displays(&event_loop);
+    let mut last_display_poll = Instant::now();
 
     let mut event_loop = event_loop;
-    let mut pixels = pixels;
-    event_loop.run_return(move |event, _, control_flow| {
+    let mut surfaces = surfaces;
+    event_loop.run_return(move |event, target, control_flow| {
         *control_flow = ControlFlow::Poll;
         match event {
+            Event::WindowEvent { window_id, event } => {
+                if let Some(event) = translator.translate(window_id, &event) {
+                    events.push(event);
+                }
+            }
             Event::MainEventsCleared => {
-                let frame = framebuffer.get_front_buffer();
-
-                if frame.width != width || frame.height != height {
-                    width = frame.width;
-                    height = frame.height;
-                    let scaled_width = (frame.width as f32 * scale) as u32;
-                    let scaled_height = (frame.height as f32 * scale) as u32;
-                    window.set_inner_size(PhysicalSize {
-                        width: scaled_width,
-                        height: scaled_height,
-                    });
-                    pixels.resize_surface(scaled_width, scaled_height);
-                    pixels.resize_buffer(frame.width, frame.height);
+                // No OS event for monitor changes, so check once a second,
+                // and cover the new layout with overlay windows
+                if last_display_poll.elapsed() > Duration::from_secs(1) {
+                    last_display_poll = Instant::now();
+                    if let Some(event) = translator.poll_displays(target) {
+                        if surfaces.rebuild(target) {
+                            translator.clear_windows();
+                            add_windows(&mut translator, &surfaces);
+                        }
+                        events.push(event);
+                    }
                 }
 
-                pixels.get_frame().copy_from_slice(&*frame.buffer);
-                drop(frame);
-
-                pixels.render().unwrap();
+                let frame = framebuffer.get_front_buffer();
+                for surface in surfaces.iter_mut() {
+                    surface.present(&frame).unwrap();
+                    surface.update_hit_test(&frame);
+                }
             }
             _ => (),
         }
     });
 }
 
+/**
+ * Tell the translator where each window sits in the frame
+ */
+fn add_windows(translator: &mut EventTranslator, surfaces: &Surfaces) {
+    for surface in surfaces.iter() {
+        translator.add_window(surface.window().id(), surface.offset());
+    }
+}
+
 /**
  * List of animations to register in a declarative manner
  */
-fn register_animations(anims: &mut AnimManager) {
-    anims.register("idle").import("assets/idle.png");
+fn register_animations(anims: &mut AnimManager, assets: &Path) {
+    anims.register("idle").import(assets.join("idle.png"));
     anims
         .register("walking")
         .width(64)
         .height(32)
         .frames(8)
         .fps(12)
-        .import("assets/walking.png");
+        .import(assets.join("walking.png"));
     anims
         .register("dancing")
         .width(32)
         .height(32)
         .frames(8)
         .fps(12)
-        .import("assets/wagging.png");
-    anims.register("ready").import("assets/ready.png");
+        .import(assets.join("wagging.png"));
+    anims.alias("wagging", "dancing");
+    anims.register("ready").import(assets.join("ready.png"));
+    // Getting ready for bed, stretching up then sinking out of sight
+    anims.pose(
+        "yawning",
+        "ready",
+        &[(0, 0), (0, -1), (0, -2), (0, -2), (0, -1)],
+        4,
+    );
+    anims.pose("lying", "idle", &[(0, 1), (0, 2), (0, 3), (0, 4)], 2);
+    anims.pose("dozing", "idle", &[(0, 4), (0, 4), (0, 5), (0, 5)], 2);
+}
+
+/**
+ * Collect the thresholds the machine crosses, to be taken as inputs on
+ * the next poll, None without a metrics service
+ */
+fn register_metrics_callback(events: &Arc<Mutex<Vec<MetricsEvent>>>) -> Option<Subscription> {
+    let events_ref = Arc::downgrade(events);
+    let subscription = Services::metrics()?.subscribe(move |event| {
+        if let Some(events) = events_ref.upgrade() {
+            events.lock().push(*event);
+        }
+    });
+    Some(subscription)
+}
+
+/**
+ * Seconds of user inactivity before sleeping, from the environment if
+ * it's set to a number
+ */
+fn sleep_after() -> f32 {
+    match env::var(SLEEP_ENV).map(|value| value.parse::<f32>()) {
+        Ok(Ok(seconds)) if seconds > 0.0 => seconds,
+        Ok(_) => {
+            eprintln!(
+                "{} isn't a number of seconds, using {}",
+                SLEEP_ENV, SLEEP_AFTER
+            );
+            SLEEP_AFTER
+        }
+        Err(_) => SLEEP_AFTER,
+    }
+}
+
+/**
+ * What Digit may do once he's done idling, each scored from how he's
+ * doing. Names are shared with `score = "..."` in the behavior file.
+ */
+fn register_scores() -> Utility<Digit> {
+    Utility::<Digit>::new(Selection::Weighted)
+        .candidate("walk", |digit| {
+            ramp(digit.since_walk(), 5.0, 60.0) * digit.energy()
+        })
+        .candidate("curious", |digit| match digit.cursor_distance() {
+            Some(distance) => (1.0 - ramp(distance, 100.0, 600.0)) * 0.8,
+            None => 0.0,
+        })
+        .candidate("rest", |digit| {
+            0.2 + (1.0 - digit.energy()) * 0.6 + (1.0 - digit.mood()) * 0.2
+        })
+        .candidate("wag", |digit| match digit.is_dancing() {
+            true => 0.0,
+            false => digit.mood() * digit.mood() * 0.5,
+        })
 }
 
 /**
- * Register a variable to be synced to be true when media is playing
+ * Register a variable to be synced to be true when media is playing,
+ * and one to hold what song it is, for as long as the subscription lives.
+ * None without a media service, they stay false and empty.
  */
-fn register_media_callback(dancing_bool: &Arc<AtomicBool>) {
-    // Downgrade to a weak ref so that the callback can exist forever, even if the
-    // reference gets invalidated or dropped
+fn register_media_callback(
+    dancing_bool: &Arc<AtomicBool>,
+    track: &Arc<Mutex<Option<String>>>,
+) -> Option<Subscription> {
+    // Downgrade to a weak ref so a delivery already on its way after
+    // unsubscribing doesn't keep them alive
     let dancing_ref = Arc::downgrade(dancing_bool);
+    let track_ref = Arc::downgrade(track);
 
     // Construct callback to be called when media status changes
     // Just updates dancing_bool
-    let callback = move |session_opt: &Option<MediaSession>| {
+    let callback = move |state: &MediaState| {
         if let Some(dancing) = dancing_ref.upgrade() {
-            match session_opt {
-                Some(session) => {
-                    // Set dancing_bool to true if media is playing
-                    // We can also detect if this is a video if we want
-                    let should_dance = session.GetPlaybackInfo().unwrap().PlaybackStatus().unwrap()
-                        == MediaPlaybackStatus::Playing;
-                    dancing.store(should_dance, Ordering::SeqCst);
-                }
-
-                // Fails closed so if there was an error getting the session,
-                // don't update the variable
-                None => dancing.store(false, Ordering::SeqCst),
-            }
+            // Set dancing_bool to true if media is playing, fails closed so
+            // no session or an unknown status stops the dancing
+            dancing.store(state.is_playing(), Ordering::SeqCst);
+        }
+        if let Some(track) = track_ref.upgrade() {
+            *track.lock() = state.metadata.as_ref().and_then(Metadata::summary);
         }
     };
 
     // Lock media service
-    let mut media_service = Services::media();
-    // Run callback immediately to correct internal state
-    callback(&media_service.get_media_session());
-    // And then register it to be called whenever the service changes
-    media_service.subscribe(callback);
+    let media_service = Services::media()?;
+    // Register it to be called whenever the service changes, first so
+    // a change between seeding and subscribing isn't missed
+    let subscription = media_service.subscribe(callback.clone());
+    // Then run callback immediately to correct internal state
+    callback(&media_service.state());
+    Some(subscription)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay::WorkArea,
+        services::{activity::ManualIdle, Service},
+    };
+    use dengine::clock::ManualClock;
+
+    const TIMESTEP: f32 = 1.0 / 30.0;
+    // The workspace's assets, wherever the tests run from
+    const ASSETS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+    /**
+     * A minute of Digit on a 1080p screen, with a song playing for ten
+     * seconds of it
+     */
+    fn simulate(seed: u64) -> String {
+        let clock = ManualClock::new(12);
+        let mut digit = Digit::headless(Path::new(ASSETS), Box::new(clock.clone()), Rng::new(seed));
+        digit.set_position(32.0, 952.0);
+        for tick in 0..60 * 30 {
+            let mut inputs = Inputs::new();
+            inputs.dancing = (20 * 30..30 * 30).contains(&tick);
+            inputs.work_area = Some(WorkArea {
+                left: 0,
+                top: 0,
+                right: 1920,
+                bottom: 1080,
+            });
+            clock.advance(Duration::from_secs_f32(TIMESTEP));
+            digit.update_with(TIMESTEP, inputs);
+        }
+        digit.transition_history()
+    }
+
+    #[test]
+    fn headless_runs_repeat_exactly() {
+        let history = simulate(3);
+        assert_eq!(simulate(3), history);
+        let lines = history.lines().map(str::trim_start).collect::<Vec<_>>();
+        assert_eq!(
+            lines[..2],
+            ["0.033s * -> AwakeState (init)", "0.033s * -> idle (init)"]
+        );
+        // Whatever he was doing, the song starts and stops the dancing
+        let dancing = lines
+            .iter()
+            .filter(|line| line.contains("media_playing"))
+            .map(|line| line.split(' ').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(dancing, ["20.033s", "30.033s"]);
+    }
+
+    #[test]
+    fn sprite_frame_fits_the_bubble() {
+        let mut digit = Digit::headless(
+            Path::new(ASSETS),
+            Box::new(ManualClock::new(0)),
+            Rng::new(1),
+        );
+        digit.set_position(32.0, 952.0);
+        digit.update_with(TIMESTEP, Inputs::new());
+        digit.say("huff... puff...", 5.0);
+        let opaque = |frame: &Frame| frame.buffer.chunks_exact(4).filter(|p| p[3] > 0).count();
+        let scale = digit.scale;
+        // Drawn with room to spare, nothing can fall off the edges
+        let (left, top) = (
+            digit.sprite.x - 100.0 * scale,
+            digit.sprite.y - 100.0 * scale,
+        );
+        let mut roomy = Frame::new(300, 300);
+        digit.draw(&mut roomy, |x, y| {
+            (
+                ((x - left) / scale).floor() as i32,
+                ((y - top) / scale).floor() as i32,
+            )
+        });
+        let (left, top, width, height) = digit.sprite_frame().unwrap();
+        assert!(top < digit.sprite.y && width > 32);
+        let mut fitted = Frame::new(width, height);
+        digit.draw(&mut fitted, |x, y| {
+            (
+                ((x - left) / scale).floor() as i32,
+                ((y - top) / scale).floor() as i32,
+            )
+        });
+        assert!(opaque(&fitted) > 0);
+        assert_eq!(opaque(&fitted), opaque(&roomy));
+    }
+
+    #[test]
+    fn walks_stay_inside_a_narrow_work_area() {
+        let clock = ManualClock::new(0);
+        let mut digit = Digit::headless(Path::new(ASSETS), Box::new(clock.clone()), Rng::new(5));
+        digit.set_position(100.0, 952.0);
+        let width = 32.0 * digit.scale;
+        for _ in 0..2 * 60 * 30 {
+            let mut inputs = Inputs::new();
+            inputs.work_area = Some(WorkArea {
+                left: 100,
+                top: 0,
+                right: 300,
+                bottom: 1080,
+            });
+            clock.advance(Duration::from_secs_f32(TIMESTEP));
+            digit.update_with(TIMESTEP, inputs);
+            assert!(digit.sprite.x >= 100.0 && digit.sprite.x + width <= 300.0);
+        }
+        // Bounds leave room for whatever he's playing at the moment
+        let (playing, _) = digit.sprite.size().unwrap();
+        assert_eq!(
+            Actor::walk_bounds(&digit),
+            Some((100.0, 300.0 - playing as f32 * digit.scale))
+        );
+    }
+
+    #[test]
+    fn falls_asleep_and_wakes_up() {
+        let idle = ManualIdle::new();
+        let mut activity = ActivityService::new(Box::new(idle.clone()));
+        activity.start().unwrap();
+        let clock = ManualClock::new(12);
+        let mut digit = Digit::headless(Path::new(ASSETS), Box::new(clock.clone()), Rng::new(2));
+        // Every state he went through with the anim it played
+        let mut seen: Vec<(String, String)> = Vec::new();
+        let mut run = |digit: &mut Digit, seconds: f32| {
+            for _ in 0..(seconds / TIMESTEP) as u32 {
+                let mut inputs = Inputs::new();
+                inputs.idle = activity.idle_time().map(|idle| idle.as_secs() as f32);
+                clock.advance(Duration::from_secs_f32(TIMESTEP));
+                digit.update_with(TIMESTEP, inputs);
+                let path = digit.state_path().unwrap_or_default();
+                let state = String::from(path.rsplit('/').next().unwrap_or_default());
+                if seen.last().map(|(last, _)| last) != Some(&state) {
+                    seen.push((state, digit.anim.clone()));
+                }
+            }
+        };
+        run(&mut digit, 2.0);
+        idle.set(Some(Duration::from_secs_f32(digit.sleep_after + 60.0)));
+        run(&mut digit, 10.0);
+        idle.set(Some(Duration::from_secs(0)));
+        run(&mut digit, 3.0);
+
+        let states = seen
+            .iter()
+            .map(|(state, _)| state.as_str())
+            .collect::<Vec<_>>();
+        let asleep = states
+            .iter()
+            .position(|&state| state == "YawnState")
+            .unwrap();
+        assert_eq!(
+            states[asleep..asleep + 4],
+            ["YawnState", "LieDownState", "DozeState", "WakeState"]
+        );
+        assert!(!states[..asleep].contains(&"WakeState"));
+        assert!(!states[asleep + 4..].is_empty());
+        // Each step of falling asleep looks like something of its own
+        let anims = seen[asleep..asleep + 3]
+            .iter()
+            .map(|(_, anim)| anim.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(anims, ["yawning", "lying", "dozing"]);
+        assert!(!seen[..asleep]
+            .iter()
+            .any(|(_, anim)| anims.contains(&anim.as_str())));
+        let frames = |name: &str| {
+            let anim = digit.anim_manager.get(name).unwrap();
+            let mut frame = Frame::new(anim.width(), anim.height());
+            anim.draw(0, false, &mut frame, 0, 0);
+            frame.buffer
+        };
+        assert_ne!(frames("yawning"), frames("lying"));
+        assert_ne!(frames("lying"), frames("dozing"));
+        assert_ne!(frames("idle"), frames("dozing"));
+    }
+
+    #[test]
+    fn metrics_reach_scripted_behavior() {
+        let mut digit = Digit::headless(
+            Path::new(ASSETS),
+            Box::new(ManualClock::new(0)),
+            Rng::new(1),
+        );
+        assert!(digit.behaviors().is_some());
+        let tick = |digit: &mut Digit, metrics: &[MetricsEvent]| {
+            let mut inputs = Inputs::new();
+            inputs.metrics = metrics.to_vec();
+            digit.update_with(TIMESTEP, inputs);
+        };
+        tick(&mut digit, &[]);
+        tick(&mut digit, &[MetricsEvent::CpuBusy, MetricsEvent::Charged]);
+        assert!(digit.flag("cpu_busy") && digit.flag("charged"));
+        assert!(!digit.flag("battery_low"));
+        assert!(digit.transition_history().contains("PantState"));
+        tick(
+            &mut digit,
+            &[MetricsEvent::CpuCalm, MetricsEvent::Unplugged],
+        );
+        assert!(!digit.flag("cpu_busy") && !digit.flag("charged"));
+        // Catching his breath first, the pant is on top until then
+        for _ in 0..100 {
+            tick(&mut digit, &[]);
+        }
+        tick(&mut digit, &[MetricsEvent::BatteryLow]);
+        assert!(digit.flag("battery_low"));
+        assert!(digit
+            .transition_history()
+            .contains("-> YawnState (push): battery is low"));
+    }
 }
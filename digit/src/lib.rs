@@ -1,6 +1,8 @@
 mod digit;
 pub use digit::Digit;
 
+pub mod replay;
+
 pub mod states;
 
 mod util;
@@ -1,24 +1,82 @@
-use digit::Digit;
-use std::time::Instant;
+use dengine::{
+    clock::{ManualClock, SystemClock},
+    rng::Rng,
+};
+use digit::{
+    replay::{Recorder, Replayer},
+    services::{
+        activity::{ActivityService, ManualIdle},
+        audio::AudioService,
+        media::{ManualSource, MediaService},
+        metrics::{ManualMetrics, MetricsService},
+        Services,
+    },
+    Digit,
+};
+use std::{env, io, process, thread, time::Duration};
 
 const TIMESTEP: f32 = 1.0 / 30.0;
 
-fn main() -> windows::Result<()> {
+const USAGE: &str = "usage: digit [--record <file> | --replay <file>]";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => run(None),
+        ["--record", path] => run(Some(path)),
+        ["--replay", path] => replay(path),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+/**
+ * Normal run, optionally recording everything Digit sees to a file
+ */
+fn run(record: Option<&str>) {
     // Instantiate main Digit class to initialize basically everything
-    let mut digit = Digit::new();
+    let rng = Rng::from_entropy();
+    let seed = rng.seed();
+    let mut digit = Digit::with_context(Box::new(SystemClock::new()), rng);
 
-    // Initialize time tracking for update deltas
-    let mut last = Instant::now();
+    let mut recorder = record.map(|path| {
+        let start = (digit.sprite().x, digit.sprite().y);
+        Recorder::create(path, seed, digit.behaviors_hash(), start).unwrap_or_else(|err| {
+            eprintln!("can't record to {}: {}", path, err);
+            process::exit(1);
+        })
+    });
+
+    // Initialize time tracking for update deltas, going through Digit's
+    // clock so a simulated run sees the same time as everything else
+    let mut last = digit.clock().now();
     loop {
         // Track elapsed time since last frame
-        let current = Instant::now();
+        let current = digit.clock().now();
         let mut elapsed = (current - last).as_micros() as f32 / 1000000.0;
         last = current;
 
         // Fixed timestep updating
         while elapsed.abs() > 0.00001 {
             let dt = elapsed.min(TIMESTEP);
-            digit.update(dt);
+            match recorder
+                .as_mut()
+                .map(|recorder| record_tick(recorder, &mut digit, dt))
+            {
+                Some(Ok(())) => (),
+                Some(Err(err)) => {
+                    eprintln!("recording stopped: {}", err);
+                    recorder = None;
+                }
+                None => digit.update(dt),
+            }
             elapsed -= dt;
         }
         digit.render();
@@ -26,3 +84,87 @@ fn main() -> windows::Result<()> {
         //TODO: implement sleeping here to decrease CPU usage
     }
 }
+
+fn record_tick(recorder: &mut Recorder, digit: &mut Digit, dt: f32) -> io::Result<()> {
+    let inputs = digit.poll_inputs();
+    let result = recorder.tick(dt, &inputs);
+    digit.update_with(dt, inputs);
+    result.and_then(|_| recorder.checkpoint(digit))
+}
+
+/**
+ * Play a recording back at its original pace, stopping at the first
+ * point where Digit doesn't end up where he did when it was recorded
+ */
+fn replay(path: &str) {
+    let mut replayer = Replayer::open(path).unwrap_or_else(|err| {
+        eprintln!("can't replay {}: {}", path, err);
+        process::exit(1);
+    });
+    // What was playing, its beat, how long the user was away and how the machine
+    // was doing come from the recording too, and gestures in it shouldn't reach
+    // the player that's running now
+    let media = MediaService::new(Box::new(ManualSource::new()));
+    let services = Services::new()
+        .with(media)
+        .with(AudioService::new(None))
+        .with(ActivityService::new(Box::new(ManualIdle::new())))
+        .with(MetricsService::new(Box::new(ManualMetrics::new())));
+    if Services::install(services).is_err() {
+        eprintln!("services already started, replay may control real media");
+    }
+    // Only drives the main loop's timing, the hour comes from the recording
+    let clock = ManualClock::new(0);
+    let mut digit = Digit::with_context(Box::new(clock.clone()), Rng::new(replayer.seed()));
+    // Replaying against a different behavior file goes its own way sooner
+    // or later, and only the divergence would say so
+    if digit.behaviors_hash() != replayer.behaviors() {
+        eprintln!(
+            "can't replay {}: the behavior file changed since it was recorded",
+            path
+        );
+        process::exit(1);
+    }
+    let (x, y) = replayer.start();
+    digit.set_position(x, y);
+
+    loop {
+        let (delta, inputs) = match replayer.next_tick() {
+            Ok(Some(tick)) => tick,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("can't replay {}: {}", path, err);
+                process::exit(1);
+            }
+        };
+        clock.advance(Duration::from_secs_f32(delta));
+        // Events come from the recording, the window's own would only
+        // pile up in its queue
+        if let Some(window) = digit.window() {
+            window.events().drain();
+        }
+        digit.update_with(delta, inputs);
+        match replayer.verify(&digit) {
+            Ok(Ok(())) => (),
+            Ok(Err(divergence)) => {
+                eprintln!("{}", divergence);
+                eprint!(
+                    "transitions leading up to it:\n{}",
+                    digit.transition_history()
+                );
+                process::exit(1);
+            }
+            Err(err) => {
+                eprintln!("can't replay {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+        digit.render();
+        thread::sleep(Duration::from_secs_f32(delta));
+    }
+    Services::global().stop();
+    println!(
+        "replayed {} ticks, all checkpoints matched",
+        replayer.ticks()
+    );
+}
@@ -0,0 +1,773 @@
+use crate::{services::metrics::MetricsEvent, Digit};
+use dengine::{
+    beat::Beat,
+    event::{DEvent, MouseButton},
+};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/**
+ * Everything from outside that one `Digit::update` gets to see
+ *
+ * Digit reads these instead of asking the OS mid-update, so a recorded
+ * session replays exactly no matter what machine it runs on
+ */
+#[derive(Clone, Debug)]
+pub struct Inputs {
+    /** Window events since the last tick, in frame pixels */
+    pub events: Vec<DEvent>,
+    pub dancing: bool,
+    /** `Title - Artist` of the song playing or paused */
+    pub track: Option<String>,
+    /** Beat of whatever the speakers are playing */
+    pub beat: Option<Beat>,
+    /** Whole seconds since the user last touched keyboard or mouse */
+    pub idle: Option<f32>,
+    /** Machine thresholds crossed since the last tick */
+    pub metrics: Vec<MetricsEvent>,
+    /** Cursor in world coordinates */
+    pub cursor: Option<(i32, i32)>,
+    /** Usable area of the monitor Digit is on, taskbar excluded */
+    pub work_area: Option<WorkArea>,
+    /** World position of the frame's top-left pixel */
+    pub origin: (i32, i32),
+    pub hour: u32,
+}
+
+impl Inputs {
+    pub fn new() -> Inputs {
+        Inputs {
+            events: Vec::new(),
+            dancing: false,
+            track: None,
+            beat: None,
+            idle: None,
+            metrics: Vec::new(),
+            cursor: None,
+            work_area: None,
+            origin: (0, 0),
+            hour: 12,
+        }
+    }
+}
+
+impl Default for Inputs {
+    fn default() -> Self {
+        Inputs::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorkArea {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+// File layout: header (magic, version, rng seed, behavior file hash,
+// start position), then a stream of records each starting with a
+// tag byte. Input records hold until changed and apply to the next
+// tick, events and metrics events only to the next tick. Numbers are
+// little endian.
+//
+// Key events are left out: nothing reacts to them yet, and winit's key
+// codes have no fixed numbering to store. Recording them would take
+// numbering them here and a new version.
+const MAGIC: &[u8; 4] = b"DREC";
+const VERSION: u8 = 6;
+
+const TICK: u8 = 1;
+const EVENT: u8 = 2;
+const DANCING: u8 = 3;
+const CURSOR: u8 = 4;
+const WORK_AREA: u8 = 5;
+const ORIGIN: u8 = 6;
+const HOUR: u8 = 7;
+const CHECKPOINT: u8 = 8;
+const TRACK: u8 = 9;
+const BEAT: u8 = 10;
+const IDLE: u8 = 11;
+const METRICS: u8 = 12;
+
+// Ticks between checkpoints, on top of one per state change
+const CHECKPOINT_EVERY: u32 = 30;
+
+/**
+ * Where Digit is and what he's doing after a tick, for replays to
+ * compare against
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub tick: u32,
+    pub state: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Checkpoint {
+    fn of(digit: &Digit, tick: u32) -> Checkpoint {
+        Checkpoint {
+            tick,
+            state: digit.state_path().unwrap_or_default(),
+            x: digit.sprite().x,
+            y: digit.sprite().y,
+        }
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at ({}, {})", self.state, self.x, self.y)
+    }
+}
+
+/**
+ * Writes a session to a file as it happens
+ *
+ * Call `tick` with the inputs right before `Digit::update_with` and
+ * `checkpoint` right after. Flushes at every checkpoint, so a session
+ * killed while stuck still leaves a useful file.
+ */
+pub struct Recorder {
+    out: BufWriter<File>,
+    last: HashMap<u8, Vec<u8>>,
+    ticks: u32,
+    last_state: Option<String>,
+}
+
+impl Recorder {
+    /**
+     * `seed` is the seed of Digit's `Rng`, `behaviors` the hash of the
+     * behavior file he runs, `start` where he starts out
+     */
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        seed: u64,
+        behaviors: u64,
+        start: (f32, f32),
+    ) -> io::Result<Recorder> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        out.write_all(&seed.to_le_bytes())?;
+        out.write_all(&behaviors.to_le_bytes())?;
+        out.write_all(&start.0.to_le_bytes())?;
+        out.write_all(&start.1.to_le_bytes())?;
+        Ok(Recorder {
+            out,
+            last: HashMap::new(),
+            ticks: 0,
+            last_state: None,
+        })
+    }
+
+    pub fn tick(&mut self, delta: f32, inputs: &Inputs) -> io::Result<()> {
+        for event in &inputs.events {
+            let mut bytes = Vec::new();
+            if encode_event(event, &mut bytes) {
+                self.write_record(EVENT, &bytes)?;
+            }
+        }
+        for event in &inputs.metrics {
+            self.write_record(METRICS, &[encode_metrics_event(*event)])?;
+        }
+
+        let mut cursor = vec![inputs.cursor.is_some() as u8];
+        if let Some((x, y)) = inputs.cursor {
+            cursor.extend_from_slice(&x.to_le_bytes());
+            cursor.extend_from_slice(&y.to_le_bytes());
+        }
+        let mut work_area = vec![inputs.work_area.is_some() as u8];
+        if let Some(area) = inputs.work_area {
+            for value in &[area.left, area.top, area.right, area.bottom] {
+                work_area.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let mut track = vec![inputs.track.is_some() as u8];
+        if let Some(name) = &inputs.track {
+            encode_string(name, &mut track);
+        }
+        let mut beat = vec![inputs.beat.is_some() as u8];
+        if let Some(b) = inputs.beat {
+            for value in &[b.bpm, b.phase, b.confidence] {
+                beat.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let mut idle = vec![inputs.idle.is_some() as u8];
+        if let Some(seconds) = inputs.idle {
+            idle.extend_from_slice(&seconds.to_le_bytes());
+        }
+        let mut origin = inputs.origin.0.to_le_bytes().to_vec();
+        origin.extend_from_slice(&inputs.origin.1.to_le_bytes());
+        let records = vec![
+            (DANCING, vec![inputs.dancing as u8]),
+            (TRACK, track),
+            (BEAT, beat),
+            (IDLE, idle),
+            (CURSOR, cursor),
+            (WORK_AREA, work_area),
+            (ORIGIN, origin),
+            (HOUR, vec![inputs.hour as u8]),
+        ];
+        // Only what changed since the last tick, so everything on the first
+        for (tag, bytes) in records {
+            if self.last.get(&tag) != Some(&bytes) {
+                self.write_record(tag, &bytes)?;
+                self.last.insert(tag, bytes);
+            }
+        }
+
+        self.write_record(TICK, &delta.to_le_bytes())?;
+        self.ticks += 1;
+        Ok(())
+    }
+
+    /**
+     * Record where Digit ended up, only written every so often or when
+     * his state changed
+     */
+    pub fn checkpoint(&mut self, digit: &Digit) -> io::Result<()> {
+        let checkpoint = Checkpoint::of(digit, self.ticks);
+        let changed = self.last_state.as_ref() != Some(&checkpoint.state);
+        if !changed && self.ticks % CHECKPOINT_EVERY != 0 {
+            return Ok(());
+        }
+        let mut bytes = checkpoint.tick.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&checkpoint.x.to_le_bytes());
+        bytes.extend_from_slice(&checkpoint.y.to_le_bytes());
+        encode_string(&checkpoint.state, &mut bytes);
+        self.write_record(CHECKPOINT, &bytes)?;
+        self.last_state = Some(checkpoint.state);
+        self.out.flush()
+    }
+
+    fn write_record(&mut self, tag: u8, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(&[tag])?;
+        self.out.write_all(bytes)
+    }
+}
+
+/**
+ * First point where a replay stopped matching its recording
+ */
+#[derive(Debug)]
+pub struct Divergence {
+    pub expected: Checkpoint,
+    pub actual: Checkpoint,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "replay diverged at tick {}: recorded {}, replayed {}",
+            self.expected.tick, self.expected, self.actual
+        )
+    }
+}
+
+/**
+ * Reads a recorded session back one tick at a time
+ *
+ * Build Digit with `Rng::new(replayer.seed())`, check he loaded the
+ * same `behaviors()` it was recorded with, move him to `start()`, then
+ * feed every `next_tick` into `Digit::update_with` and `verify` after
+ * each one
+ */
+pub struct Replayer {
+    data: Vec<u8>,
+    pos: usize,
+    seed: u64,
+    behaviors: u64,
+    start: (f32, f32),
+    inputs: Inputs,
+    ticks: u32,
+}
+
+impl Replayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Replayer> {
+        let data = fs::read(path)?;
+        if data.len() < 29 || &data[..4] != MAGIC || data[4] != VERSION {
+            return Err(invalid("not a digit recording, or from another version"));
+        }
+        let mut replayer = Replayer {
+            data,
+            pos: 5,
+            seed: 0,
+            behaviors: 0,
+            start: (0.0, 0.0),
+            inputs: Inputs::new(),
+            ticks: 0,
+        };
+        replayer.seed = u64::from_le_bytes(replayer.take(8)?.try_into().unwrap());
+        replayer.behaviors = u64::from_le_bytes(replayer.take(8)?.try_into().unwrap());
+        replayer.start = (replayer.f32()?, replayer.f32()?);
+        Ok(replayer)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /**
+     * `Digit::behaviors_hash` when it was recorded, a different one
+     * means the behavior file changed and the replay won't match
+     */
+    pub fn behaviors(&self) -> u64 {
+        self.behaviors
+    }
+
+    pub fn start(&self) -> (f32, f32) {
+        self.start
+    }
+
+    /**
+     * Ticks replayed so far
+     */
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /**
+     * Delta and inputs of the next tick, None at the end of the file
+     */
+    pub fn next_tick(&mut self) -> io::Result<Option<(f32, Inputs)>> {
+        self.inputs.events.clear();
+        self.inputs.metrics.clear();
+        while self.pos < self.data.len() {
+            let tag = self.take(1)?[0];
+            match tag {
+                TICK => {
+                    let delta = self.f32()?;
+                    self.ticks += 1;
+                    return Ok(Some((delta, self.inputs.clone())));
+                }
+                EVENT => {
+                    let event = self.event()?;
+                    self.inputs.events.push(event);
+                }
+                METRICS => {
+                    let event = self.metrics_event()?;
+                    self.inputs.metrics.push(event);
+                }
+                DANCING => self.inputs.dancing = self.take(1)?[0] != 0,
+                TRACK => {
+                    self.inputs.track = match self.take(1)?[0] {
+                        0 => None,
+                        _ => Some(self.string()?),
+                    }
+                }
+                BEAT => {
+                    self.inputs.beat = match self.take(1)?[0] {
+                        0 => None,
+                        _ => Some(Beat {
+                            bpm: self.f32()?,
+                            phase: self.f32()?,
+                            confidence: self.f32()?,
+                        }),
+                    }
+                }
+                IDLE => {
+                    self.inputs.idle = match self.take(1)?[0] {
+                        0 => None,
+                        _ => Some(self.f32()?),
+                    }
+                }
+                CURSOR => {
+                    self.inputs.cursor = match self.take(1)?[0] {
+                        0 => None,
+                        _ => Some((self.i32()?, self.i32()?)),
+                    }
+                }
+                WORK_AREA => {
+                    self.inputs.work_area = match self.take(1)?[0] {
+                        0 => None,
+                        _ => Some(WorkArea {
+                            left: self.i32()?,
+                            top: self.i32()?,
+                            right: self.i32()?,
+                            bottom: self.i32()?,
+                        }),
+                    }
+                }
+                ORIGIN => self.inputs.origin = (self.i32()?, self.i32()?),
+                HOUR => self.inputs.hour = self.take(1)?[0] as u32,
+                // Belongs to the tick before, `verify` should have read it
+                CHECKPOINT => {
+                    self.checkpoint()?;
+                }
+                _ => return Err(invalid("unknown record")),
+            }
+        }
+        Ok(None)
+    }
+
+    /**
+     * Compare Digit against the checkpoint recorded after the tick just
+     * replayed, if there is one
+     */
+    pub fn verify(&mut self, digit: &Digit) -> io::Result<Result<(), Divergence>> {
+        if self.data.get(self.pos) != Some(&CHECKPOINT) {
+            return Ok(Ok(()));
+        }
+        self.pos += 1;
+        let expected = self.checkpoint()?;
+        let actual = Checkpoint::of(digit, self.ticks);
+        if expected == actual {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(Divergence { expected, actual }))
+        }
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Checkpoint> {
+        let tick = self.u32()?;
+        let x = self.f32()?;
+        let y = self.f32()?;
+        let state = self.string()?;
+        Ok(Checkpoint { tick, state, x, y })
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("string isn't utf-8"))
+    }
+
+    fn metrics_event(&mut self) -> io::Result<MetricsEvent> {
+        let event = match self.take(1)?[0] {
+            0 => MetricsEvent::CpuBusy,
+            1 => MetricsEvent::CpuCalm,
+            2 => MetricsEvent::MemoryFull,
+            3 => MetricsEvent::MemoryFreed,
+            4 => MetricsEvent::BatteryLow,
+            5 => MetricsEvent::BatteryRecovered,
+            6 => MetricsEvent::PluggedIn,
+            7 => MetricsEvent::Unplugged,
+            8 => MetricsEvent::Charged,
+            _ => return Err(invalid("unknown metrics event")),
+        };
+        Ok(event)
+    }
+
+    fn event(&mut self) -> io::Result<DEvent> {
+        let event = match self.take(1)?[0] {
+            1 => DEvent::MouseMoved {
+                x: self.f32()?,
+                y: self.f32()?,
+            },
+            2 => DEvent::MousePressed {
+                button: self.button()?,
+                x: self.f32()?,
+                y: self.f32()?,
+            },
+            3 => DEvent::MouseReleased {
+                button: self.button()?,
+                x: self.f32()?,
+                y: self.f32()?,
+            },
+            4 => DEvent::MouseWheel {
+                delta: self.f32()?,
+                x: self.f32()?,
+                y: self.f32()?,
+            },
+            5 => DEvent::MouseEntered,
+            6 => DEvent::MouseLeft,
+            7 => DEvent::Focused(self.take(1)?[0] != 0),
+            8 => DEvent::WindowMoved {
+                x: self.i32()?,
+                y: self.i32()?,
+            },
+            9 => DEvent::WindowResized {
+                width: self.u32()?,
+                height: self.u32()?,
+            },
+            10 => DEvent::ScaleChanged {
+                scale: f64::from_le_bytes(self.take(8)?.try_into().unwrap()),
+            },
+            11 => DEvent::DisplayChanged,
+            12 => DEvent::Timer(self.u32()?),
+            _ => return Err(invalid("unknown event")),
+        };
+        Ok(event)
+    }
+
+    fn button(&mut self) -> io::Result<MouseButton> {
+        let button = match self.take(1)?[0] {
+            0 => MouseButton::Left,
+            1 => MouseButton::Right,
+            2 => MouseButton::Middle,
+            _ => MouseButton::Other(u16::from_le_bytes(self.take(2)?.try_into().unwrap())),
+        };
+        Ok(button)
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.pos + len > self.data.len() {
+            return Err(invalid("recording ends mid-record"));
+        }
+        self.pos += len;
+        Ok(&self.data[self.pos - len..self.pos])
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+/**
+ * FNV-1a, which unlike std's hashers is guaranteed to give the same
+ * hash on every build, for telling whether a file changed between
+ * recording and replaying
+ */
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/**
+ * Length as a u16 then the bytes, cut short to fit
+ */
+fn encode_string(string: &str, out: &mut Vec<u8>) {
+    let mut len = string.len().min(u16::MAX as usize);
+    while !string.is_char_boundary(len) {
+        len -= 1;
+    }
+    out.extend_from_slice(&(len as u16).to_le_bytes());
+    out.extend_from_slice(string[..len].as_bytes());
+}
+
+/**
+ * Byte a metrics event is recorded as
+ */
+fn encode_metrics_event(event: MetricsEvent) -> u8 {
+    match event {
+        MetricsEvent::CpuBusy => 0,
+        MetricsEvent::CpuCalm => 1,
+        MetricsEvent::MemoryFull => 2,
+        MetricsEvent::MemoryFreed => 3,
+        MetricsEvent::BatteryLow => 4,
+        MetricsEvent::BatteryRecovered => 5,
+        MetricsEvent::PluggedIn => 6,
+        MetricsEvent::Unplugged => 7,
+        MetricsEvent::Charged => 8,
+    }
+}
+
+/**
+ * Append an event's record, false for events that can't be recorded
+ * (keys, see the file layout, and custom payloads)
+ */
+fn encode_event(event: &DEvent, out: &mut Vec<u8>) -> bool {
+    let floats = |out: &mut Vec<u8>, values: &[f32]| {
+        for value in values {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    };
+    let button = |out: &mut Vec<u8>, button: &MouseButton| match button {
+        MouseButton::Left => out.push(0),
+        MouseButton::Right => out.push(1),
+        MouseButton::Middle => out.push(2),
+        MouseButton::Other(other) => {
+            out.push(3);
+            out.extend_from_slice(&other.to_le_bytes());
+        }
+    };
+    match event {
+        DEvent::MouseMoved { x, y } => {
+            out.push(1);
+            floats(out, &[*x, *y]);
+        }
+        DEvent::MousePressed { button: b, x, y } => {
+            out.push(2);
+            button(out, b);
+            floats(out, &[*x, *y]);
+        }
+        DEvent::MouseReleased { button: b, x, y } => {
+            out.push(3);
+            button(out, b);
+            floats(out, &[*x, *y]);
+        }
+        DEvent::MouseWheel { delta, x, y } => {
+            out.push(4);
+            floats(out, &[*delta, *x, *y]);
+        }
+        DEvent::MouseEntered => out.push(5),
+        DEvent::MouseLeft => out.push(6),
+        DEvent::Focused(focused) => out.extend_from_slice(&[7, *focused as u8]),
+        DEvent::WindowMoved { x, y } => {
+            out.push(8);
+            out.extend_from_slice(&x.to_le_bytes());
+            out.extend_from_slice(&y.to_le_bytes());
+        }
+        DEvent::WindowResized { width, height } => {
+            out.push(9);
+            out.extend_from_slice(&width.to_le_bytes());
+            out.extend_from_slice(&height.to_le_bytes());
+        }
+        DEvent::ScaleChanged { scale } => {
+            out.push(10);
+            out.extend_from_slice(&scale.to_le_bytes());
+        }
+        DEvent::DisplayChanged => out.push(11),
+        DEvent::Timer(id) => {
+            out.push(12);
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        DEvent::KeyPressed(_) | DEvent::KeyReleased(_) | DEvent::Custom(_) => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dengine::{clock::ManualClock, rng::Rng};
+    use std::{env, process, time::Duration};
+
+    const TIMESTEP: f32 = 1.0 / 30.0;
+    const SEED: u64 = 11;
+    const ASSETS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+    /**
+     * Half a minute of a bit of everything: a song with its beat,
+     * clicks on Digit, the monitors changing, the machine getting busy
+     * and the user wandering off
+     */
+    fn inputs(tick: u32, digit: &Digit) -> Inputs {
+        let mut inputs = Inputs::new();
+        let moved = tick >= 600;
+        inputs.origin = match moved {
+            true => (-1920, 0),
+            false => (0, 0),
+        };
+        inputs.work_area = Some(match moved {
+            true => WorkArea {
+                left: -1920,
+                top: 0,
+                right: 1280,
+                bottom: 1040,
+            },
+            false => WorkArea {
+                left: 0,
+                top: 0,
+                right: 1920,
+                bottom: 1080,
+            },
+        });
+        if tick == 600 {
+            inputs.events.push(DEvent::DisplayChanged);
+        }
+        if (200..400).contains(&tick) {
+            inputs.dancing = true;
+            inputs.track = Some(String::from("Song - Artist"));
+            inputs.beat = Some(Beat {
+                bpm: 120.0,
+                phase: (tick % 15) as f32 / 15.0,
+                confidence: 0.9,
+            });
+        }
+        // Clicks right on him, in frame pixels
+        let scale = digit.scale();
+        let x = (digit.sprite().x - inputs.origin.0 as f32) / scale + 4.0;
+        let y = (digit.sprite().y - inputs.origin.1 as f32) / scale + 4.0;
+        let button = MouseButton::Left;
+        match tick {
+            100 | 700 => inputs.events.push(DEvent::MousePressed { button, x, y }),
+            101 | 701 => inputs.events.push(DEvent::MouseReleased { button, x, y }),
+            _ => (),
+        }
+        inputs.metrics = match tick {
+            450 => vec![MetricsEvent::CpuBusy, MetricsEvent::Unplugged],
+            520 => vec![MetricsEvent::CpuCalm],
+            800 => vec![MetricsEvent::BatteryLow],
+            _ => Vec::new(),
+        };
+        inputs.idle = Some((tick / 30) as f32);
+        inputs.cursor = Some((tick as i32, 500));
+        inputs
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let path = env::temp_dir().join(format!("digit-replay-{}.drec", process::id()));
+
+        let clock = ManualClock::new(12);
+        let mut digit = Digit::headless(Path::new(ASSETS), Box::new(clock.clone()), Rng::new(SEED));
+        digit.set_position(32.0, 952.0);
+        let mut recorder =
+            Recorder::create(&path, SEED, digit.behaviors_hash(), (32.0, 952.0)).unwrap();
+        let mut recorded = Vec::new();
+        for tick in 0..900 {
+            let inputs = inputs(tick, &digit);
+            recorder.tick(TIMESTEP, &inputs).unwrap();
+            clock.advance(Duration::from_secs_f32(TIMESTEP));
+            digit.update_with(TIMESTEP, inputs);
+            recorder.checkpoint(&digit).unwrap();
+            recorded.push((digit.sprite().x, digit.sprite().y));
+        }
+        drop(recorder);
+        let history = digit.transition_history();
+
+        let mut replayer = Replayer::open(&path).unwrap();
+        assert_eq!(replayer.seed(), SEED);
+        let clock = ManualClock::new(12);
+        let mut digit = Digit::headless(
+            Path::new(ASSETS),
+            Box::new(clock.clone()),
+            Rng::new(replayer.seed()),
+        );
+        assert_eq!(replayer.behaviors(), digit.behaviors_hash());
+        let (x, y) = replayer.start();
+        digit.set_position(x, y);
+        let mut replayed = Vec::new();
+        while let Some((delta, inputs)) = replayer.next_tick().unwrap() {
+            clock.advance(Duration::from_secs_f32(delta));
+            digit.update_with(delta, inputs);
+            if let Err(divergence) = replayer.verify(&digit).unwrap() {
+                panic!("{}", divergence);
+            }
+            replayed.push((digit.sprite().x, digit.sprite().y));
+        }
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(replayer.ticks(), 900);
+        assert_eq!(digit.transition_history(), history);
+        assert_eq!(replayed, recorded);
+        for reason in &[
+            "media_playing",
+            "clicked",
+            "cpu is pegged",
+            "battery is low",
+        ] {
+            assert!(
+                history.contains(reason),
+                "{} missing from\n{}",
+                reason,
+                history
+            );
+        }
+    }
+}
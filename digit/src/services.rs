@@ -1,46 +1,483 @@
+pub mod activity;
+pub mod audio;
 pub mod media;
+pub mod metrics;
 
+use activity::ActivityService;
+use audio::AudioService;
+use dengine::fsm::short_type_name;
+use lazy_static::lazy_static;
 use media::MediaService;
+use metrics::MetricsService;
 use parking_lot::{Mutex, MutexGuard};
-use std::sync::Arc;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    error, fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-static mut SERVICES: Option<Services> = None;
+// Wait before the first retry of a service that didn't start, doubling
+// after each failure up to RETRY_MAX
+const RETRY_FIRST: Duration = Duration::from_secs(2);
+const RETRY_MAX: Duration = Duration::from_secs(5 * 60);
+// How often the retry thread checks whether it was stopped while waiting
+const RETRY_POLL: Duration = Duration::from_millis(250);
 
 /**
- * Static struct that contains all services
- * 
- * MUTABLE STATIC DATA IS DANGEROUS, BE VERY CAREFUL
- * CONTAIN ALL DATA IN THREAD-SAFE STRUCTURES
+ * Why a service couldn't start
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServiceError {
+    /** What it needs isn't on this system, or can't be reached right now */
+    Unavailable(String),
+    /** It was there but something went wrong talking to it */
+    Failed(String),
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::Unavailable(message) => write!(f, "unavailable: {}", message),
+            ServiceError::Failed(message) => write!(f, "failed: {}", message),
+        }
+    }
+}
+
+impl error::Error for ServiceError {}
+
+/**
+ * How a service is doing, as far as the registry knows
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Health {
+    /** Not started yet, or stopped */
+    Stopped,
+    Running,
+    /** Didn't start or stopped working, being retried in the background */
+    Unavailable {
+        error: ServiceError,
+        attempts: u32,
+    },
+}
+
+impl Health {
+    pub fn is_running(&self) -> bool {
+        *self == Health::Running
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Health::Stopped => write!(f, "stopped"),
+            Health::Running => write!(f, "running"),
+            Health::Unavailable { error, attempts } => {
+                write!(f, "{} ({} attempts)", error, attempts)
+            }
+        }
+    }
+}
+
+/**
+ * Called by a running service that stopped working, possibly from
+ * another thread
+ */
+pub type Lost = Arc<dyn Fn(ServiceError) + Send + Sync>;
+
+/**
+ * Something running alongside Digit that the registry owns
+ *
+ * Construction should be cheap, connect to the OS in `start`
+ */
+pub trait Service: Any + Send {
+    /**
+     * Called by `Services::start`, in registration order, then again in
+     * the background until it succeeds
+     *
+     * Leave things so `start` can be called again after an error
+     */
+    fn start(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    /**
+     * Called by `Services::stop`, in reverse registration order, only if
+     * it started
+     */
+    fn stop(&mut self) {}
+
+    /**
+     * Called by `Services::start` before `start`, with what to call if
+     * the service stops working later on so it's stopped and retried
+     * like one that didn't start
+     */
+    fn on_lost(&mut self, _lost: Lost) {}
+
+    fn name(&self) -> &str {
+        short_type_name::<Self>()
+    }
+}
+
+struct Entry {
+    // The `Arc<Mutex<S>>` itself, to downcast back to the concrete type
+    typed: Box<dyn Any + Send + Sync>,
+    service: Arc<Mutex<dyn Service>>,
+    health: Arc<Mutex<Health>>,
+}
+
+/**
+ * A service that didn't start, as handed to the retry thread
+ */
+struct Retry {
+    name: String,
+    service: Arc<Mutex<dyn Service>>,
+    health: Arc<Mutex<Health>>,
+}
+
+lazy_static! {
+    static ref GLOBAL: Mutex<Option<&'static Services>> = Mutex::new(None);
+}
+
+/**
+ * Registry of services, looked up by type
+ *
+ * `Services::global()` is the one Digit runs with. Tests and tools can
+ * build their own with mock services, or `install` one as the global
+ * before anything asks for it.
+ *
+ * A service that fails to start, or reports it was lost once running,
+ * doesn't stop the others or Digit, it's marked unavailable and retried
+ * with backoff until it comes up.
  */
 pub struct Services {
-    media_service: Arc<Mutex<MediaService>>,
+    entries: HashMap<TypeId, Entry>,
+    order: Vec<TypeId>,
+    // Set while started, cleared to stop the retry thread
+    running: Mutex<Option<Arc<AtomicBool>>>,
 }
 
 impl Services {
     /**
-     * Get an instance of the Services singleton
-     * 
-     * Uses lazy singleton construction pattern, call early if
-     * this takes a while
+     * Empty registry
      */
-    fn instance() -> &'static Services {
-        unsafe {
-            if SERVICES.is_none() {
-                SERVICES = Some(Services {
-                    media_service: MediaService::new(),
-                })
-            }
-            SERVICES.as_ref().unwrap()
+    pub fn new() -> Services {
+        Services {
+            entries: HashMap::new(),
+            order: Vec::new(),
+            running: Mutex::new(None),
+        }
+    }
+
+    /**
+     * Every service Digit needs, talking to the real OS
+     */
+    pub fn system() -> Services {
+        Services::new()
+            .with(MediaService::new(media::system_source()))
+            .with(AudioService::new(audio::system_source()))
+            .with(ActivityService::new(activity::system_source()))
+            .with(MetricsService::new(metrics::system_source()))
+    }
+
+    pub fn with<S: Service>(mut self, service: S) -> Services {
+        self.register(service);
+        self
+    }
+
+    /**
+     * Add a service, replacing any other of the same type but keeping
+     * its place in the start order
+     */
+    pub fn register<S: Service>(&mut self, service: S) {
+        let typed = Arc::new(Mutex::new(service));
+        let entry = Entry {
+            service: typed.clone(),
+            typed: Box::new(typed),
+            health: Arc::new(Mutex::new(Health::Stopped)),
+        };
+        if self.entries.insert(TypeId::of::<S>(), entry).is_none() {
+            self.order.push(TypeId::of::<S>());
         }
     }
 
+    pub fn get<S: Service>(&self) -> Option<Arc<Mutex<S>>> {
+        self.entries
+            .get(&TypeId::of::<S>())
+            .and_then(|entry| entry.typed.downcast_ref::<Arc<Mutex<S>>>())
+            .cloned()
+    }
+
     /**
-     * Lock and get a reference to MediaService
-     * 
+     * Lock and get a reference to a service, None if it isn't
+     * registered
+     *
      * Unlocks as it goes out of scope, or with a `drop()` if you
-     * need it to be unlocked earlier
+     * need it to be unlocked earlier.
+     */
+    pub fn lock<S: Service>(&self) -> Option<MutexGuard<'_, S>> {
+        self.entries
+            .get(&TypeId::of::<S>())
+            .and_then(|entry| entry.typed.downcast_ref::<Arc<Mutex<S>>>())
+            .map(|service| service.lock())
+    }
+
+    /**
+     * Like `lock`, but None while someone else holds the service, say a
+     * retry waiting on a slow `start`
      */
-    pub fn media() -> MutexGuard<'static, MediaService> {
-        Self::instance().media_service.lock()
+    pub fn try_lock<S: Service>(&self) -> Option<MutexGuard<'_, S>> {
+        self.entries
+            .get(&TypeId::of::<S>())
+            .and_then(|entry| entry.typed.downcast_ref::<Arc<Mutex<S>>>())
+            .and_then(|service| service.try_lock())
+    }
+
+    /**
+     * Start every service in the order they were registered, does
+     * nothing if already started
+     *
+     * Services that fail are retried on a background thread until they
+     * start or the registry is stopped
+     */
+    pub fn start(&self) {
+        let mut running = self.running.lock();
+        if running.is_some() {
+            return;
+        }
+        let flag = Arc::new(AtomicBool::new(true));
+        let mut failed = Vec::new();
+        for id in &self.order {
+            let entry = &self.entries[id];
+            let mut service = entry.service.lock();
+            let name = String::from(service.name());
+            service.on_lost(lost(name, entry, &flag));
+            match service.start() {
+                Ok(()) => *entry.health.lock() = Health::Running,
+                Err(error) => {
+                    eprintln!("{} {}, retrying in the background", service.name(), error);
+                    *entry.health.lock() = Health::Unavailable { error, attempts: 1 };
+                    failed.push(Retry {
+                        name: String::from(service.name()),
+                        service: entry.service.clone(),
+                        health: entry.health.clone(),
+                    });
+                }
+            }
+        }
+        if !failed.is_empty() {
+            let flag = flag.clone();
+            thread::spawn(move || retry(failed, flag));
+        }
+        *running = Some(flag);
+    }
+
+    /**
+     * Stop every running service in reverse order, so nothing stops
+     * before a service started after it
+     */
+    pub fn stop(&self) {
+        let mut running = self.running.lock();
+        match running.take() {
+            Some(flag) => flag.store(false, Ordering::SeqCst),
+            None => return,
+        }
+        for id in self.order.iter().rev() {
+            let entry = &self.entries[id];
+            // Waits out a retry in progress, which may have just started it
+            let mut service = entry.service.lock();
+            let mut health = entry.health.lock();
+            if health.is_running() {
+                service.stop();
+            }
+            *health = Health::Stopped;
+        }
+    }
+
+    /**
+     * How a service is doing, None if it isn't registered
+     */
+    pub fn health<S: Service>(&self) -> Option<Health> {
+        self.entries
+            .get(&TypeId::of::<S>())
+            .map(|entry| entry.health.lock().clone())
+    }
+
+    /**
+     * Every service's name and health, in start order
+     */
+    pub fn report(&self) -> Vec<(String, Health)> {
+        self.order
+            .iter()
+            .map(|id| {
+                let entry = &self.entries[id];
+                let name = String::from(entry.service.lock().name());
+                (name, entry.health.lock().clone())
+            })
+            .collect()
+    }
+
+    /**
+     * Names of every service, in start order
+     */
+    pub fn names(&self) -> Vec<String> {
+        self.order
+            .iter()
+            .map(|id| String::from(self.entries[id].service.lock().name()))
+            .collect()
+    }
+
+    /**
+     * Make `services` the global registry, fails if something already
+     * asked for the global one
+     */
+    pub fn install(services: Services) -> Result<(), Services> {
+        let mut global = GLOBAL.lock();
+        if global.is_some() {
+            return Err(services);
+        }
+        *global = Some(Box::leak(Box::new(services)));
+        Ok(())
+    }
+
+    /**
+     * The registry Digit runs with, `Services::system()` unless another
+     * one was installed first
+     *
+     * Constructed once even if several threads ask at the same time,
+     * call early if this takes a while. Not started until someone calls
+     * `start`.
+     */
+    pub fn global() -> &'static Services {
+        let mut global = GLOBAL.lock();
+        global.get_or_insert_with(|| Box::leak(Box::new(Services::system())))
+    }
+
+    /**
+     * Lock and get a reference to the global MediaService, None if an
+     * installed registry left it out
+     */
+    pub fn media() -> Option<MutexGuard<'static, MediaService>> {
+        Self::global().lock()
+    }
+
+    /**
+     * Lock and get a reference to the global AudioService, None if an
+     * installed registry left it out
+     */
+    pub fn audio() -> Option<MutexGuard<'static, AudioService>> {
+        Self::global().lock()
+    }
+
+    /**
+     * Lock and get a reference to the global ActivityService, None if an
+     * installed registry left it out
+     */
+    pub fn activity() -> Option<MutexGuard<'static, ActivityService>> {
+        Self::global().lock()
+    }
+
+    /**
+     * Lock and get a reference to the global MetricsService, None if an
+     * installed registry left it out
+     */
+    pub fn metrics() -> Option<MutexGuard<'static, MetricsService>> {
+        Self::global().lock()
+    }
+}
+
+impl Default for Services {
+    fn default() -> Self {
+        Services::new()
+    }
+}
+
+/**
+ * What `entry` calls when it stops working: stop it and retry it on a
+ * thread of its own, unless it already was or the registry stopped
+ */
+fn lost(name: String, entry: &Entry, running: &Arc<AtomicBool>) -> Lost {
+    let service = Arc::downgrade(&entry.service);
+    let health = Arc::downgrade(&entry.health);
+    let running = running.clone();
+    Arc::new(move |error| {
+        let (service, health) = match (service.upgrade(), health.upgrade()) {
+            (Some(service), Some(health)) => (service, health),
+            _ => return,
+        };
+        let name = name.clone();
+        let running = running.clone();
+        // Whoever reports it may have the service locked
+        thread::spawn(move || {
+            {
+                let mut locked = service.lock();
+                let mut health = health.lock();
+                if !running.load(Ordering::SeqCst) || !health.is_running() {
+                    return;
+                }
+                eprintln!("{} {}, retrying in the background", name, error);
+                locked.stop();
+                *health = Health::Unavailable { error, attempts: 1 };
+            }
+            retry(
+                vec![Retry {
+                    name,
+                    service,
+                    health,
+                }],
+                running,
+            );
+        });
+    })
+}
+
+/**
+ * Keep starting `failed` services with exponential backoff until they all
+ * run or `running` is cleared
+ */
+fn retry(mut failed: Vec<Retry>, running: Arc<AtomicBool>) {
+    let mut wait = RETRY_FIRST;
+    while !failed.is_empty() {
+        let until = Instant::now() + wait;
+        loop {
+            if !running.load(Ordering::SeqCst) {
+                return;
+            }
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+            thread::sleep(RETRY_POLL.min(until - now));
+        }
+        failed.retain(|retry| {
+            let mut service = retry.service.lock();
+            // Checked with the service locked, so `stop` can't miss it
+            if !running.load(Ordering::SeqCst) {
+                return true;
+            }
+            let result = service.start();
+            let mut health = retry.health.lock();
+            match result {
+                Ok(()) => {
+                    eprintln!("{} started", retry.name);
+                    *health = Health::Running;
+                    false
+                }
+                Err(error) => {
+                    let attempts = match &*health {
+                        Health::Unavailable { attempts, .. } => attempts + 1,
+                        _ => 1,
+                    };
+                    *health = Health::Unavailable { error, attempts };
+                    true
+                }
+            }
+        });
+        wait = (wait * 2).min(RETRY_MAX);
     }
 }
@@ -0,0 +1,96 @@
+mod manual;
+#[cfg(windows)]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub use manual::ManualIdle;
+#[cfg(windows)]
+pub use win32::Win32Idle;
+#[cfg(target_os = "linux")]
+pub use x11::X11Idle;
+
+use super::{Service, ServiceError};
+use std::time::Duration;
+
+/**
+ * Somewhere to ask how long it's been since the user last typed or
+ * moved the mouse
+ */
+pub trait IdleSource: Send {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    fn stop(&mut self) {}
+
+    /**
+     * Time since the last input anywhere on the system, None if it
+     * can't be told right now
+     */
+    fn idle_time(&self) -> Option<Duration>;
+}
+
+/**
+ * The source for the platform being run on, one that never goes idle
+ * where there isn't any
+ */
+pub fn system_source() -> Box<dyn IdleSource> {
+    #[cfg(windows)]
+    let source = Win32Idle::new();
+    #[cfg(target_os = "linux")]
+    let source = X11Idle::new();
+    #[cfg(not(any(windows, target_os = "linux")))]
+    let source = ManualIdle::new();
+    Box::new(source)
+}
+
+/**
+ * Tells how long the user has been away from the keyboard and mouse
+ *
+ * Until the source starts the idle time isn't known, and nothing should
+ * treat the user as gone
+ */
+pub struct ActivityService {
+    source: Box<dyn IdleSource>,
+    available: bool,
+}
+
+impl ActivityService {
+    /**
+     * Pass a `ManualIdle` to drive it by hand
+     */
+    pub fn new(source: Box<dyn IdleSource>) -> ActivityService {
+        ActivityService {
+            source,
+            available: false,
+        }
+    }
+
+    pub fn idle_time(&self) -> Option<Duration> {
+        match self.available {
+            true => self.source.idle_time(),
+            false => None,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available
+    }
+}
+
+impl Service for ActivityService {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        let result = self.source.start();
+        if result.is_err() {
+            self.source.stop();
+        }
+        self.available = result.is_ok();
+        result
+    }
+
+    fn stop(&mut self) {
+        self.source.stop();
+        self.available = false;
+    }
+}
@@ -0,0 +1,45 @@
+use super::IdleSource;
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+
+/**
+ * Idle time that only changes when told to, for tests and for platforms
+ * without a real source
+ *
+ * Clones share the same time, so keep one to `set` after handing
+ * another to an `ActivityService`
+ */
+#[derive(Clone)]
+pub struct ManualIdle {
+    idle: Arc<Mutex<Option<Duration>>>,
+}
+
+impl ManualIdle {
+    /**
+     * Starts out with the user right there
+     */
+    pub fn new() -> ManualIdle {
+        ManualIdle {
+            idle: Arc::new(Mutex::new(Some(Duration::from_secs(0)))),
+        }
+    }
+
+    /**
+     * None for a source that can't tell
+     */
+    pub fn set(&self, idle: Option<Duration>) {
+        *self.idle.lock() = idle;
+    }
+}
+
+impl Default for ManualIdle {
+    fn default() -> Self {
+        ManualIdle::new()
+    }
+}
+
+impl IdleSource for ManualIdle {
+    fn idle_time(&self) -> Option<Duration> {
+        *self.idle.lock()
+    }
+}
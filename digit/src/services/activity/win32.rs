@@ -0,0 +1,35 @@
+use super::IdleSource;
+use std::{mem, time::Duration};
+use winapi::um::{
+    sysinfoapi::GetTickCount,
+    winuser::{GetLastInputInfo, LASTINPUTINFO},
+};
+
+/**
+ * Asks Windows when the last input was, which covers every app in the
+ * session but not other sessions or remote desktops
+ */
+pub struct Win32Idle;
+
+impl Win32Idle {
+    pub fn new() -> Win32Idle {
+        Win32Idle
+    }
+}
+
+impl IdleSource for Win32Idle {
+    fn idle_time(&self) -> Option<Duration> {
+        let mut info = LASTINPUTINFO {
+            cbSize: mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        unsafe {
+            if GetLastInputInfo(&mut info) == 0 {
+                return None;
+            }
+            // Both are milliseconds since boot that wrap every 49 days
+            let idle = GetTickCount().wrapping_sub(info.dwTime);
+            Some(Duration::from_millis(idle as u64))
+        }
+    }
+}
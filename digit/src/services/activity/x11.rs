@@ -0,0 +1,107 @@
+use super::IdleSource;
+use crate::services::ServiceError;
+use std::{os::raw::c_void, ptr, time::Duration};
+use x11_dl::{
+    xlib::{Display, Xlib},
+    xss::{XScreenSaverInfo, Xss},
+};
+
+/**
+ * An open display and what's needed to ask it for the idle time
+ */
+struct Connection {
+    xlib: Xlib,
+    xss: Xss,
+    display: *mut Display,
+    info: *mut XScreenSaverInfo,
+}
+
+// The display is only ever used from behind the service's lock
+unsafe impl Send for Connection {}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xlib.XFree)(self.info as *mut c_void);
+            (self.xlib.XCloseDisplay)(self.display);
+        }
+    }
+}
+
+/**
+ * Asks the X server's screen saver extension, which sees input to every
+ * window on the display
+ *
+ * Wayland sessions without XWayland, or X servers without the
+ * extension, are unavailable
+ */
+pub struct X11Idle {
+    connection: Option<Connection>,
+}
+
+impl X11Idle {
+    pub fn new() -> X11Idle {
+        X11Idle { connection: None }
+    }
+}
+
+impl Default for X11Idle {
+    fn default() -> Self {
+        X11Idle::new()
+    }
+}
+
+impl IdleSource for X11Idle {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        self.connection = None;
+        let unavailable = |err: &dyn std::fmt::Display| ServiceError::Unavailable(err.to_string());
+        let xlib = Xlib::open().map_err(|err| unavailable(&err))?;
+        let xss = Xss::open().map_err(|err| unavailable(&err))?;
+        unsafe {
+            let display = (xlib.XOpenDisplay)(ptr::null());
+            if display.is_null() {
+                return Err(ServiceError::Unavailable(String::from(
+                    "can't open the X display",
+                )));
+            }
+            let (mut event_base, mut error_base) = (0, 0);
+            if (xss.XScreenSaverQueryExtension)(display, &mut event_base, &mut error_base) == 0 {
+                (xlib.XCloseDisplay)(display);
+                return Err(ServiceError::Unavailable(String::from(
+                    "X server has no screen saver extension",
+                )));
+            }
+            let info = (xss.XScreenSaverAllocInfo)();
+            if info.is_null() {
+                (xlib.XCloseDisplay)(display);
+                return Err(ServiceError::Failed(String::from(
+                    "can't allocate screen saver info",
+                )));
+            }
+            self.connection = Some(Connection {
+                xlib,
+                xss,
+                display,
+                info,
+            });
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.connection = None;
+    }
+
+    fn idle_time(&self) -> Option<Duration> {
+        let connection = self.connection.as_ref()?;
+        unsafe {
+            let root = (connection.xlib.XDefaultRootWindow)(connection.display);
+            if (connection.xss.XScreenSaverQueryInfo)(connection.display, root, connection.info)
+                == 0
+            {
+                return None;
+            }
+            Some(Duration::from_millis((*connection.info).idle))
+        }
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(windows)]
+mod loopback;
+mod pipe;
+mod wav;
+
+#[cfg(windows)]
+pub use loopback::LoopbackSource;
+pub use pipe::PipeSource;
+pub use wav::WavSource;
+
+use super::{Service, ServiceError};
+use dengine::beat::{Beat, BeatTracker};
+use parking_lot::Mutex;
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// Picks the audio source: `loopback` (the default, Windows only), `off`,
+// `wav:<file>` or `pipe:<file>`, where `-` is stdin
+const AUDIO_ENV: &str = "DIGIT_AUDIO";
+// No samples for this long and the beat is lost
+const STALE: Duration = Duration::from_millis(500);
+
+/**
+ * Called by a source with mono samples in -1 to 1 and their sample
+ * rate, from its own thread
+ */
+pub type Samples = Arc<dyn Fn(&[f32], u32) + Send + Sync>;
+
+/**
+ * Somewhere to hear what's playing from
+ */
+pub trait AudioSource: Send {
+    /**
+     * Start passing samples to `samples` until stopped
+     */
+    fn start(&mut self, samples: Samples) -> Result<(), ServiceError>;
+
+    fn stop(&mut self) {}
+}
+
+/**
+ * The source `DIGIT_AUDIO` asks for, None if it's off or nothing can
+ * be heard on this build
+ */
+pub fn system_source() -> Option<Box<dyn AudioSource>> {
+    let setting = env::var(AUDIO_ENV).unwrap_or_else(|_| String::from("loopback"));
+    if let Some(path) = setting.strip_prefix("wav:") {
+        return Some(Box::new(WavSource::new(path)));
+    }
+    if let Some(path) = setting.strip_prefix("pipe:") {
+        return Some(Box::new(PipeSource::new(path)));
+    }
+    match setting.as_str() {
+        "off" => None,
+        #[cfg(windows)]
+        "loopback" => Some(Box::new(LoopbackSource::new())),
+        #[cfg(not(windows))]
+        "loopback" => None,
+        other => {
+            eprintln!("{} {} isn't a source, not listening", AUDIO_ENV, other);
+            None
+        }
+    }
+}
+
+struct Analysis {
+    tracker: Option<BeatTracker>,
+    // When the last samples came in, the tracker's beat is as of then
+    heard: Option<Instant>,
+}
+
+/**
+ * Listens to what's playing and keeps track of the beat
+ *
+ * Without a source it still runs, the beat is just never known and
+ * anything dancing keeps its own pace
+ */
+pub struct AudioService {
+    source: Option<Box<dyn AudioSource>>,
+    analysis: Arc<Mutex<Analysis>>,
+}
+
+impl AudioService {
+    pub fn new(source: Option<Box<dyn AudioSource>>) -> AudioService {
+        AudioService {
+            source,
+            analysis: Arc::new(Mutex::new(Analysis {
+                tracker: None,
+                heard: None,
+            })),
+        }
+    }
+
+    /**
+     * The beat right now, None without a steady one
+     */
+    pub fn beat(&self) -> Option<Beat> {
+        let analysis = self.analysis.lock();
+        let since = analysis.heard?.elapsed();
+        if since > STALE {
+            return None;
+        }
+        let beat = analysis.tracker.as_ref()?.beat()?;
+        Some(beat.advance(since.as_secs_f32()))
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.source.is_some()
+    }
+}
+
+impl Service for AudioService {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        let source = match &mut self.source {
+            Some(source) => source,
+            None => {
+                eprintln!("no audio source, dancing at a fixed pace");
+                return Ok(());
+            }
+        };
+        // Weak so the source's thread doesn't keep the analysis alive
+        let analysis = Arc::downgrade(&self.analysis);
+        let samples: Samples = Arc::new(move |samples, sample_rate| {
+            if let Some(analysis) = analysis.upgrade() {
+                let mut analysis = analysis.lock();
+                let tracker = match &mut analysis.tracker {
+                    Some(tracker) if tracker.sample_rate() == sample_rate => tracker,
+                    tracker => tracker.insert(BeatTracker::new(sample_rate)),
+                };
+                tracker.feed(samples);
+                analysis.heard = Some(Instant::now());
+            }
+        });
+        let result = source.start(samples);
+        if result.is_err() {
+            source.stop();
+        }
+        result
+    }
+
+    fn stop(&mut self) {
+        if let Some(source) = &mut self.source {
+            source.stop();
+        }
+        let mut analysis = self.analysis.lock();
+        analysis.tracker = None;
+        analysis.heard = None;
+    }
+}
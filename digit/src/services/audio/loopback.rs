@@ -0,0 +1,107 @@
+use super::{AudioSource, Samples};
+use crate::services::ServiceError;
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    Sample, SampleFormat, StreamConfig,
+};
+use std::{
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+/**
+ * Whatever the default output device is playing
+ *
+ * Opens an input stream on the output device, which is loopback capture
+ * on WASAPI. Streams can't move between threads on every platform, so
+ * one thread opens the stream and holds it until stopped.
+ */
+pub struct LoopbackSource {
+    stop: Option<Sender<()>>,
+}
+
+impl LoopbackSource {
+    pub fn new() -> LoopbackSource {
+        LoopbackSource { stop: None }
+    }
+}
+
+impl AudioSource for LoopbackSource {
+    fn start(&mut self, samples: Samples) -> Result<(), ServiceError> {
+        self.stop();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        thread::spawn(move || {
+            let stream = match open(samples) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    let _ = started_tx.send(Err(err));
+                    return;
+                }
+            };
+            let _ = started_tx.send(Ok(()));
+            // Returns once stopped, or the source is dropped
+            let _ = stop_rx.recv();
+            drop(stream);
+        });
+        match started_rx.recv() {
+            Ok(Ok(())) => {
+                self.stop = Some(stop_tx);
+                Ok(())
+            }
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(ServiceError::Failed(String::from(
+                "loopback thread died while opening",
+            ))),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+fn open(samples: Samples) -> Result<cpal::Stream, ServiceError> {
+    let unavailable = |err: &dyn std::fmt::Display| ServiceError::Unavailable(err.to_string());
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| ServiceError::Unavailable(String::from("no output device")))?;
+    let supported = device
+        .default_output_config()
+        .map_err(|err| unavailable(&err))?;
+    let format = supported.sample_format();
+    let config: StreamConfig = supported.into();
+    let channels = config.channels as usize;
+    let sample_rate = config.sample_rate.0;
+    let report = |err| eprintln!("loopback: {}", err);
+    let stream = match format {
+        SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |data: &[f32], _: &_| samples(&mono(data, channels), sample_rate),
+            report,
+        ),
+        SampleFormat::I16 => device.build_input_stream(
+            &config,
+            move |data: &[i16], _: &_| samples(&mono(data, channels), sample_rate),
+            report,
+        ),
+        SampleFormat::U16 => device.build_input_stream(
+            &config,
+            move |data: &[u16], _: &_| samples(&mono(data, channels), sample_rate),
+            report,
+        ),
+    }
+    .map_err(|err| unavailable(&err))?;
+    stream
+        .play()
+        .map_err(|err| ServiceError::Failed(err.to_string()))?;
+    Ok(stream)
+}
+
+fn mono<S: Sample>(data: &[S], channels: usize) -> Vec<f32> {
+    data.chunks(channels.max(1))
+        .map(|frame| frame.iter().map(Sample::to_f32).sum::<f32>() / frame.len() as f32)
+        .collect()
+}
@@ -0,0 +1,85 @@
+use super::{AudioSource, Samples};
+use crate::services::ServiceError;
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: usize = 2;
+// Frames read at a time, about 10ms
+const FRAMES: usize = 441;
+
+/**
+ * Raw 16 bit little endian stereo at 44.1kHz from a file, named pipe or
+ * stdin (`-`), so any tool that can capture audio can feed the beat
+ * tracker, like
+ *
+ * `parec --format=s16le --rate=44100 --channels=2 -d @DEFAULT_MONITOR@`
+ *
+ * Stops at the end of the stream.
+ */
+pub struct PipeSource {
+    path: PathBuf,
+    running: Option<Arc<AtomicBool>>,
+}
+
+impl PipeSource {
+    pub fn new<P: Into<PathBuf>>(path: P) -> PipeSource {
+        PipeSource {
+            path: path.into(),
+            running: None,
+        }
+    }
+}
+
+impl AudioSource for PipeSource {
+    fn start(&mut self, samples: Samples) -> Result<(), ServiceError> {
+        self.stop();
+        let mut reader: Box<dyn Read + Send> = match self.path.to_str() {
+            Some("-") => Box::new(io::stdin()),
+            _ => Box::new(File::open(&self.path).map_err(|err| {
+                ServiceError::Unavailable(format!("can't open {}: {}", self.path.display(), err))
+            })?),
+        };
+
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = Some(running.clone());
+        thread::spawn(move || {
+            let mut bytes = vec![0; FRAMES * CHANNELS * 2];
+            // Exits on the read after being stopped, reads block until then
+            while running.load(Ordering::SeqCst) {
+                if let Err(err) = reader.read_exact(&mut bytes) {
+                    if err.kind() != io::ErrorKind::UnexpectedEof {
+                        eprintln!("audio pipe: {}", err);
+                    }
+                    break;
+                }
+                let mono = bytes
+                    .chunks_exact(CHANNELS * 2)
+                    .map(|frame| {
+                        let sum = frame
+                            .chunks_exact(2)
+                            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                            .sum::<f32>();
+                        sum / CHANNELS as f32
+                    })
+                    .collect::<Vec<_>>();
+                samples(&mono, SAMPLE_RATE);
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use super::{AudioSource, Samples};
+use crate::services::ServiceError;
+use std::{
+    convert::TryInto,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// Samples are handed over this many times a second, like a sound card
+const CHUNKS_PER_SECOND: u32 = 100;
+
+/**
+ * Plays a WAV file to the beat tracker in real time, looping, for
+ * trying out dancing without any audio setup
+ *
+ * Takes 8, 16, 24 and 32 bit PCM or 32 bit float, any number of
+ * channels. Nothing is actually played out loud.
+ */
+pub struct WavSource {
+    path: PathBuf,
+    running: Option<Arc<AtomicBool>>,
+}
+
+impl WavSource {
+    pub fn new<P: Into<PathBuf>>(path: P) -> WavSource {
+        WavSource {
+            path: path.into(),
+            running: None,
+        }
+    }
+}
+
+impl AudioSource for WavSource {
+    fn start(&mut self, samples: Samples) -> Result<(), ServiceError> {
+        self.stop();
+        let data = fs::read(&self.path).map_err(|err| {
+            ServiceError::Unavailable(format!("can't read {}: {}", self.path.display(), err))
+        })?;
+        let (mono, sample_rate) = decode(&data).map_err(|err| {
+            ServiceError::Failed(format!("can't play {}: {}", self.path.display(), err))
+        })?;
+        if mono.is_empty() {
+            return Err(ServiceError::Failed(format!(
+                "{} is empty",
+                self.path.display()
+            )));
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = Some(running.clone());
+        thread::spawn(move || {
+            let chunk = (sample_rate / CHUNKS_PER_SECOND).max(1) as usize;
+            let every = Duration::from_secs_f64(chunk as f64 / sample_rate as f64);
+            // Paced against when it started so sleeps don't add up to drift
+            let mut next = Instant::now();
+            let mut position = 0;
+            while running.load(Ordering::SeqCst) {
+                let end = (position + chunk).min(mono.len());
+                samples(&mono[position..end], sample_rate);
+                position = if end == mono.len() { 0 } else { end };
+                next += every;
+                let now = Instant::now();
+                if next > now {
+                    thread::sleep(next - now);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/**
+ * Mono samples and sample rate out of a whole WAV file
+ */
+fn decode(data: &[u8]) -> Result<(Vec<f32>, u32), String> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(String::from("not a WAV file"));
+    }
+    let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+
+    let mut format = None;
+    let mut samples = None;
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let id = &data[at..at + 4];
+        let len = u32_at(at + 4) as usize;
+        let body = at + 8;
+        let end = (body + len).min(data.len());
+        match id {
+            b"fmt " if end - body >= 16 => {
+                let mut tag = u16_at(body);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real tag in its sub format
+                if tag == 0xfffe && end - body >= 26 {
+                    tag = u16_at(body + 24);
+                }
+                format = Some((tag, u16_at(body + 2), u32_at(body + 4), u16_at(body + 14)));
+            }
+            b"data" => samples = Some(&data[body..end]),
+            _ => (),
+        }
+        // Chunks are padded to an even length
+        at = body + len + (len & 1);
+    }
+    let (tag, channels, sample_rate, bits) = format.ok_or("no fmt chunk")?;
+    let samples = samples.ok_or("no data chunk")?;
+    if channels == 0 || sample_rate == 0 {
+        return Err(String::from("no channels"));
+    }
+
+    let width = (bits as usize + 7) / 8;
+    let decode_one: fn(&[u8]) -> f32 = match (tag, bits) {
+        (1, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+        (1, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (1, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0,
+        (1, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0,
+        (3, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        _ => return Err(format!("format {} at {} bits isn't supported", tag, bits)),
+    };
+    let frame = width * channels as usize;
+    let mono = samples
+        .chunks_exact(frame)
+        .map(|frame| {
+            let sum = frame.chunks_exact(width).map(decode_one).sum::<f32>();
+            sum / channels as f32
+        })
+        .collect();
+    Ok((mono, sample_rate))
+}
@@ -1,67 +1,438 @@
-use bindings::Windows::Foundation::TypedEventHandler;
-pub use bindings::Windows::Media::Control::{
-    GlobalSystemMediaTransportControlsSession as MediaSession,
-    GlobalSystemMediaTransportControlsSessionManager as MediaSessionManager,
-    GlobalSystemMediaTransportControlsSessionPlaybackStatus as MediaPlaybackStatus,
+#[cfg(windows)]
+mod gsmtc;
+mod manual;
+#[cfg(target_os = "linux")]
+mod mpris;
+
+#[cfg(windows)]
+pub use gsmtc::GsmtcSource;
+pub use manual::ManualSource;
+#[cfg(target_os = "linux")]
+pub use mpris::MprisSource;
+
+use super::{Lost, Service, ServiceError};
+use dengine::{
+    event::EventQueue,
+    observer::{Observers, Subscription},
 };
 use parking_lot::Mutex;
-use std::sync::Arc;
+use std::{fmt, sync::Arc, time::Duration};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+    /** No session, or one that's opening, closing or changing tracks */
+    Unknown,
+    /** The OS couldn't be asked, the source didn't start or was lost */
+    Unavailable,
+}
+
+/**
+ * What's playing, whatever the OS could tell
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /** How long the whole track is */
+    pub length: Option<Duration>,
+}
+
+impl Metadata {
+    /**
+     * Whether both are the same song, even if one knows more about it
+     */
+    pub fn same_track(&self, other: &Metadata) -> bool {
+        self.title == other.title && self.artist == other.artist && self.album == other.album
+    }
+
+    /**
+     * `Title - Artist`, or just the title, None without one
+     */
+    pub fn summary(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        match &self.artist {
+            Some(artist) => Some(format!("{} - {}", title, artist)),
+            None => Some(title.clone()),
+        }
+    }
+}
+
+/**
+ * Cover art as the OS handed it over, usually a PNG or JPEG
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thumbnail {
+    pub data: Vec<u8>,
+    /** MIME type, if the source said */
+    pub content_type: Option<String>,
+}
+
+/**
+ * Everything a `MediaSource` knows at one point in time
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaState {
+    /** App or player behind the current session, None if there isn't one */
+    pub session: Option<String>,
+    pub status: PlaybackStatus,
+    pub metadata: Option<Metadata>,
+}
+
+impl MediaState {
+    /**
+     * No session at all
+     */
+    pub fn none() -> MediaState {
+        MediaState {
+            session: None,
+            status: PlaybackStatus::Unknown,
+            metadata: None,
+        }
+    }
+
+    /**
+     * What's known when there's no source to ask
+     */
+    pub fn unavailable() -> MediaState {
+        MediaState {
+            status: PlaybackStatus::Unavailable,
+            ..MediaState::none()
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.status == PlaybackStatus::Playing
+    }
+
+    /**
+     * The session that counts out of several: the OS's `current` one if
+     * it's playing, or else whichever is playing, or else the current
+     * one, or else the first
+     */
+    pub fn pick(sessions: &[MediaState], current: Option<&str>) -> MediaState {
+        let is_current =
+            |state: &&MediaState| current.is_some() && state.session.as_deref() == current;
+        sessions
+            .iter()
+            .find(|state| is_current(state) && state.is_playing())
+            .or_else(|| sessions.iter().find(|state| state.is_playing()))
+            .or_else(|| sessions.iter().find(is_current))
+            .or_else(|| sessions.first())
+            .cloned()
+            .unwrap_or_else(MediaState::none)
+    }
+}
 
+/**
+ * What a media remote can ask a session to do
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    /** Play if paused, pause if playing */
+    Toggle,
+    Next,
+    Previous,
+    /** Jump to a point from the start of the track */
+    Seek(Duration),
+}
+
+impl fmt::Display for MediaCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaCommand::Play => write!(f, "play"),
+            MediaCommand::Pause => write!(f, "pause"),
+            MediaCommand::Toggle => write!(f, "play/pause"),
+            MediaCommand::Next => write!(f, "next"),
+            MediaCommand::Previous => write!(f, "previous"),
+            MediaCommand::Seek(position) => write!(f, "seek to {:.1}s", position.as_secs_f32()),
+        }
+    }
+}
+
+// Errors the media API gives when it isn't there at all: class not
+// registered, no such interface, not implemented, access denied
+#[cfg(windows)]
+const WINDOWS_UNAVAILABLE: [u32; 4] = [0x8004_0154, 0x8000_4002, 0x8000_4001, 0x8007_0005];
+
+#[cfg(windows)]
+impl From<windows::Error> for ServiceError {
+    fn from(err: windows::Error) -> ServiceError {
+        match WINDOWS_UNAVAILABLE.contains(&err.code().0) {
+            true => ServiceError::Unavailable(err.message()),
+            false => ServiceError::Failed(err.message()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<dbus::Error> for ServiceError {
+    fn from(err: dbus::Error) -> ServiceError {
+        let message = String::from(err.message().unwrap_or("D-Bus error"));
+        match err.name() {
+            Some("org.freedesktop.DBus.Error.NoServer")
+            | Some("org.freedesktop.DBus.Error.FileNotFound")
+            | Some("org.freedesktop.DBus.Error.NotSupported")
+            | Some("org.freedesktop.DBus.Error.ServiceUnknown") => {
+                ServiceError::Unavailable(message)
+            }
+            _ => ServiceError::Failed(message),
+        }
+    }
+}
+
+/**
+ * Called by a source with its new state whenever anything changes,
+ * possibly from another thread
+ */
+pub type Notify = Arc<dyn Fn(&MediaState) + Send + Sync>;
+
+/**
+ * Where the OS reports what media is playing
+ */
+pub trait MediaSource: Send {
+    /**
+     * Connect and report every change to `notify` until stopped, or
+     * to `lost` if the connection goes away
+     */
+    fn start(&mut self, notify: Notify, lost: Lost) -> Result<(), ServiceError>;
+
+    fn stop(&mut self) {}
+
+    /**
+     * The session media keys would control right now
+     */
+    fn current_session(&self) -> Option<String>;
+
+    fn playback_status(&self) -> PlaybackStatus;
+
+    fn metadata(&self) -> Option<Metadata>;
+
+    /**
+     * Every session the OS knows of, playing or not
+     */
+    fn sessions(&self) -> Vec<MediaState>;
+
+    /**
+     * How far into the track the current session is, asked fresh every
+     * call since it changes all the time
+     */
+    fn position(&self) -> Option<Duration> {
+        None
+    }
+
+    /**
+     * The current session's cover art, fetched on every call so cache
+     * it per track
+     */
+    fn thumbnail(&self) -> Option<Thumbnail> {
+        None
+    }
+
+    /**
+     * Send a command to the current session, waiting for it to answer
+     */
+    fn control(&self, command: MediaCommand) -> Result<(), ServiceError> {
+        Err(ServiceError::Unavailable(format!(
+            "this source can't {}",
+            command
+        )))
+    }
+
+    /**
+     * The current session's state, which is playing if any session is
+     */
+    fn state(&self) -> MediaState {
+        MediaState {
+            session: self.current_session(),
+            status: self.playback_status(),
+            metadata: self.metadata(),
+        }
+    }
+}
+
+/**
+ * The media source for this OS, one that never plays anything where
+ * there isn't one
+ */
+pub fn system_source() -> Box<dyn MediaSource> {
+    #[cfg(windows)]
+    let source = GsmtcSource::new();
+    #[cfg(target_os = "linux")]
+    let source = MprisSource::new();
+    #[cfg(not(any(windows, target_os = "linux")))]
+    let source = ManualSource::new();
+    Box::new(source)
+}
+
+#[derive(Clone)]
+struct Subscribers {
+    changes: Observers<MediaState>,
+    tracks: Observers<MediaState>,
+    // Track last given to `tracks`, to tell a new song from a pause
+    track: Arc<Mutex<Option<Metadata>>>,
+}
+
+impl Subscribers {
+    /**
+     * Posted rather than called, since sources can report while the
+     * service is locked, like from `start`
+     */
+    fn post(&self, state: &MediaState) {
+        self.changes.post(state.clone());
+        let new_track = {
+            let mut track = self.track.lock();
+            let same = match (&*track, &state.metadata) {
+                (Some(last), Some(metadata)) => last.same_track(metadata),
+                (None, None) => true,
+                _ => false,
+            };
+            if !same {
+                *track = state.metadata.clone();
+            }
+            !same
+        };
+        if new_track {
+            self.tracks.post(state.clone());
+        }
+    }
+}
+
+/**
+ * Follows a `MediaSource` and tells subscribers what's playing
+ *
+ * Until the source starts, its state is `MediaState::unavailable()` and
+ * nothing is ever playing
+ */
 pub struct MediaService {
-    _session_manager: Option<MediaSessionManager>,
-    callbacks: Vec<Box<dyn Fn(&Option<MediaSession>)>>,
+    source: Box<dyn MediaSource>,
+    subscribers: Subscribers,
+    available: bool,
+    lost: Lost,
 }
 
 impl MediaService {
     /**
-     * Only `services.rs` should be able to construct services
-     * 
-     * Comes prewrapped in an ArcMutex, you're welcome :)
-     */
-    pub(super) fn new() -> Arc<Mutex<MediaService>> {
-        let media_service = Arc::new(Mutex::new(MediaService {
-            _session_manager: None,
-            callbacks: Vec::new(),
-        }));
-
-        // Hang onto a weak reference 
-        let weak_media_service = Arc::downgrade(&media_service);
-
-        let session_manager = MediaSessionManager::RequestAsync().unwrap().get().unwrap();
-        let current_session_result = session_manager.GetCurrentSession();
-        if let Ok(session) = current_session_result {
-            session
-                .PlaybackInfoChanged(TypedEventHandler::new(move |session, _args| {
-                    if let Some(ms) = weak_media_service.upgrade() {
-                        ms.lock().notify_all(session);
-                    }
-                    Ok(())
-                }))
-                .unwrap();
-            media_service.lock()._session_manager = Some(session_manager);
+     * Pass a `ManualSource` to drive it by hand
+     */
+    pub fn new(source: Box<dyn MediaSource>) -> MediaService {
+        MediaService {
+            source,
+            subscribers: Subscribers {
+                changes: Observers::new(),
+                tracks: Observers::new(),
+                track: Arc::new(Mutex::new(None)),
+            },
+            available: false,
+            lost: Arc::new(|_| {}),
         }
-        media_service
     }
 
-    pub fn subscribe<F>(&mut self, callback: F)
+    /**
+     * Called on any change at all: playing, pausing, a new track or its
+     * length showing up late, on a thread of the service's own
+     */
+    pub fn subscribe<F>(&self, callback: F) -> Subscription
     where
-        F: 'static + Fn(&Option<MediaSession>),
+        F: 'static + Fn(&MediaState) + Send + Sync,
     {
-        self.callbacks.push(Box::new(callback));
+        self.subscribers.changes.subscribe(callback)
     }
 
-    pub fn notify_all(&self, session: &Option<MediaSession>) {
-        for c in &self.callbacks {
-            c(session);
+    /**
+     * Called only when the song changes, including to no song at all
+     */
+    pub fn subscribe_track<F>(&self, callback: F) -> Subscription
+    where
+        F: 'static + Fn(&MediaState) + Send + Sync,
+    {
+        self.subscribers.tracks.subscribe(callback)
+    }
+
+    /**
+     * Changes as `DEvent::Custom(MediaState)` on the game thread's queue
+     */
+    pub fn forward(&self, queue: &EventQueue) -> Subscription {
+        self.subscribers.changes.forward(queue)
+    }
+
+    pub fn notify_all(&self, state: &MediaState) {
+        self.subscribers.post(state);
+    }
+
+    pub fn state(&self) -> MediaState {
+        match self.available {
+            true => self.source.state(),
+            false => MediaState::unavailable(),
+        }
+    }
+
+    pub fn metadata(&self) -> Option<Metadata> {
+        self.state().metadata
+    }
+
+    pub fn position(&self) -> Option<Duration> {
+        match self.available {
+            true => self.source.position(),
+            false => None,
+        }
+    }
+
+    pub fn thumbnail(&self) -> Option<Thumbnail> {
+        match self.available {
+            true => self.source.thumbnail(),
+            false => None,
+        }
+    }
+
+    /**
+     * Ask the current session to play, pause, skip or seek
+     *
+     * Can take a moment for the player to answer, so call from a thread
+     * other than the one drawing Digit
+     */
+    pub fn control(&self, command: MediaCommand) -> Result<(), ServiceError> {
+        match self.available {
+            true => self.source.control(command),
+            false => Err(ServiceError::Unavailable(String::from(
+                "media source didn't start",
+            ))),
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available
+    }
+
+    pub fn source(&self) -> &dyn MediaSource {
+        self.source.as_ref()
+    }
+}
+
+impl Service for MediaService {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        let subscribers = self.subscribers.clone();
+        let notify: Notify = Arc::new(move |state| subscribers.post(state));
+        let result = self.source.start(notify, self.lost.clone());
+        if result.is_err() {
+            // Don't leave half a connection behind for the next attempt
+            self.source.stop();
         }
+        self.available = result.is_ok();
+        result
+    }
+
+    fn stop(&mut self) {
+        self.source.stop();
+        self.available = false;
     }
 
-    pub fn get_media_session(&self) -> Option<MediaSession> {
-        MediaSessionManager::RequestAsync()
-            .unwrap()
-            .get()
-            .unwrap()
-            .GetCurrentSession()
-            .ok()
+    fn on_lost(&mut self, lost: Lost) {
+        self.lost = lost;
     }
 }
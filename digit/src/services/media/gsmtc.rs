@@ -0,0 +1,420 @@
+use super::{MediaCommand, MediaSource, MediaState, Metadata, Notify, PlaybackStatus, Thumbnail};
+use crate::services::{Lost, ServiceError};
+use bindings::Windows::Foundation::{
+    DateTime, EventRegistrationToken, TimeSpan, TypedEventHandler,
+};
+use bindings::Windows::Media::Control::{
+    GlobalSystemMediaTransportControlsSession as MediaSession,
+    GlobalSystemMediaTransportControlsSessionManager as MediaSessionManager,
+    GlobalSystemMediaTransportControlsSessionPlaybackStatus as MediaPlaybackStatus,
+};
+use bindings::Windows::Storage::Streams::DataReader;
+use parking_lot::Mutex;
+use std::{
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use windows::HSTRING;
+
+// 100ns ticks between 1601, where WinRT's DateTime starts, and 1970
+const UNIX_EPOCH_TICKS: i64 = 116_444_736_000_000_000;
+
+struct Subscription {
+    session: MediaSession,
+    playback: EventRegistrationToken,
+    properties: EventRegistrationToken,
+}
+
+struct Shared {
+    session_manager: Option<MediaSessionManager>,
+    notify: Option<Notify>,
+    // Last state reported, so each change is only reported once
+    last: MediaState,
+}
+
+/**
+ * Windows' global system media transport controls, the same sessions
+ * the volume flyout shows
+ *
+ * Follows every session, resubscribing whenever apps open or close
+ * them, so a player started after Digit still counts
+ */
+pub struct GsmtcSource {
+    shared: Arc<Mutex<Shared>>,
+    // Locked for as long as subscriptions are being changed, so handlers
+    // racing to resubscribe take turns
+    subscriptions: Arc<Mutex<Vec<Subscription>>>,
+    // Tokens for the session manager's own events
+    watching: Option<(EventRegistrationToken, EventRegistrationToken)>,
+}
+
+impl GsmtcSource {
+    pub fn new() -> GsmtcSource {
+        GsmtcSource {
+            shared: Arc::new(Mutex::new(Shared {
+                session_manager: None,
+                notify: None,
+                last: MediaState::none(),
+            })),
+            subscriptions: Arc::new(Mutex::new(Vec::new())),
+            watching: None,
+        }
+    }
+
+    fn handle(&self) -> Handle {
+        Handle {
+            shared: Arc::downgrade(&self.shared),
+            subscriptions: Arc::downgrade(&self.subscriptions),
+        }
+    }
+
+    fn session_manager(&self) -> Option<MediaSessionManager> {
+        self.shared.lock().session_manager.clone()
+    }
+
+    /**
+     * The session `state` picked, for asking it more
+     */
+    fn picked_session(&self) -> Option<MediaSession> {
+        let session_manager = self.session_manager()?;
+        let name = manager_state(&session_manager).session?;
+        all_sessions(&session_manager)
+            .into_iter()
+            .find(|session| session_name(session).as_deref() == Some(name.as_str()))
+    }
+}
+
+impl MediaSource for GsmtcSource {
+    fn start(&mut self, notify: Notify, _lost: Lost) -> Result<(), ServiceError> {
+        self.stop();
+        let session_manager = MediaSessionManager::RequestAsync()?.get()?;
+        {
+            let mut shared = self.shared.lock();
+            shared.session_manager = Some(session_manager.clone());
+            shared.notify = Some(notify);
+        }
+
+        let handle = self.handle();
+        let sessions = session_manager.SessionsChanged(TypedEventHandler::new(
+            move |_manager: &Option<MediaSessionManager>, _args| {
+                handle.resubscribe();
+                handle.report();
+                Ok(())
+            },
+        ))?;
+        let handle = self.handle();
+        let current = session_manager.CurrentSessionChanged(TypedEventHandler::new(
+            move |_manager: &Option<MediaSessionManager>, _args| {
+                handle.report();
+                Ok(())
+            },
+        ))?;
+        self.watching = Some((sessions, current));
+
+        let handle = self.handle();
+        handle.resubscribe();
+        handle.report();
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        let session_manager = self.session_manager();
+        if let (Some(session_manager), Some((sessions, current))) =
+            (session_manager, self.watching.take())
+        {
+            let _ = session_manager.RemoveSessionsChanged(sessions);
+            let _ = session_manager.RemoveCurrentSessionChanged(current);
+        }
+        for subscription in self.subscriptions.lock().drain(..) {
+            subscription.unsubscribe();
+        }
+        let mut shared = self.shared.lock();
+        shared.session_manager = None;
+        shared.notify = None;
+        shared.last = MediaState::none();
+    }
+
+    fn current_session(&self) -> Option<String> {
+        self.state().session
+    }
+
+    fn playback_status(&self) -> PlaybackStatus {
+        self.state().status
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.state().metadata
+    }
+
+    fn sessions(&self) -> Vec<MediaState> {
+        match self.session_manager() {
+            Some(session_manager) => all_sessions(&session_manager)
+                .iter()
+                .map(session_state)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn state(&self) -> MediaState {
+        match self.session_manager() {
+            Some(session_manager) => manager_state(&session_manager),
+            None => MediaState::none(),
+        }
+    }
+
+    /**
+     * Sessions only report their position every so often, so this
+     * counts on from the last report while playing
+     */
+    fn position(&self) -> Option<Duration> {
+        let session = self.picked_session()?;
+        let timeline = session.GetTimelineProperties().ok()?;
+        let position = duration(timeline.Position().ok()?)?;
+        let length = timeline.EndTime().ok().and_then(duration);
+        if session_status(&session) != PlaybackStatus::Playing {
+            return Some(position);
+        }
+        let since = timeline
+            .LastUpdatedTime()
+            .ok()
+            .and_then(system_time)
+            .and_then(|updated| SystemTime::now().duration_since(updated).ok())
+            .unwrap_or_default();
+        match length {
+            Some(length) => Some((position + since).min(length)),
+            None => Some(position + since),
+        }
+    }
+
+    fn thumbnail(&self) -> Option<Thumbnail> {
+        let session = self.picked_session()?;
+        let properties = session.TryGetMediaPropertiesAsync().ok()?.get().ok()?;
+        let stream = properties
+            .Thumbnail()
+            .ok()?
+            .OpenReadAsync()
+            .ok()?
+            .get()
+            .ok()?;
+        let size = stream.Size().ok()? as u32;
+        let reader = DataReader::CreateDataReader(&stream).ok()?;
+        let read = reader.LoadAsync(size).ok()?.get().ok()?;
+        let mut data = vec![0; read as usize];
+        reader.ReadBytes(&mut data).ok()?;
+        Some(Thumbnail {
+            data,
+            content_type: stream.ContentType().ok().and_then(non_empty),
+        })
+    }
+
+    fn control(&self, command: MediaCommand) -> Result<(), ServiceError> {
+        let session = self
+            .picked_session()
+            .ok_or_else(|| ServiceError::Failed(String::from("no session to control")))?;
+        let request = match command {
+            MediaCommand::Play => session.TryPlayAsync(),
+            MediaCommand::Pause => session.TryPauseAsync(),
+            MediaCommand::Toggle => session.TryTogglePlayPauseAsync(),
+            MediaCommand::Next => session.TrySkipNextAsync(),
+            MediaCommand::Previous => session.TrySkipPreviousAsync(),
+            MediaCommand::Seek(position) => {
+                let ticks = (position.as_nanos() / 100).min(i64::MAX as u128) as i64;
+                session.TryChangePlaybackPositionAsync(ticks)
+            }
+        };
+        // Sessions answer false for what their player doesn't support
+        match request?.get()? {
+            true => Ok(()),
+            false => Err(ServiceError::Failed(format!(
+                "{} refused to {}",
+                session_name(&session).unwrap_or_default(),
+                command
+            ))),
+        }
+    }
+}
+
+/**
+ * What the OS's event handlers hold on to, weak so a stopped source
+ * isn't kept alive by them
+ */
+#[derive(Clone)]
+struct Handle {
+    shared: Weak<Mutex<Shared>>,
+    subscriptions: Weak<Mutex<Vec<Subscription>>>,
+}
+
+impl Handle {
+    /**
+     * Swap the subscriptions over to whatever sessions exist now
+     */
+    fn resubscribe(&self) {
+        let (shared, subscriptions) = match (self.shared.upgrade(), self.subscriptions.upgrade()) {
+            (Some(shared), Some(subscriptions)) => (shared, subscriptions),
+            _ => return,
+        };
+        let session_manager = match shared.lock().session_manager.clone() {
+            Some(session_manager) => session_manager,
+            None => return,
+        };
+        let mut subscriptions = subscriptions.lock();
+        for subscription in subscriptions.drain(..) {
+            subscription.unsubscribe();
+        }
+        for session in all_sessions(&session_manager) {
+            let handle = self.clone();
+            let playback = session.PlaybackInfoChanged(TypedEventHandler::new(
+                move |_session: &Option<MediaSession>, _args| {
+                    handle.report();
+                    Ok(())
+                },
+            ));
+            let handle = self.clone();
+            let properties = session.MediaPropertiesChanged(TypedEventHandler::new(
+                move |_session: &Option<MediaSession>, _args| {
+                    handle.report();
+                    Ok(())
+                },
+            ));
+            match (playback, properties) {
+                (Ok(playback), Ok(properties)) => subscriptions.push(Subscription {
+                    session,
+                    playback,
+                    properties,
+                }),
+                // A session closing as it's subscribed to, the next
+                // SessionsChanged will clear it up
+                (playback, properties) => {
+                    if let Ok(playback) = playback {
+                        let _ = session.RemovePlaybackInfoChanged(playback);
+                    }
+                    if let Ok(properties) = properties {
+                        let _ = session.RemoveMediaPropertiesChanged(properties);
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+     * Notify if the state across all sessions changed
+     */
+    fn report(&self) {
+        let shared = match self.shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let session_manager = match shared.lock().session_manager.clone() {
+            Some(session_manager) => session_manager,
+            None => return,
+        };
+        // Asking the sessions can take a moment, so not while locked
+        let state = manager_state(&session_manager);
+        let notify = {
+            let mut shared = shared.lock();
+            if shared.last == state {
+                return;
+            }
+            shared.last = state.clone();
+            shared.notify.clone()
+        };
+        if let Some(notify) = notify {
+            notify(&state);
+        }
+    }
+}
+
+impl Subscription {
+    fn unsubscribe(self) {
+        let _ = self.session.RemovePlaybackInfoChanged(self.playback);
+        let _ = self.session.RemoveMediaPropertiesChanged(self.properties);
+    }
+}
+
+fn all_sessions(session_manager: &MediaSessionManager) -> Vec<MediaSession> {
+    let sessions = match session_manager.GetSessions() {
+        Ok(sessions) => sessions,
+        Err(_) => return Vec::new(),
+    };
+    let count = sessions.Size().unwrap_or(0);
+    (0..count)
+        .filter_map(|index| sessions.GetAt(index).ok())
+        .collect()
+}
+
+fn manager_state(session_manager: &MediaSessionManager) -> MediaState {
+    let sessions = all_sessions(session_manager)
+        .iter()
+        .map(session_state)
+        .collect::<Vec<_>>();
+    let current = session_manager
+        .GetCurrentSession()
+        .ok()
+        .and_then(|session| session_name(&session));
+    MediaState::pick(&sessions, current.as_deref())
+}
+
+fn session_state(session: &MediaSession) -> MediaState {
+    MediaState {
+        session: session_name(session),
+        status: session_status(session),
+        metadata: session_metadata(session),
+    }
+}
+
+fn session_name(session: &MediaSession) -> Option<String> {
+    non_empty(session.SourceAppUserModelId().ok()?)
+}
+
+fn session_status(session: &MediaSession) -> PlaybackStatus {
+    let status = session
+        .GetPlaybackInfo()
+        .and_then(|info| info.PlaybackStatus());
+    match status {
+        Ok(MediaPlaybackStatus::Playing) => PlaybackStatus::Playing,
+        Ok(MediaPlaybackStatus::Paused) => PlaybackStatus::Paused,
+        Ok(MediaPlaybackStatus::Stopped) => PlaybackStatus::Stopped,
+        _ => PlaybackStatus::Unknown,
+    }
+}
+
+fn session_metadata(session: &MediaSession) -> Option<Metadata> {
+    let properties = session.TryGetMediaPropertiesAsync().ok()?.get().ok()?;
+    Some(Metadata {
+        title: properties.Title().ok().and_then(non_empty),
+        artist: properties.Artist().ok().and_then(non_empty),
+        album: properties.AlbumTitle().ok().and_then(non_empty),
+        length: session
+            .GetTimelineProperties()
+            .and_then(|timeline| timeline.EndTime())
+            .ok()
+            .and_then(duration)
+            .filter(|length| *length > Duration::from_secs(0)),
+    })
+}
+
+/**
+ * None for the negative spans sessions sometimes report
+ */
+fn duration(span: TimeSpan) -> Option<Duration> {
+    match span.Duration >= 0 {
+        true => Some(Duration::from_nanos(span.Duration as u64 * 100)),
+        false => None,
+    }
+}
+
+fn system_time(time: DateTime) -> Option<SystemTime> {
+    let since_unix = time.UniversalTime.checked_sub(UNIX_EPOCH_TICKS)?;
+    match since_unix >= 0 {
+        true => Some(UNIX_EPOCH + Duration::from_nanos(since_unix as u64 * 100)),
+        false => None,
+    }
+}
+
+fn non_empty(string: HSTRING) -> Option<String> {
+    match string.is_empty() {
+        true => None,
+        false => Some(string.to_string_lossy()),
+    }
+}
@@ -0,0 +1,113 @@
+use super::{MediaCommand, MediaSource, MediaState, Metadata, Notify, PlaybackStatus};
+use crate::services::{Lost, ServiceError};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+struct Shared {
+    state: MediaState,
+    notify: Option<Notify>,
+}
+
+/**
+ * Media that only changes when told to, for tests and for platforms
+ * without a real source
+ *
+ * Clones share the same state, so keep one to `set` after handing
+ * another to a `MediaService`
+ */
+#[derive(Clone)]
+pub struct ManualSource {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl ManualSource {
+    /**
+     * Starts out with no session
+     */
+    pub fn new() -> ManualSource {
+        ManualSource {
+            shared: Arc::new(Mutex::new(Shared {
+                state: MediaState::none(),
+                notify: None,
+            })),
+        }
+    }
+
+    /**
+     * Change the state, notifying if started and anything changed
+     */
+    pub fn set(&self, state: MediaState) {
+        let notify = {
+            let mut shared = self.shared.lock();
+            if shared.state == state {
+                return;
+            }
+            shared.state = state.clone();
+            shared.notify.clone()
+        };
+        if let Some(notify) = notify {
+            notify(&state);
+        }
+    }
+}
+
+impl Default for ManualSource {
+    fn default() -> Self {
+        ManualSource::new()
+    }
+}
+
+impl MediaSource for ManualSource {
+    fn start(&mut self, notify: Notify, _lost: Lost) -> Result<(), ServiceError> {
+        self.shared.lock().notify = Some(notify);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.shared.lock().notify = None;
+    }
+
+    fn current_session(&self) -> Option<String> {
+        self.shared.lock().state.session.clone()
+    }
+
+    fn playback_status(&self) -> PlaybackStatus {
+        self.shared.lock().state.status
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.shared.lock().state.metadata.clone()
+    }
+
+    fn sessions(&self) -> Vec<MediaState> {
+        let state = self.state();
+        match state.session {
+            Some(_) => vec![state],
+            None => Vec::new(),
+        }
+    }
+
+    fn state(&self) -> MediaState {
+        self.shared.lock().state.clone()
+    }
+
+    /**
+     * Plays and pauses the session if there is one, anything else is
+     * taken and ignored
+     */
+    fn control(&self, command: MediaCommand) -> Result<(), ServiceError> {
+        let mut state = self.state();
+        if state.session.is_none() {
+            return Err(ServiceError::Failed(String::from("no session to control")));
+        }
+        state.status = match (command, state.status) {
+            (MediaCommand::Play, _) => PlaybackStatus::Playing,
+            (MediaCommand::Pause, _) => PlaybackStatus::Paused,
+            (MediaCommand::Toggle, PlaybackStatus::Playing) => PlaybackStatus::Paused,
+            (MediaCommand::Toggle, _) => PlaybackStatus::Playing,
+            (_, status) => status,
+        };
+        self.set(state);
+        Ok(())
+    }
+}
@@ -0,0 +1,526 @@
+use super::{MediaCommand, MediaSource, MediaState, Metadata, Notify, PlaybackStatus, Thumbnail};
+use crate::services::{Lost, ServiceError};
+use dbus::{
+    arg::{PropMap, RefArg},
+    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection},
+    message::MatchRule,
+};
+use parking_lot::Mutex;
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const TIMEOUT: Duration = Duration::from_millis(500);
+// How long the listening thread waits for messages before checking
+// whether it was stopped
+const POLL: Duration = Duration::from_millis(250);
+
+/**
+ * MPRIS players on the D-Bus session bus
+ *
+ * Every player is its own session. MPRIS has no idea of a current one,
+ * so it's the first player that's playing, or else the first player.
+ */
+pub struct MprisSource {
+    sessions: Arc<Mutex<Vec<MediaState>>>,
+    running: Option<Arc<AtomicBool>>,
+    // Separate from the listening thread's, for asking players things
+    // on demand
+    conn: Option<Mutex<Connection>>,
+}
+
+impl MprisSource {
+    pub fn new() -> MprisSource {
+        MprisSource {
+            sessions: Arc::new(Mutex::new(Vec::new())),
+            running: None,
+            conn: None,
+        }
+    }
+
+    /**
+     * Bus name of the player `state` picked
+     */
+    fn picked_player(&self) -> Option<String> {
+        let session = self.state().session?;
+        Some(format!("{}{}", PLAYER_PREFIX, session))
+    }
+}
+
+impl Default for MprisSource {
+    fn default() -> Self {
+        MprisSource::new()
+    }
+}
+
+impl MediaSource for MprisSource {
+    fn start(&mut self, notify: Notify, lost: Lost) -> Result<(), ServiceError> {
+        self.stop();
+        // Connect here so a missing session bus is reported right away
+        let conn = Connection::new_session()
+            .map_err(|err| ServiceError::Unavailable(format!("no D-Bus session bus: {}", err)))?;
+        let changed = Arc::new(AtomicBool::new(false));
+
+        let properties_changed = changed.clone();
+        let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+            .with_path(PLAYER_PATH);
+        conn.add_match(rule, move |_: (String, PropMap, Vec<String>), _, _| {
+            properties_changed.store(true, Ordering::SeqCst);
+            true
+        })?;
+        // Players coming and going
+        let owner_changed = changed.clone();
+        let rule = MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged");
+        conn.add_match(rule, move |(name, _, _): (String, String, String), _, _| {
+            if name.starts_with(PLAYER_PREFIX) {
+                owner_changed.store(true, Ordering::SeqCst);
+            }
+            true
+        })?;
+
+        *self.sessions.lock() = query(&conn);
+        self.conn = Some(Mutex::new(Connection::new_session()?));
+
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = Some(running.clone());
+        let sessions = self.sessions.clone();
+        thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                if let Err(err) = conn.process(POLL) {
+                    // Lost the bus, so nothing can be playing any more
+                    sessions.lock().clear();
+                    notify(&MediaState::unavailable());
+                    if running.load(Ordering::SeqCst) {
+                        lost(ServiceError::from(err));
+                    }
+                    break;
+                }
+                if !changed.swap(false, Ordering::SeqCst) {
+                    continue;
+                }
+                let new = query(&conn);
+                let mut current = sessions.lock();
+                if *current != new {
+                    let state = MediaState::pick(&new, None);
+                    let changed = state != MediaState::pick(&current, None);
+                    *current = new;
+                    drop(current);
+                    if changed {
+                        notify(&state);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /**
+     * The listening thread notices within `POLL` and exits
+     */
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::SeqCst);
+        }
+        self.conn = None;
+    }
+
+    fn current_session(&self) -> Option<String> {
+        self.state().session
+    }
+
+    fn playback_status(&self) -> PlaybackStatus {
+        self.state().status
+    }
+
+    fn metadata(&self) -> Option<Metadata> {
+        self.state().metadata
+    }
+
+    fn sessions(&self) -> Vec<MediaState> {
+        self.sessions.lock().clone()
+    }
+
+    fn state(&self) -> MediaState {
+        MediaState::pick(&self.sessions.lock(), None)
+    }
+
+    fn position(&self) -> Option<Duration> {
+        let name = self.picked_player()?;
+        let conn = self.conn.as_ref()?.lock();
+        let player = conn.with_proxy(name, PLAYER_PATH, TIMEOUT);
+        let micros = player.get::<i64>(PLAYER_INTERFACE, "Position").ok()?;
+        microseconds(micros)
+    }
+
+    /**
+     * Only art players saved to a file, which is most of them
+     */
+    fn thumbnail(&self) -> Option<Thumbnail> {
+        let name = self.picked_player()?;
+        let conn = self.conn.as_ref()?.lock();
+        let player = conn.with_proxy(name, PLAYER_PATH, TIMEOUT);
+        let metadata = player.get::<PropMap>(PLAYER_INTERFACE, "Metadata").ok()?;
+        let url = metadata.get("mpris:artUrl")?.0.as_str()?.to_string();
+        let path = url.strip_prefix("file://")?;
+        Some(Thumbnail {
+            data: fs::read(path).ok()?,
+            content_type: None,
+        })
+    }
+
+    fn control(&self, command: MediaCommand) -> Result<(), ServiceError> {
+        let name = self
+            .picked_player()
+            .ok_or_else(|| ServiceError::Failed(String::from("no player to control")))?;
+        let conn = match &self.conn {
+            Some(conn) => conn.lock(),
+            None => return Err(ServiceError::Unavailable(String::from("not started"))),
+        };
+        let player = conn.with_proxy(name, PLAYER_PATH, TIMEOUT);
+        let method = match command {
+            MediaCommand::Play => "Play",
+            MediaCommand::Pause => "Pause",
+            MediaCommand::Toggle => "PlayPause",
+            MediaCommand::Next => "Next",
+            MediaCommand::Previous => "Previous",
+            MediaCommand::Seek(position) => {
+                // SetPosition only takes effect for the track it names
+                let metadata = player.get::<PropMap>(PLAYER_INTERFACE, "Metadata")?;
+                let track = metadata
+                    .get("mpris:trackid")
+                    .and_then(|value| value.0.as_str())
+                    .map(String::from)
+                    .ok_or_else(|| ServiceError::Failed(String::from("no track to seek in")))?;
+                let track = dbus::Path::new(track)
+                    .map_err(|err| ServiceError::Failed(format!("bad track id: {}", err)))?;
+                let micros = position.as_micros().min(i64::MAX as u128) as i64;
+                player.method_call::<(), _, _, _>(
+                    PLAYER_INTERFACE,
+                    "SetPosition",
+                    (track, micros),
+                )?;
+                return Ok(());
+            }
+        };
+        player.method_call::<(), _, _, _>(PLAYER_INTERFACE, method, ())?;
+        Ok(())
+    }
+}
+
+/**
+ * Ask every player what it's doing
+ */
+fn query(conn: &Connection) -> Vec<MediaState> {
+    let bus = conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", TIMEOUT);
+    let names: Result<(Vec<String>,), _> = bus.method_call("org.freedesktop.DBus", "ListNames", ());
+    let players = match names {
+        Ok((names,)) => names
+            .into_iter()
+            .filter(|name| name.starts_with(PLAYER_PREFIX))
+            .collect::<Vec<_>>(),
+        Err(_) => return Vec::new(),
+    };
+    players
+        .iter()
+        .map(|name| MediaState {
+            session: Some(String::from(&name[PLAYER_PREFIX.len()..])),
+            status: player_status(conn, name),
+            metadata: player_metadata(conn, name),
+        })
+        .collect()
+}
+
+fn player_status(conn: &Connection, name: &str) -> PlaybackStatus {
+    let player = conn.with_proxy(name, PLAYER_PATH, TIMEOUT);
+    match player
+        .get::<String>(PLAYER_INTERFACE, "PlaybackStatus")
+        .as_deref()
+    {
+        Ok("Playing") => PlaybackStatus::Playing,
+        Ok("Paused") => PlaybackStatus::Paused,
+        Ok("Stopped") => PlaybackStatus::Stopped,
+        _ => PlaybackStatus::Unknown,
+    }
+}
+
+fn player_metadata(conn: &Connection, name: &str) -> Option<Metadata> {
+    let player = conn.with_proxy(name, PLAYER_PATH, TIMEOUT);
+    let metadata = player.get::<PropMap>(PLAYER_INTERFACE, "Metadata").ok()?;
+    let text = |key: &str| {
+        metadata
+            .get(key)
+            .and_then(|value| value.0.as_str())
+            .filter(|text| !text.is_empty())
+            .map(String::from)
+    };
+    // Artists are a list in MPRIS
+    let artist = metadata
+        .get("xesam:artist")
+        .and_then(|value| value.0.as_iter())
+        .map(|artists| {
+            artists
+                .filter_map(|artist| artist.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .filter(|artist| !artist.is_empty());
+    Some(Metadata {
+        title: text("xesam:title"),
+        artist,
+        album: text("xesam:album"),
+        length: metadata
+            .get("mpris:length")
+            .and_then(|value| value.0.as_i64())
+            .and_then(microseconds)
+            .filter(|length| *length > Duration::from_secs(0)),
+    })
+}
+
+fn microseconds(micros: i64) -> Option<Duration> {
+    match micros >= 0 {
+        true => Some(Duration::from_micros(micros as u64)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{media::MediaService, Health, Services};
+    use dbus::{arg::Variant, channel::MatchingReceiver, Message};
+    use std::{
+        collections::HashMap,
+        env,
+        ffi::CString,
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::mpsc,
+        time::Instant,
+    };
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    /**
+     * A session bus of our own, gone when dropped
+     *
+     * libdbus reads the address once per process, so only one test can
+     * start one
+     */
+    struct Bus(Child);
+
+    impl Bus {
+        /**
+         * None if there's no `dbus-daemon` to run
+         */
+        fn start() -> Option<Bus> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim());
+            Some(Bus(daemon))
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /**
+     * Just enough of an MPRIS player to be found, asked and told what
+     * to do, logging the methods called on it
+     */
+    struct FakePlayer {
+        calls: Arc<Mutex<Vec<String>>>,
+        running: Arc<AtomicBool>,
+    }
+
+    impl FakePlayer {
+        fn start(name: &str) -> FakePlayer {
+            let conn = Connection::new_session().unwrap();
+            conn.request_name(format!("{}{}", PLAYER_PREFIX, name), false, true, true)
+                .unwrap();
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let logged = calls.clone();
+            let mut status = "Paused";
+            conn.start_receive(
+                MatchRule::new_method_call(),
+                Box::new(move |message: Message, conn: &Connection| {
+                    let member = message.member().map(|member| member.to_string());
+                    let reply = match member.as_deref().unwrap_or_default() {
+                        "Get" => {
+                            let (_, property): (String, String) = message.read2().unwrap();
+                            match property.as_str() {
+                                "PlaybackStatus" => {
+                                    message.method_return().append1(Variant(status))
+                                }
+                                "Metadata" => message.method_return().append1(Variant(metadata())),
+                                _ => message.error(
+                                    &"org.freedesktop.DBus.Error.UnknownProperty".into(),
+                                    &CString::new(property).unwrap(),
+                                ),
+                            }
+                        }
+                        "PlayPause" => {
+                            logged.lock().push(String::from("PlayPause"));
+                            status = match status {
+                                "Playing" => "Paused",
+                                _ => "Playing",
+                            };
+                            let changed = Message::new_signal(
+                                PLAYER_PATH,
+                                "org.freedesktop.DBus.Properties",
+                                "PropertiesChanged",
+                            )
+                            .unwrap()
+                            .append3(
+                                PLAYER_INTERFACE,
+                                PropMap::new(),
+                                Vec::<String>::new(),
+                            );
+                            let _ = conn.channel().send(changed);
+                            message.method_return()
+                        }
+                        "SetPosition" => {
+                            let (track, micros): (dbus::Path, i64) = message.read2().unwrap();
+                            logged
+                                .lock()
+                                .push(format!("SetPosition {} {}", track, micros));
+                            message.method_return()
+                        }
+                        _ => return true,
+                    };
+                    let _ = conn.channel().send(reply);
+                    true
+                }),
+            );
+            let running = Arc::new(AtomicBool::new(true));
+            let serving = running.clone();
+            thread::spawn(move || {
+                while serving.load(Ordering::SeqCst) {
+                    conn.process(Duration::from_millis(50)).unwrap();
+                }
+            });
+            FakePlayer { calls, running }
+        }
+
+        /**
+         * Leave the bus, like a player being closed
+         */
+        fn quit(&self) {
+            self.running.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn metadata() -> PropMap {
+        let mut metadata: PropMap = HashMap::new();
+        let mut insert = |key: &str, value: Box<dyn RefArg>| {
+            metadata.insert(String::from(key), Variant(value));
+        };
+        insert(
+            "mpris:trackid",
+            Box::new(dbus::Path::new("/org/example/track/1").unwrap()),
+        );
+        insert("xesam:title", Box::new(String::from("Digital Love")));
+        insert(
+            "xesam:artist",
+            Box::new(vec![String::from("Daft Punk"), String::from("Romanthony")]),
+        );
+        // Empty text counts as not known
+        insert("xesam:album", Box::<String>::default());
+        insert("mpris:length", Box::new(301_000_000i64));
+        metadata
+    }
+
+    #[test]
+    fn follows_controls_and_loses_players() {
+        let bus = match Bus::start() {
+            Some(bus) => bus,
+            None => {
+                eprintln!("no dbus-daemon to test against, skipping");
+                return;
+            }
+        };
+        let player = FakePlayer::start("fake");
+        let (sender, states) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let mut source = MprisSource::new();
+        source
+            .start(
+                Arc::new(move |state: &MediaState| {
+                    let _ = sender.lock().send(state.clone());
+                }),
+                Arc::new(|_| {}),
+            )
+            .unwrap();
+
+        let paused = MediaState {
+            session: Some(String::from("fake")),
+            status: PlaybackStatus::Paused,
+            metadata: Some(Metadata {
+                title: Some(String::from("Digital Love")),
+                artist: Some(String::from("Daft Punk, Romanthony")),
+                album: None,
+                length: Some(Duration::from_secs(301)),
+            }),
+        };
+        assert_eq!(source.sessions(), std::slice::from_ref(&paused));
+
+        source.control(MediaCommand::Toggle).unwrap();
+        let playing = states.recv_timeout(WAIT).unwrap();
+        assert_eq!(
+            playing,
+            MediaState {
+                status: PlaybackStatus::Playing,
+                ..paused
+            }
+        );
+        assert!(source.state().is_playing());
+
+        source
+            .control(MediaCommand::Seek(Duration::from_millis(1500)))
+            .unwrap();
+        assert_eq!(
+            *player.calls.lock(),
+            ["PlayPause", "SetPosition /org/example/track/1 1500000"]
+        );
+
+        player.quit();
+        assert_eq!(states.recv_timeout(WAIT).unwrap(), MediaState::none());
+        source.stop();
+
+        // The registry retries a service whose bus goes away
+        let services = Services::new().with(MediaService::new(Box::new(MprisSource::new())));
+        services.start();
+        assert_eq!(services.health::<MediaService>(), Some(Health::Running));
+        drop(bus);
+        let until = Instant::now() + WAIT;
+        while services.health::<MediaService>() == Some(Health::Running) {
+            assert!(Instant::now() < until, "never noticed the bus going");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(
+            services.health::<MediaService>(),
+            Some(Health::Unavailable { attempts: 1, .. })
+        ));
+        assert!(!services.lock::<MediaService>().unwrap().is_available());
+        services.stop();
+    }
+}
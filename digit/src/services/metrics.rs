@@ -0,0 +1,494 @@
+mod manual;
+#[cfg(target_os = "linux")]
+mod proc;
+#[cfg(windows)]
+mod win32;
+
+pub use manual::ManualMetrics;
+#[cfg(target_os = "linux")]
+pub use proc::ProcMetrics;
+#[cfg(windows)]
+pub use win32::Win32Metrics;
+
+use super::{Service, ServiceError};
+use dengine::{
+    event::EventQueue,
+    observer::{Observers, Subscription},
+};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// How often the source is read
+const INTERVAL: Duration = Duration::from_secs(2);
+// How often the sampling thread checks whether it was stopped
+const POLL: Duration = Duration::from_millis(250);
+// Seconds CPU use is averaged over, so a short spike isn't a pegged CPU
+const CPU_SMOOTHING: f32 = 10.0;
+// Thresholds come in pairs a bit apart, so a reading hovering around
+// one doesn't flip back and forth
+const CPU_BUSY: f32 = 0.85;
+const CPU_CALM: f32 = 0.6;
+const MEMORY_FULL: f32 = 0.9;
+const MEMORY_FREED: f32 = 0.8;
+const BATTERY_LOW: f32 = 0.2;
+const BATTERY_RECOVERED: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerState {
+    /** Running off the battery */
+    Battery,
+    Charging,
+    /** Plugged in with nothing left to charge */
+    Charged,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Power {
+    /** 0 to 1, None if the battery doesn't say */
+    pub charge: Option<f32>,
+    pub state: PowerState,
+}
+
+/**
+ * One reading of how the machine is doing
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    /** 0 to 1 over every core, since the reading before */
+    pub cpu: Option<f32>,
+    /** 0 to 1, memory in use out of all of it */
+    pub memory: Option<f32>,
+    /** None on machines without a battery */
+    pub power: Option<Power>,
+}
+
+impl Metrics {
+    /**
+     * Nothing known yet
+     */
+    pub fn unknown() -> Metrics {
+        Metrics {
+            cpu: None,
+            memory: None,
+            power: None,
+        }
+    }
+}
+
+/**
+ * A threshold crossed, sent once each time it happens
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricsEvent {
+    CpuBusy,
+    CpuCalm,
+    MemoryFull,
+    MemoryFreed,
+    BatteryLow,
+    BatteryRecovered,
+    PluggedIn,
+    Unplugged,
+    /** Done charging while plugged in */
+    Charged,
+}
+
+impl fmt::Display for MetricsEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MetricsEvent::CpuBusy => "cpu busy",
+            MetricsEvent::CpuCalm => "cpu calm",
+            MetricsEvent::MemoryFull => "memory full",
+            MetricsEvent::MemoryFreed => "memory freed",
+            MetricsEvent::BatteryLow => "battery low",
+            MetricsEvent::BatteryRecovered => "battery recovered",
+            MetricsEvent::PluggedIn => "plugged in",
+            MetricsEvent::Unplugged => "unplugged",
+            MetricsEvent::Charged => "charged",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+ * Somewhere to read the machine's load and battery from
+ */
+pub trait MetricsSource: Send {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    fn stop(&mut self) {}
+
+    /**
+     * Read everything now, with CPU use since the last call, which is
+     * None on the first one
+     */
+    fn sample(&mut self) -> Metrics;
+}
+
+/**
+ * The source for the platform being run on, one that never reads
+ * anything where there isn't any
+ */
+pub fn system_source() -> Box<dyn MetricsSource> {
+    #[cfg(windows)]
+    let source = Win32Metrics::new();
+    #[cfg(target_os = "linux")]
+    let source = ProcMetrics::new();
+    #[cfg(not(any(windows, target_os = "linux")))]
+    let source = ManualMetrics::new();
+    Box::new(source)
+}
+
+/**
+ * Which side of each threshold readings were on last, to tell when one
+ * is crossed
+ */
+struct Levels {
+    cpu: Option<f32>,
+    cpu_busy: bool,
+    memory_full: bool,
+    battery_low: bool,
+    power: Option<PowerState>,
+}
+
+impl Levels {
+    fn new() -> Levels {
+        Levels {
+            cpu: None,
+            cpu_busy: false,
+            memory_full: false,
+            battery_low: false,
+            power: None,
+        }
+    }
+
+    /**
+     * Events for every threshold `metrics` crossed, taken `seconds`
+     * after the last reading
+     */
+    fn update(&mut self, metrics: &Metrics, seconds: f32) -> Vec<MetricsEvent> {
+        let mut events = Vec::new();
+        if let Some(cpu) = metrics.cpu {
+            let alpha = (seconds / (seconds + CPU_SMOOTHING)).min(1.0);
+            self.cpu = Some(match self.cpu {
+                Some(average) => average + (cpu - average) * alpha,
+                None => cpu,
+            });
+        }
+        events.extend(cross(&mut self.cpu_busy, self.cpu, CPU_BUSY, CPU_CALM).map(
+            |busy| match busy {
+                true => MetricsEvent::CpuBusy,
+                false => MetricsEvent::CpuCalm,
+            },
+        ));
+        events.extend(
+            cross(
+                &mut self.memory_full,
+                metrics.memory,
+                MEMORY_FULL,
+                MEMORY_FREED,
+            )
+            .map(|full| match full {
+                true => MetricsEvent::MemoryFull,
+                false => MetricsEvent::MemoryFreed,
+            }),
+        );
+
+        // Low only counts on battery, so plugging in recovers it too,
+        // and it's how drained the battery is that has to go high
+        let power = metrics.power;
+        let drained = power.and_then(|power| match power.state {
+            PowerState::Battery => power.charge.map(|charge| 1.0 - charge),
+            _ => Some(0.0),
+        });
+        let (low, recovered) = (1.0 - BATTERY_LOW, 1.0 - BATTERY_RECOVERED);
+        events.extend(
+            cross(&mut self.battery_low, drained, low, recovered).map(|low| match low {
+                true => MetricsEvent::BatteryLow,
+                false => MetricsEvent::BatteryRecovered,
+            }),
+        );
+
+        // Plugging in and out only counts after the first reading
+        let state = power.map(|power| power.state);
+        if let (Some(last), Some(state)) = (self.power, state) {
+            match (last, state) {
+                (PowerState::Battery, PowerState::Charging)
+                | (PowerState::Battery, PowerState::Charged) => {
+                    events.push(MetricsEvent::PluggedIn)
+                }
+                (_, PowerState::Battery) if last != PowerState::Battery => {
+                    events.push(MetricsEvent::Unplugged)
+                }
+                _ => (),
+            }
+            if last == PowerState::Charging && state == PowerState::Charged {
+                events.push(MetricsEvent::Charged);
+            }
+        }
+        if state.is_some() {
+            self.power = state;
+        }
+        events
+    }
+}
+
+/**
+ * Whether `value` just went up to `high` or back below `low`, keeping
+ * track of which side it's on in `on`
+ */
+fn cross(on: &mut bool, value: Option<f32>, high: f32, low: f32) -> Option<bool> {
+    let value = value?;
+    if !*on && value >= high || *on && value < low {
+        *on = !*on;
+        Some(*on)
+    } else {
+        None
+    }
+}
+
+/**
+ * Keeps an eye on CPU, memory and the battery, and tells subscribers
+ * whenever one crosses a threshold
+ *
+ * Read on a thread of its own every couple of seconds, callbacks are
+ * called from it with nothing locked
+ */
+pub struct MetricsService {
+    source: Arc<Mutex<Box<dyn MetricsSource>>>,
+    latest: Arc<Mutex<Metrics>>,
+    observers: Observers<MetricsEvent>,
+    interval: Duration,
+    // Set while started, cleared to stop the sampling thread
+    running: Option<Arc<AtomicBool>>,
+}
+
+impl MetricsService {
+    /**
+     * Pass a `ManualMetrics` to drive it by hand
+     */
+    pub fn new(source: Box<dyn MetricsSource>) -> MetricsService {
+        MetricsService {
+            source: Arc::new(Mutex::new(source)),
+            latest: Arc::new(Mutex::new(Metrics::unknown())),
+            observers: Observers::new(),
+            interval: INTERVAL,
+            running: None,
+        }
+    }
+
+    /**
+     * Read the source this often instead, takes effect on the next start
+     */
+    pub fn interval(mut self, interval: Duration) -> MetricsService {
+        self.interval = interval;
+        self
+    }
+
+    /**
+     * The latest reading, unknown until started
+     */
+    pub fn metrics(&self) -> Metrics {
+        *self.latest.lock()
+    }
+
+    /**
+     * Called with every threshold crossed
+     */
+    pub fn subscribe<F>(&self, callback: F) -> Subscription
+    where
+        F: 'static + Fn(&MetricsEvent) + Send + Sync,
+    {
+        self.observers.subscribe(callback)
+    }
+
+    /**
+     * Threshold crossings as `DEvent::Custom(MetricsEvent)` on the game
+     * thread's queue
+     */
+    pub fn forward(&self, queue: &EventQueue) -> Subscription {
+        self.observers.forward(queue)
+    }
+}
+
+impl Service for MetricsService {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        let result = self.source.lock().start();
+        if let Err(err) = result {
+            self.source.lock().stop();
+            return Err(err);
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = Some(running.clone());
+        let source = self.source.clone();
+        let latest = self.latest.clone();
+        let observers = self.observers.clone();
+        let interval = self.interval;
+        thread::spawn(move || {
+            let mut levels = Levels::new();
+            let mut last = Instant::now();
+            loop {
+                let metrics = {
+                    let mut source = source.lock();
+                    // Checked with the source locked, so `stop` can't miss it
+                    if !running.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    source.sample()
+                };
+                *latest.lock() = metrics;
+                let now = Instant::now();
+                let events = levels.update(&metrics, (now - last).as_secs_f32());
+                last = now;
+                for event in &events {
+                    observers.notify(event);
+                }
+
+                let until = now + interval;
+                loop {
+                    if !running.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    let now = Instant::now();
+                    if now >= until {
+                        break;
+                    }
+                    thread::sleep(POLL.min(until - now));
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::SeqCst);
+        }
+        self.source.lock().stop();
+        *self.latest.lock() = Metrics::unknown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn power(state: PowerState, charge: f32) -> Metrics {
+        Metrics {
+            power: Some(Power {
+                charge: Some(charge),
+                state,
+            }),
+            ..Metrics::unknown()
+        }
+    }
+
+    /**
+     * Every reading's events, two seconds apart, with the index of the
+     * reading they came from
+     */
+    fn feed(readings: &[Metrics]) -> Vec<(usize, MetricsEvent)> {
+        let mut levels = Levels::new();
+        readings
+            .iter()
+            .enumerate()
+            .flat_map(|(index, metrics)| {
+                let events = levels.update(metrics, 2.0);
+                events.into_iter().map(move |event| (index, event))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn thresholds_have_room_to_hover() {
+        let readings = [0.85, 0.92, 0.85, 0.88, 0.79, 0.85, 0.9]
+            .iter()
+            .map(|&memory| Metrics {
+                memory: Some(memory),
+                ..Metrics::unknown()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            feed(&readings),
+            [
+                (1, MetricsEvent::MemoryFull),
+                (4, MetricsEvent::MemoryFreed),
+                (6, MetricsEvent::MemoryFull),
+            ]
+        );
+    }
+
+    #[test]
+    fn cpu_is_smoothed_over_seconds() {
+        // Idle, pegged for half a minute, then idle again
+        let readings = (0..30)
+            .map(|index| Metrics {
+                cpu: Some(match index {
+                    1..=15 => 1.0,
+                    _ => 0.0,
+                }),
+                ..Metrics::unknown()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            feed(&readings),
+            [(11, MetricsEvent::CpuBusy), (18, MetricsEvent::CpuCalm)]
+        );
+    }
+
+    #[test]
+    fn tells_plugging_in_and_out_apart_from_charging() {
+        let readings = [
+            power(PowerState::Battery, 0.5),
+            power(PowerState::Charging, 0.5),
+            power(PowerState::Charged, 1.0),
+            power(PowerState::Battery, 1.0),
+            // Plugged in already full is no news
+            power(PowerState::Charged, 1.0),
+            Metrics::unknown(),
+            power(PowerState::Battery, 1.0),
+        ];
+        assert_eq!(
+            feed(&readings),
+            [
+                (1, MetricsEvent::PluggedIn),
+                (2, MetricsEvent::Charged),
+                (3, MetricsEvent::Unplugged),
+                (4, MetricsEvent::PluggedIn),
+                (6, MetricsEvent::Unplugged),
+            ]
+        );
+    }
+
+    #[test]
+    fn plugging_in_recovers_a_low_battery() {
+        let readings = [
+            power(PowerState::Battery, 0.3),
+            power(PowerState::Battery, 0.19),
+            power(PowerState::Battery, 0.22),
+            power(PowerState::Charging, 0.22),
+            power(PowerState::Battery, 0.22),
+            power(PowerState::Battery, 0.15),
+        ];
+        assert_eq!(
+            feed(&readings),
+            [
+                (1, MetricsEvent::BatteryLow),
+                (3, MetricsEvent::BatteryRecovered),
+                (3, MetricsEvent::PluggedIn),
+                (4, MetricsEvent::Unplugged),
+                (5, MetricsEvent::BatteryLow),
+            ]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use super::{Metrics, MetricsSource, Power, PowerState};
+use std::{fs, path::Path};
+
+const STAT: &str = "/proc/stat";
+const MEMINFO: &str = "/proc/meminfo";
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+/**
+ * Reads `/proc` for CPU and memory and `/sys/class/power_supply` for
+ * batteries and chargers
+ *
+ * With several batteries the charge is their average, and a missing
+ * file only leaves its reading unknown
+ */
+pub struct ProcMetrics {
+    // Busy and total jiffies at the last sample
+    last_cpu: Option<(u64, u64)>,
+}
+
+impl ProcMetrics {
+    pub fn new() -> ProcMetrics {
+        ProcMetrics { last_cpu: None }
+    }
+}
+
+impl Default for ProcMetrics {
+    fn default() -> Self {
+        ProcMetrics::new()
+    }
+}
+
+impl MetricsSource for ProcMetrics {
+    fn sample(&mut self) -> Metrics {
+        let times = fs::read_to_string(STAT)
+            .ok()
+            .and_then(|stat| cpu_times(&stat));
+        let cpu = match (self.last_cpu, times) {
+            (Some((last_busy, last_total)), Some((busy, total))) if total > last_total => {
+                Some(busy.saturating_sub(last_busy) as f32 / (total - last_total) as f32)
+            }
+            _ => None,
+        };
+        self.last_cpu = times;
+        Metrics {
+            cpu,
+            memory: fs::read_to_string(MEMINFO)
+                .ok()
+                .and_then(|meminfo| memory_used(&meminfo)),
+            power: power(Path::new(POWER_SUPPLY)),
+        }
+    }
+}
+
+/**
+ * Busy and total jiffies across every core, from the first line of
+ * `/proc/stat`
+ */
+fn cpu_times(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let times = line
+        .split_whitespace()
+        .skip(1)
+        .map(|time| time.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    // user nice system idle iowait irq softirq steal, guest time is
+    // already counted in user
+    let total = times.iter().take(8).sum::<u64>();
+    let idle = times.get(3)? + times.get(4).unwrap_or(&0);
+    Some((total - idle, total))
+}
+
+/**
+ * Share of memory in use, counting what could be freed for other
+ * programs as available
+ */
+fn memory_used(meminfo: &str) -> Option<f32> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse::<u64>().ok())
+    };
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+    match total {
+        0 => None,
+        _ => Some(1.0 - available.min(total) as f32 / total as f32),
+    }
+}
+
+/**
+ * Batteries and whether anything is charging them, None if there are
+ * no batteries
+ */
+fn power(dir: &Path) -> Option<Power> {
+    let read = |path: &Path, name: &str| {
+        fs::read_to_string(path.join(name))
+            .ok()
+            .map(|value| String::from(value.trim()))
+    };
+    let mut charges = Vec::new();
+    let mut statuses = Vec::new();
+    let mut plugged = false;
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        match read(&path, "type").as_deref() {
+            Some("Battery") => {
+                // Peripherals like mice report batteries too
+                if read(&path, "scope").as_deref() == Some("Device") {
+                    continue;
+                }
+                if let Some(capacity) = read(&path, "capacity").and_then(|c| c.parse::<f32>().ok())
+                {
+                    charges.push((capacity / 100.0).clamp(0.0, 1.0));
+                }
+                statuses.push(read(&path, "status").unwrap_or_default());
+            }
+            Some(_) => plugged |= read(&path, "online").as_deref() == Some("1"),
+            None => (),
+        }
+    }
+    if statuses.is_empty() {
+        return None;
+    }
+    let charge = match charges.is_empty() {
+        true => None,
+        false => Some(charges.iter().sum::<f32>() / charges.len() as f32),
+    };
+    // "Not charging" is plugged in but held below full to spare the battery
+    let state = if statuses.iter().any(|status| status == "Charging") {
+        PowerState::Charging
+    } else if statuses
+        .iter()
+        .all(|status| status == "Full" || status == "Not charging")
+        || plugged
+    {
+        PowerState::Charged
+    } else {
+        PowerState::Battery
+    };
+    Some(Power { charge, state })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, path::PathBuf, process};
+
+    const STAT_SAMPLE: &str = "\
+cpu  4705 150 1120 16250 520 0 25 0 30 0
+cpu0 2350 75 560 8125 260 0 12 0 15 0
+intr 1462898 0 0 0
+ctxt 2556946
+";
+
+    const MEMINFO_SAMPLE: &str = "\
+MemTotal:       16000000 kB
+MemFree:         2000000 kB
+MemAvailable:    4000000 kB
+Buffers:          500000 kB
+";
+
+    /**
+     * A fake `/sys/class/power_supply`, removed again when dropped
+     */
+    struct Supplies(PathBuf);
+
+    impl Supplies {
+        fn new(name: &str, devices: &[(&str, &[(&str, &str)])]) -> Supplies {
+            let dir = env::temp_dir().join(format!("digit-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for (device, files) in devices {
+                fs::create_dir_all(dir.join(device)).unwrap();
+                for (file, value) in files.iter() {
+                    fs::write(dir.join(device).join(file), format!("{}\n", value)).unwrap();
+                }
+            }
+            fs::create_dir_all(&dir).unwrap();
+            Supplies(dir)
+        }
+    }
+
+    impl Drop for Supplies {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn reads_cpu_and_memory() {
+        // Idle and iowait aren't busy, guest time is already in user
+        assert_eq!(cpu_times(STAT_SAMPLE), Some((6000, 22770)));
+        assert_eq!(cpu_times("cpu0 1 2 3 4\n"), None);
+        assert_eq!(cpu_times("cpu  1 2 x 4\n"), None);
+        let used = memory_used(MEMINFO_SAMPLE).unwrap();
+        assert!((used - 0.75).abs() < 1e-6);
+        assert_eq!(memory_used("MemTotal: 0 kB\nMemAvailable: 0 kB\n"), None);
+        assert_eq!(memory_used("MemFree: 10 kB\n"), None);
+    }
+
+    #[test]
+    fn reads_batteries_and_chargers() {
+        let laptop = Supplies::new(
+            "laptop",
+            &[
+                ("AC", &[("type", "Mains"), ("online", "0")]),
+                (
+                    "BAT0",
+                    &[
+                        ("type", "Battery"),
+                        ("capacity", "40"),
+                        ("status", "Discharging"),
+                    ],
+                ),
+                (
+                    "BAT1",
+                    &[
+                        ("type", "Battery"),
+                        ("capacity", "120"),
+                        ("status", "Unknown"),
+                    ],
+                ),
+                // A wireless mouse's battery doesn't power the machine
+                (
+                    "hidpp_battery_0",
+                    &[
+                        ("type", "Battery"),
+                        ("scope", "Device"),
+                        ("capacity", "5"),
+                        ("status", "Charging"),
+                    ],
+                ),
+            ],
+        );
+        let reading = power(&laptop.0).unwrap();
+        assert_eq!(reading.state, PowerState::Battery);
+        assert!((reading.charge.unwrap() - 0.7).abs() < 1e-6);
+
+        let held = Supplies::new(
+            "held",
+            &[(
+                "BAT0",
+                &[
+                    ("type", "Battery"),
+                    ("capacity", "80"),
+                    ("status", "Not charging"),
+                ],
+            )],
+        );
+        assert_eq!(power(&held.0).unwrap().state, PowerState::Charged);
+
+        let charging = Supplies::new(
+            "charging",
+            &[
+                ("AC", &[("type", "Mains"), ("online", "1")]),
+                ("BAT0", &[("type", "Battery"), ("status", "Charging")]),
+            ],
+        );
+        let reading = power(&charging.0).unwrap();
+        assert_eq!(reading.state, PowerState::Charging);
+        assert_eq!(reading.charge, None);
+
+        let desktop = Supplies::new("desktop", &[("AC", &[("type", "Mains"), ("online", "1")])]);
+        assert_eq!(power(&desktop.0), None);
+        assert_eq!(power(&desktop.0.join("missing")), None);
+    }
+}
@@ -0,0 +1,101 @@
+use super::{Metrics, MetricsSource, Power, PowerState};
+use std::mem;
+use winapi::{
+    shared::minwindef::FILETIME,
+    um::{
+        processthreadsapi::GetSystemTimes,
+        sysinfoapi::{GlobalMemoryStatusEx, MEMORYSTATUSEX},
+        winbase::{GetSystemPowerStatus, SYSTEM_POWER_STATUS},
+    },
+};
+
+// SYSTEM_POWER_STATUS flags and values
+const AC_ONLINE: u8 = 1;
+const BATTERY_CHARGING: u8 = 8;
+const NO_BATTERY: u8 = 128;
+const UNKNOWN: u8 = 255;
+
+/**
+ * Asks Windows for system times, memory status and power status
+ */
+pub struct Win32Metrics {
+    // Busy and total 100ns ticks at the last sample
+    last_cpu: Option<(u64, u64)>,
+}
+
+impl Win32Metrics {
+    pub fn new() -> Win32Metrics {
+        Win32Metrics { last_cpu: None }
+    }
+}
+
+impl MetricsSource for Win32Metrics {
+    fn sample(&mut self) -> Metrics {
+        let times = cpu_times();
+        let cpu = match (self.last_cpu, times) {
+            (Some((last_busy, last_total)), Some((busy, total))) if total > last_total => {
+                Some(busy.saturating_sub(last_busy) as f32 / (total - last_total) as f32)
+            }
+            _ => None,
+        };
+        self.last_cpu = times;
+        Metrics {
+            cpu,
+            memory: memory_used(),
+            power: power(),
+        }
+    }
+}
+
+/**
+ * Busy and total time across every core, kernel time includes idle
+ */
+fn cpu_times() -> Option<(u64, u64)> {
+    let ticks = |time: FILETIME| (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+    unsafe {
+        let mut idle = mem::zeroed();
+        let mut kernel = mem::zeroed();
+        let mut user = mem::zeroed();
+        if GetSystemTimes(&mut idle, &mut kernel, &mut user) == 0 {
+            return None;
+        }
+        let total = ticks(kernel) + ticks(user);
+        Some((total.saturating_sub(ticks(idle)), total))
+    }
+}
+
+fn memory_used() -> Option<f32> {
+    unsafe {
+        let mut status: MEMORYSTATUSEX = mem::zeroed();
+        status.dwLength = mem::size_of::<MEMORYSTATUSEX>() as u32;
+        if GlobalMemoryStatusEx(&mut status) == 0 || status.ullTotalPhys == 0 {
+            return None;
+        }
+        Some(1.0 - status.ullAvailPhys as f32 / status.ullTotalPhys as f32)
+    }
+}
+
+fn power() -> Option<Power> {
+    let status = unsafe {
+        let mut status: SYSTEM_POWER_STATUS = mem::zeroed();
+        if GetSystemPowerStatus(&mut status) == 0 {
+            return None;
+        }
+        status
+    };
+    if status.BatteryFlag == UNKNOWN || status.BatteryFlag & NO_BATTERY != 0 {
+        return None;
+    }
+    let charge = match status.BatteryLifePercent {
+        UNKNOWN => None,
+        percent => Some((percent as f32 / 100.0).min(1.0)),
+    };
+    let state = if status.BatteryFlag & BATTERY_CHARGING != 0 {
+        PowerState::Charging
+    } else if status.ACLineStatus == AC_ONLINE {
+        PowerState::Charged
+    } else {
+        PowerState::Battery
+    };
+    Some(Power { charge, state })
+}
@@ -0,0 +1,130 @@
+use super::{DanceState, IdleState, InteractState, PantState, ReactState, SleepState, YawnState};
+use crate::{
+    services::{media::MediaCommand, metrics::MetricsEvent},
+    Digit,
+};
+use dengine::{
+    event::DEvent,
+    fsm::{short_type_name, DState, StateMachine, Transition},
+};
+
+/**
+ * Parent of everything Digit does while awake: idling, walking and
+ * dancing
+ *
+ * Clicks, music and the machine's load and battery are handled here
+ * once instead of in every child, anything else is left to the active
+ * child. When the behavior file loaded, its states are the children and
+ * handle everything instead, except gestures while there's a song, which
+ * always control it, and the machine's load and battery, which its
+ * states can also check as flags.
+ * Either way he falls asleep once the user has been away for a while,
+ * unless there's music to dance to.
+ */
+pub struct AwakeState {
+    children: StateMachine<Digit>,
+    scripted: bool,
+}
+
+impl DState<Digit> for AwakeState {
+    fn enter(sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        let children = StateMachine::new();
+        if let Some(tracer) = sm.tracer() {
+            children.set_tracer(tracer);
+        }
+        let behaviors = digit.behaviors().cloned();
+        let scripted = behaviors.is_some();
+        if let Some(behaviors) = behaviors {
+            behaviors.init(&children, digit);
+        } else if digit.is_dancing() {
+            children.init::<DanceState>(digit);
+        } else {
+            children.init::<IdleState>(digit);
+        }
+        Box::new(Self { children, scripted })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if digit.is_user_away() && !digit.is_dancing() {
+            return Some(Transition::to::<SleepState>().because("user is away"));
+        }
+        // Music starts a dance from anything, DanceState stops it itself
+        let dance = short_type_name::<DanceState>();
+        if !self.scripted
+            && digit.is_dancing()
+            && self.children.current_state_name().as_deref() != Some(dance)
+        {
+            if let Some(transition) = self.children.transition(
+                digit,
+                Transition::to::<DanceState>().because("music started"),
+            ) {
+                return Some(transition);
+            }
+        }
+        self.children.update(digit, delta)
+    }
+
+    fn handle_event(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        event: &DEvent,
+    ) -> Option<Transition<Digit>> {
+        if digit.has_media() {
+            if digit.is_clicked(event) {
+                return Some(InteractState::clicked());
+            }
+            if let Some(delta) = digit.scrolled(event) {
+                let command = match delta > 0.0 {
+                    true => MediaCommand::Previous,
+                    false => MediaCommand::Next,
+                };
+                return Some(InteractState::command(command));
+            }
+        }
+        if let Some(transition) = react_to_metrics(digit, event) {
+            return Some(transition);
+        }
+        if !self.scripted && digit.is_clicked(event) {
+            Some(Transition::push::<ReactState>().because("clicked"))
+        } else {
+            self.children.handle_event(digit, event)
+        }
+    }
+
+    fn exit(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        self.children.shutdown(digit);
+    }
+
+    fn pause(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        self.children.pause(digit);
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        self.children.resume(digit);
+    }
+
+    fn child(&self) -> Option<&StateMachine<Digit>> {
+        Some(&self.children)
+    }
+}
+
+/**
+ * Pant when the CPU is pegged, yawn at a low battery and celebrate a
+ * full one
+ */
+fn react_to_metrics(digit: &mut Digit, event: &DEvent) -> Option<Transition<Digit>> {
+    match event.as_custom::<MetricsEvent>()? {
+        MetricsEvent::CpuBusy => Some(Transition::push::<PantState>().because("cpu is pegged")),
+        MetricsEvent::BatteryLow => Some(YawnState::sleepy("battery is low")),
+        MetricsEvent::Charged => {
+            digit.say("all charged!", 3.0);
+            Some(Transition::push::<ReactState>().because("done charging"))
+        }
+        _ => None,
+    }
+}
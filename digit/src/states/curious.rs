@@ -0,0 +1,117 @@
+use super::IdleState;
+use crate::Digit;
+use dengine::{
+    bt::{Action, BehaviorTree, Condition, Selector, Sequence, Status, Tick, Timeout},
+    fsm::{DState, StateMachine, Transition},
+};
+
+/**
+ * Walks up to the cursor and sniffs it, then either wags at it if
+ * he's in a good mood or wanders off
+ *
+ * Goes back to idling once the tree finishes, or right away if the
+ * cursor can't be found or reached in time
+ */
+pub struct CuriousState {
+    tree: BehaviorTree<Digit>,
+}
+
+const SPEED: f32 = 200.0;
+// How close to the cursor counts as there, in world pixels
+const REACH: f32 = 48.0;
+
+impl DState<Digit> for CuriousState {
+    fn enter(_sm: &StateMachine<Digit>, _digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        let approach = Action::new(|digit: &mut Digit, tick: &Tick, _| {
+            let target = match cursor_x(digit) {
+                Some(target) => target,
+                None => return Status::Failure,
+            };
+            let x = digit.sprite().x;
+            if (target - x).abs() <= REACH {
+                return Status::Success;
+            }
+            digit.set_flipped(target < x);
+            digit.sprite_mut().x += (target - x).signum() * SPEED * tick.delta;
+            Status::Running
+        })
+        .on_start(|digit| digit.set_anim("walking"));
+
+        let sniff = Action::new(|_digit: &mut Digit, _: &Tick, elapsed| until(elapsed, 1.5))
+            .on_start(|digit| digit.set_anim("idle"));
+
+        let wag = Sequence::new()
+            .then(Condition::new(|digit: &Digit| digit.mood() > 0.6))
+            .then(
+                Action::new(|_digit: &mut Digit, _: &Tick, elapsed| until(elapsed, 1.5))
+                    .on_start(|digit| digit.set_anim("wagging")),
+            );
+
+        let leave = Action::new(|digit: &mut Digit, tick: &Tick, elapsed| {
+            let direction = if digit.sprite().flipped() { -1.0 } else { 1.0 };
+            step_within(digit, direction * SPEED * tick.delta);
+            until(elapsed, 1.5)
+        })
+        .on_start(|digit| {
+            // Turn around, away from the cursor he just sniffed
+            let flipped = !digit.sprite().flipped();
+            digit.set_flipped(flipped);
+            digit.set_anim("walking");
+        });
+
+        let tree = Sequence::new()
+            .then(Timeout::new(5.0, approach))
+            .then(sniff)
+            .then(Selector::new().or(wag).or(leave));
+        Box::new(Self {
+            tree: BehaviorTree::new(tree),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        match self.tree.tick(digit, delta) {
+            Status::Running => None,
+            Status::Success => Some(Transition::to::<IdleState>().because("done sniffing")),
+            Status::Failure => Some(Transition::to::<IdleState>().because("lost the cursor")),
+        }
+    }
+}
+
+/**
+ * Where Digit's left edge needs to be for him to stand on the cursor
+ */
+fn cursor_x(digit: &Digit) -> Option<f32> {
+    let (x, _) = digit.cursor()?;
+    let (width, _) = digit.sprite().size()?;
+    Some(x as f32 - width as f32 * digit.scale() / 2.0)
+}
+
+/**
+ * Move Digit along, but not out of the work area, nor at all if
+ * there's no telling where it ends
+ */
+fn step_within(digit: &mut Digit, dx: f32) {
+    let area = match digit.work_area() {
+        Some(area) => area,
+        None => return,
+    };
+    let width = match digit.sprite().size() {
+        Some((width, _)) => width as f32 * digit.scale(),
+        None => 0.0,
+    };
+    let left = area.left as f32;
+    let right = (area.right as f32 - width).max(left);
+    let x = digit.sprite().x + dx;
+    digit.sprite_mut().x = x.max(left).min(right);
+}
+
+fn until(elapsed: f32, duration: f32) -> Status {
+    match elapsed >= duration {
+        true => Status::Success,
+        false => Status::Running,
+    }
+}
@@ -0,0 +1,31 @@
+use super::IdleState;
+use crate::Digit;
+use dengine::fsm::{DState, StateMachine, Transition};
+
+/**
+ * Dances for as long as media is playing, entered by `AwakeState`
+ */
+pub struct DanceState;
+
+impl DState<Digit> for DanceState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("dancing");
+        Box::new(Self)
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        _delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if digit.is_dancing() {
+            None
+        } else {
+            Some(Transition::to::<IdleState>().because("music stopped"))
+        }
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("dancing");
+    }
+}
@@ -0,0 +1,40 @@
+use crate::Digit;
+use dengine::{
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * Sound asleep, snoring every so often, until `SleepState` wakes him
+ */
+pub struct DozeState {
+    snore: Timer,
+}
+
+// Seconds between snores, and how long each one shows
+const SNORE_EVERY: f32 = 4.0;
+const SNORE_SECONDS: f32 = 2.0;
+
+impl DState<Digit> for DozeState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("dozing");
+        Box::new(Self {
+            snore: Timer::repeating(SNORE_EVERY),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if self.snore.tick(delta) {
+            digit.say("z z z", SNORE_SECONDS);
+        }
+        None
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("dozing");
+    }
+}
@@ -1,42 +1,51 @@
-use super::WalkState;
+use super::{CuriousState, ReactState, WalkState};
 use crate::Digit;
-use dengine::fsm::{DState, StateMachine};
-use std::time::Instant;
+use dengine::{
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+};
 
+/**
+ * Waits a bit, then lets `Digit::choose` decide between walking off,
+ * checking out the cursor, resting some more or a happy wag
+ */
 pub struct IdleState {
-    start: Instant,
-    duration: f32,
-    is_dancing: bool,
+    // Ticked from update deltas so it stops while interrupted
+    timer: Timer,
 }
 
 impl DState<Digit> for IdleState {
     fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
-        digit.anims().set_anim("ready");
+        digit.set_anim("ready");
         Box::new(Self {
-            start: Instant::now(),
-            duration: rand::random::<f32>() * 10.0 + 1.0,
-            is_dancing: false,
+            timer: Timer::new(random_duration(digit)),
         })
     }
     fn update(
-        mut self: Box<Self>,
-        sm: &StateMachine<Digit>,
+        &mut self,
+        _sm: &StateMachine<Digit>,
         digit: &mut Digit,
-        _delta: f32,
-    ) -> Box<dyn DState<Digit>> {
-        if self.start.elapsed().as_millis() as f32 / 1000.0 > self.duration {
-            sm.transit::<WalkState>(digit)
-        } else {
-        let is_now_dancing = digit.is_dancing();
-        if is_now_dancing != self.is_dancing {
-            self.is_dancing = is_now_dancing;
-            if is_now_dancing {
-                digit.anims().set_anim("dancing");
-            } else {
-                digit.anims().set_anim("ready");
-            }
-        }
-        self
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if !self.timer.tick(delta) {
+            return None;
         }
+        self.timer.restart_with(random_duration(digit));
+        let scores = digit.choose();
+        let transition = match scores.chosen() {
+            Some("walk") => Transition::to::<WalkState>(),
+            Some("curious") => Transition::to::<CuriousState>(),
+            Some("wag") => Transition::push::<ReactState>(),
+            _ => return None,
+        };
+        Some(transition.because(scores.to_string()))
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("ready");
     }
 }
+
+fn random_duration(digit: &mut Digit) -> f32 {
+    digit.rng().range(1.0, 11.0)
+}
@@ -0,0 +1,109 @@
+use crate::{services::media::MediaCommand, Digit};
+use dengine::{
+    event::DEvent,
+    fsm::{short_type_name, DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * Digit as a tiny media remote
+ *
+ * Pushed when he's clicked or scrolled while there's a song. A click
+ * waits a moment for a second one: a double click skips ahead, a single
+ * one plays or pauses. Scrolling up goes back a track and down skips
+ * ahead right away. Either way he acts it out, then pops back to
+ * whatever he was doing.
+ */
+pub struct InteractState {
+    // Waiting for a second click, None once the command was sent
+    waiting: Option<Timer>,
+    timer: Timer,
+}
+
+// Seconds a second click has to count as a double click
+const DOUBLE_CLICK: f32 = 0.3;
+const DURATION: f32 = 1.0;
+
+impl InteractState {
+    /**
+     * After a click, wait to see if it's a double click
+     */
+    pub fn clicked() -> Transition<Digit> {
+        Transition::push::<InteractState>().because("clicked")
+    }
+
+    /**
+     * Send a command right away, for gestures that can't be anything else
+     */
+    pub fn command(command: MediaCommand) -> Transition<Digit> {
+        let name = short_type_name::<InteractState>();
+        Transition::push_with(name, move |_sm, digit: &mut Digit| {
+            let mut state = InteractState {
+                waiting: None,
+                timer: Timer::new(DURATION),
+            };
+            state.send(digit, command);
+            Box::new(state)
+        })
+        .because(format!("asked to {}", command))
+    }
+
+    fn send(&mut self, digit: &mut Digit, command: MediaCommand) {
+        self.waiting = None;
+        digit.control_media(command);
+        // Act out what he asked the player to do
+        match command {
+            MediaCommand::Toggle if digit.is_dancing() => digit.set_anim("idle"),
+            MediaCommand::Toggle | MediaCommand::Play => digit.set_anim("dancing"),
+            MediaCommand::Pause => digit.set_anim("idle"),
+            MediaCommand::Next | MediaCommand::Seek(_) => {
+                digit.set_anim("walking");
+                digit.set_flipped(false);
+            }
+            MediaCommand::Previous => {
+                digit.set_anim("walking");
+                digit.set_flipped(true);
+            }
+        }
+    }
+}
+
+impl DState<Digit> for InteractState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("ready");
+        Box::new(InteractState {
+            waiting: Some(Timer::new(DOUBLE_CLICK)),
+            timer: Timer::new(DURATION),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if let Some(waiting) = &mut self.waiting {
+            if waiting.tick(delta) {
+                self.send(digit, MediaCommand::Toggle);
+            }
+            return None;
+        }
+        if self.timer.tick(delta) {
+            Some(Transition::pop().because("done"))
+        } else {
+            None
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        event: &DEvent,
+    ) -> Option<Transition<Digit>> {
+        if self.waiting.is_some() && digit.is_clicked(event) {
+            self.send(digit, MediaCommand::Next);
+        }
+        None
+    }
+}
@@ -0,0 +1,40 @@
+use super::DozeState;
+use crate::Digit;
+use dengine::{
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * Settles down in a comfy spot, then dozes off
+ */
+pub struct LieDownState {
+    timer: Timer,
+}
+
+const DURATION: f32 = 1.5;
+
+impl DState<Digit> for LieDownState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("lying");
+        Box::new(Self {
+            timer: Timer::new(DURATION),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        _digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if self.timer.tick(delta) {
+            Some(Transition::to::<DozeState>().because("lying down"))
+        } else {
+            None
+        }
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("lying");
+    }
+}
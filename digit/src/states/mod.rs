@@ -1,5 +1,38 @@
+mod awake;
+pub use awake::AwakeState;
+
+mod curious;
+pub use curious::CuriousState;
+
+mod dance;
+pub use dance::DanceState;
+
+mod doze;
+pub use doze::DozeState;
+
 mod idle;
 pub use idle::IdleState;
 
+mod interact;
+pub use interact::InteractState;
+
+mod lie_down;
+pub use lie_down::LieDownState;
+
+mod pant;
+pub use pant::PantState;
+
+mod react;
+pub use react::ReactState;
+
+mod sleep;
+pub use sleep::SleepState;
+
 mod walk;
 pub use walk::WalkState;
+
+mod wake;
+pub use wake::WakeState;
+
+mod yawn;
+pub use yawn::YawnState;
@@ -0,0 +1,44 @@
+use crate::Digit;
+use dengine::{
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * Huffs and puffs when the CPU gets pegged, as if he were the one doing
+ * all that work
+ *
+ * Pushed on top of whatever Digit was doing, and pops back to it when
+ * done
+ */
+pub struct PantState {
+    timer: Timer,
+}
+
+const DURATION: f32 = 3.0;
+
+impl DState<Digit> for PantState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("ready");
+        digit.say("huff... puff...", DURATION);
+        Box::new(Self {
+            timer: Timer::new(DURATION),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        _digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if self.timer.tick(delta) {
+            Some(Transition::pop().because("caught his breath"))
+        } else {
+            None
+        }
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("ready");
+    }
+}
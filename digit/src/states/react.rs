@@ -0,0 +1,38 @@
+use crate::Digit;
+use dengine::{
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * Short happy wiggle when Digit gets clicked
+ *
+ * Always pushed on top of whatever Digit was doing, and pops back to
+ * it when done
+ */
+pub struct ReactState {
+    timer: Timer,
+}
+
+const DURATION: f32 = 1.5;
+
+impl DState<Digit> for ReactState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("wagging");
+        Box::new(Self {
+            timer: Timer::new(DURATION),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        _digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if self.timer.tick(delta) {
+            Some(Transition::pop().because("done wagging"))
+        } else {
+            None
+        }
+    }
+}
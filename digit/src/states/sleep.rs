@@ -0,0 +1,71 @@
+use super::{WakeState, YawnState};
+use crate::Digit;
+use dengine::{
+    event::DEvent,
+    fsm::{DState, StateMachine, Transition},
+};
+
+/**
+ * Parent of everything Digit does while asleep: yawning, lying down
+ * and dozing
+ *
+ * Entered from `AwakeState` once the user has been away long enough,
+ * and wakes up as soon as they're back, music starts or he's clicked
+ */
+pub struct SleepState {
+    children: StateMachine<Digit>,
+}
+
+impl DState<Digit> for SleepState {
+    fn enter(sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        let children = StateMachine::new();
+        if let Some(tracer) = sm.tracer() {
+            children.set_tracer(tracer);
+        }
+        children.init::<YawnState>(digit);
+        Box::new(Self { children })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if digit.is_dancing() {
+            return Some(Transition::to::<WakeState>().because("music started"));
+        }
+        if !digit.is_user_away() {
+            return Some(Transition::to::<WakeState>().because("user came back"));
+        }
+        self.children.update(digit, delta)
+    }
+
+    fn handle_event(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        digit: &mut Digit,
+        event: &DEvent,
+    ) -> Option<Transition<Digit>> {
+        if digit.is_clicked(event) {
+            Some(Transition::to::<WakeState>().because("clicked"))
+        } else {
+            self.children.handle_event(digit, event)
+        }
+    }
+
+    fn exit(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        self.children.shutdown(digit);
+    }
+
+    fn pause(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        self.children.pause(digit);
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        self.children.resume(digit);
+    }
+
+    fn child(&self) -> Option<&StateMachine<Digit>> {
+        Some(&self.children)
+    }
+}
@@ -0,0 +1,41 @@
+use super::AwakeState;
+use crate::Digit;
+use dengine::{
+    fsm::{DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * Jolts up and shakes off the sleep before getting back to being awake
+ */
+pub struct WakeState {
+    timer: Timer,
+}
+
+const DURATION: f32 = 1.5;
+
+impl DState<Digit> for WakeState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("wagging");
+        digit.say("!", DURATION);
+        Box::new(Self {
+            timer: Timer::new(DURATION),
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        _digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        if self.timer.tick(delta) {
+            Some(Transition::to::<AwakeState>().because("awake"))
+        } else {
+            None
+        }
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("wagging");
+    }
+}
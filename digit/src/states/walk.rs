@@ -1,6 +1,6 @@
 use super::IdleState;
-use crate::{get_monitorinfo, Digit};
-use dengine::fsm::{DState, StateMachine};
+use crate::Digit;
+use dengine::fsm::{DState, StateMachine, Transition};
 
 pub struct WalkState {
     walking_right: bool,
@@ -11,13 +11,12 @@ const SPEED: f32 = 400.0;
 
 impl DState<Digit> for WalkState {
     fn enter(sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
-        let mi_opt = get_monitorinfo(digit.window().x as i32, digit.window().y as i32);
-        if let Some(mi) = mi_opt {
-            let length = mi.rcWork.right - mi.rcWork.left;
-            let destination = (rand::random::<f32>() * length as f32) as i32 + mi.rcWork.left;
-            let walking_right = digit.window().x < destination as f32;
-            digit.anims().set_anim("walking");
-            digit.anims().set_flipped(!walking_right);
+        if let Some(area) = digit.work_area() {
+            let length = area.right - area.left;
+            let destination = (digit.rng().f32() * length as f32) as i32 + area.left;
+            let walking_right = digit.sprite().x < destination as f32;
+            digit.set_anim("walking");
+            digit.set_flipped(!walking_right);
             Box::new(Self {
                 walking_right,
                 destination,
@@ -27,11 +26,11 @@ impl DState<Digit> for WalkState {
         }
     }
     fn update(
-        self: Box<Self>,
-        sm: &StateMachine<Digit>,
+        &mut self,
+        _sm: &StateMachine<Digit>,
         digit: &mut Digit,
         delta: f32,
-    ) -> Box<dyn DState<Digit>> {
+    ) -> Option<Transition<Digit>> {
         // positive if dest is to the right
         // negative if dest is to the left
         let direction = match self.walking_right {
@@ -39,13 +38,18 @@ impl DState<Digit> for WalkState {
             false => -1.0,
         };
 
-        digit.window_mut().x += direction * delta * SPEED;
+        digit.sprite_mut().x += direction * delta * SPEED;
 
-        if (self.destination as f32 - digit.window().x).signum() as i8 != direction as i8 {
-            digit.window_mut().x = self.destination as f32;
-            sm.transit::<IdleState>(digit)
+        if (self.destination as f32 - digit.sprite().x).signum() as i8 != direction as i8 {
+            digit.sprite_mut().x = self.destination as f32;
+            Some(Transition::to::<IdleState>().because("arrived"))
         } else {
-            self
+            None
         }
     }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("walking");
+        digit.set_flipped(!self.walking_right);
+    }
 }
@@ -0,0 +1,64 @@
+use super::LieDownState;
+use crate::Digit;
+use dengine::{
+    fsm::{short_type_name, DState, StateMachine, Transition},
+    timer::Timer,
+};
+
+/**
+ * A big yawn before lying down, first thing `SleepState` does
+ *
+ * Also pushed on its own by `sleepy`, to yawn and go back to whatever he
+ * was doing
+ */
+pub struct YawnState {
+    timer: Timer,
+    lie_down: bool,
+}
+
+const DURATION: f32 = 2.5;
+
+impl YawnState {
+    /**
+     * Just a yawn, popping back once done instead of lying down
+     */
+    pub fn sleepy(reason: &str) -> Transition<Digit> {
+        let name = short_type_name::<YawnState>();
+        Transition::push_with(name, |_sm, digit: &mut Digit| {
+            digit.set_anim("yawning");
+            digit.say("getting sleepy...", DURATION);
+            Box::new(YawnState {
+                timer: Timer::new(DURATION),
+                lie_down: false,
+            })
+        })
+        .because(reason)
+    }
+}
+
+impl DState<Digit> for YawnState {
+    fn enter(_sm: &StateMachine<Digit>, digit: &mut Digit) -> Box<dyn DState<Digit>> {
+        digit.set_anim("yawning");
+        digit.say("yawn...", DURATION);
+        Box::new(Self {
+            timer: Timer::new(DURATION),
+            lie_down: true,
+        })
+    }
+    fn update(
+        &mut self,
+        _sm: &StateMachine<Digit>,
+        _digit: &mut Digit,
+        delta: f32,
+    ) -> Option<Transition<Digit>> {
+        match (self.timer.tick(delta), self.lie_down) {
+            (true, true) => Some(Transition::to::<LieDownState>().because("done yawning")),
+            (true, false) => Some(Transition::pop().because("done yawning")),
+            (false, _) => None,
+        }
+    }
+
+    fn resume(&mut self, _sm: &StateMachine<Digit>, digit: &mut Digit) {
+        digit.set_anim("yawning");
+    }
+}
@@ -1,4 +1,7 @@
+use crate::replay::WorkArea;
+#[cfg(windows)]
 use std::mem::{self, MaybeUninit};
+#[cfg(windows)]
 use winapi::{
     shared::windef::{DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, POINT},
     um::winuser::{
@@ -7,12 +10,25 @@ use winapi::{
     },
 };
 
+// Where the bottom of the usable screen is assumed to be without a way
+// to ask the OS
+#[cfg(not(windows))]
+const FALLBACK_BOTTOM: i32 = 1080;
+
+#[cfg(windows)]
 pub fn set_process_dpi_aware() {
     unsafe {
         SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
     }
 }
 
+/**
+ * Only Windows needs telling, everything else reports real sizes anyway
+ */
+#[cfg(not(windows))]
+pub fn set_process_dpi_aware() {}
+
+#[cfg(windows)]
 pub fn get_monitorinfo(x: i32, y: i32) -> Option<MONITORINFO> {
     unsafe {
         let hmonitor = MonitorFromPoint(POINT { x, y }, MONITOR_DEFAULTTONULL);
@@ -27,6 +43,28 @@ pub fn get_monitorinfo(x: i32, y: i32) -> Option<MONITORINFO> {
     }
 }
 
+/**
+ * Usable area of the monitor containing a point, without the taskbar
+ */
+#[cfg(windows)]
+pub fn get_work_area(x: i32, y: i32) -> Option<WorkArea> {
+    get_monitorinfo(x, y).map(|mi| WorkArea {
+        left: mi.rcWork.left,
+        top: mi.rcWork.top,
+        right: mi.rcWork.right,
+        bottom: mi.rcWork.bottom,
+    })
+}
+
+/**
+ * Not known outside Windows yet, so Digit stays put
+ */
+#[cfg(not(windows))]
+pub fn get_work_area(_x: i32, _y: i32) -> Option<WorkArea> {
+    None
+}
+
+#[cfg(windows)]
 pub fn get_taskbar_height() -> i32 {
     unsafe {
         let hmonitor = MonitorFromPoint(POINT { x: 0, y: 0 }, MONITOR_DEFAULTTOPRIMARY);
@@ -36,3 +74,8 @@ pub fn get_taskbar_height() -> i32 {
         mi.assume_init().rcWork.bottom
     }
 }
+
+#[cfg(not(windows))]
+pub fn get_taskbar_height() -> i32 {
+    FALLBACK_BOTTOM
+}